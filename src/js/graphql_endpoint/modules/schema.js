@@ -68,6 +68,8 @@ const FileType = new GraphQLObjectType({
         sha256_hash: {type: GraphQLString},
         risks: {type: GraphQLList(RiskType)},
         file_path: {type: GraphQLString},
+        changed_creation_timestamp: {type: GraphQLInt},
+        previous_creation_timestamp: {type: GraphQLInt},
     }
 });
 
@@ -99,6 +101,9 @@ const ProcessType = new GraphQLObjectType({
         image_name: {type: GraphQLString},
         process_name: {type: GraphQLString},
         arguments: {type: GraphQLString}, 
+        process_user: {type: GraphQLString},
+        process_integrity_level: {type: GraphQLString},
+        process_current_directory: {type: GraphQLString},
         children: {
             type: GraphQLList(ProcessType) 
         },
@@ -109,6 +114,15 @@ const ProcessType = new GraphQLObjectType({
         wrote_files: {type: GraphQLList(FileType)},
         created_connections: {type: GraphQLList(ProcessOutboundConnections)},
         inbound_connections: {type: GraphQLList(ProcessInboundConnections)},
+        loaded_modules: {type: GraphQLList(FileType)},
+        modified_file_time: {type: GraphQLList(FileType)},
+        accessed_process: {type: GraphQLList(ProcessType)},
+        injected_into: {type: GraphQLList(ProcessType)},
+        // DnsQuery, ProcessAccess, RemoteThread and RegistryKey are dynamic nodes
+        dns_queries: {type: GraphQLList(PluginType)},
+        process_accesses: {type: GraphQLList(PluginType)},
+        created_remote_thread: {type: GraphQLList(PluginType)},
+        modified_registry_key: {type: GraphQLList(PluginType)},
         process_id: {type: GraphQLInt},
         risks: {type: GraphQLList(RiskType)},
     })
@@ -2,6 +2,7 @@ import * as cdk from '@aws-cdk/core';
 import * as s3 from '@aws-cdk/aws-s3';
 import * as sns from '@aws-cdk/aws-sns';
 import * as sqs from '@aws-cdk/aws-sqs';
+import * as subscriptions from '@aws-cdk/aws-sns-subscriptions';
 import * as ec2 from '@aws-cdk/aws-ec2';
 import * as events from '@aws-cdk/aws-events';
 import * as targets from '@aws-cdk/aws-events-targets';
@@ -134,6 +135,19 @@ class NodeIdentifier extends cdk.NestedStack {
 
         history_db.allowReadWrite(service);
 
+        // Nodes that fail identification are written here, to be retried on their own
+        const retry_payloads = new EventEmitter(
+            this,
+            bucket_prefix + '-node-identifier-retry'
+        );
+        service.readsFrom(retry_payloads.bucket);
+        service.writesToBucket(retry_payloads.bucket);
+        retry_payloads.topic.addSubscription(
+            new subscriptions.SqsSubscription(service.queues.retry_queue, {
+                rawMessageDelivery: true,
+            })
+        );
+
         service.event_handler.connections.allowToAnyIpv4(
             ec2.Port.tcp(
                 parseInt(retry_identity_cache.cluster.attrRedisEndpointPort)
@@ -11,6 +11,10 @@ from grapl_analyzerlib.nodes.process_inbound_connection import (
 from grapl_analyzerlib.nodes.process_outbound_connection import (
     ProcessOutboundConnectionSchema,
 )
+from grapl_analyzerlib.nodes.process_access import ProcessAccessSchema
+from grapl_analyzerlib.nodes.remote_thread import RemoteThreadSchema
+from grapl_analyzerlib.nodes.registry_key import RegistryKeySchema
+from grapl_analyzerlib.nodes.dns_query import DnsQuerySchema
 from grapl_analyzerlib.nodes.risk import RiskSchema
 
 AssetSchema().init_reverse()
@@ -21,5 +25,9 @@ IpConnectionSchema().init_reverse()
 LensSchema().init_reverse()
 ProcessInboundConnectionSchema().init_reverse()
 ProcessOutboundConnectionSchema().init_reverse()
+ProcessAccessSchema().init_reverse()
+RemoteThreadSchema().init_reverse()
+RegistryKeySchema().init_reverse()
+DnsQuerySchema().init_reverse()
 RiskSchema().init_reverse()
 ProcessSchema().init_reverse()
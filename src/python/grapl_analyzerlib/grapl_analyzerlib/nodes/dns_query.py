@@ -0,0 +1,209 @@
+from typing import Any, TypeVar, List, Set, Dict, Tuple, Optional
+
+from grapl_analyzerlib.node_types import (
+    EdgeT,
+    PropType,
+    PropPrimitive,
+    EdgeRelationship,
+)
+from grapl_analyzerlib.nodes.entity import EntityQuery, EntityView, EntitySchema
+from grapl_analyzerlib.queryable import with_str_prop, with_int_prop
+from grapl_analyzerlib.schema import Schema
+
+DQQ = TypeVar("DQQ", bound="DnsQueryQuery")
+DQV = TypeVar("DQV", bound="DnsQueryView")
+
+
+def default_dns_query_properties() -> Dict[str, PropType]:
+    return {
+        "query_name": PropType(PropPrimitive.Str, False),
+        "query_status": PropType(PropPrimitive.Int, False),
+        "last_seen_timestamp": PropType(PropPrimitive.Int, False),
+    }
+
+
+def default_dns_query_edges() -> Dict[str, Tuple[EdgeT, str]]:
+    from grapl_analyzerlib.nodes.ip_address import IpAddressSchema
+
+    return {
+        "dns_query_resolved_to": (
+            # The addresses the queried name resolved to
+            EdgeT(DnsQuerySchema, IpAddressSchema, EdgeRelationship.ManyToMany),
+            "resolved_from_dns_queries",
+        ),
+    }
+
+
+class DnsQuerySchema(EntitySchema):
+    def __init__(self):
+        super(DnsQuerySchema, self).__init__(
+            default_dns_query_properties(),
+            default_dns_query_edges(),
+            lambda: DnsQueryView,
+        )
+
+    @staticmethod
+    def self_type() -> str:
+        return "DnsQuery"
+
+
+class DnsQueryQuery(EntityQuery[DQV, DQQ]):
+    @with_str_prop("query_name")
+    def with_query_name(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_int_prop("query_status")
+    def with_query_status(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    @with_int_prop("last_seen_timestamp")
+    def with_last_seen_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    def with_dns_query_resolved_to(
+        self, *dns_query_resolved_to: "IpAddressQuery"
+    ) -> "DnsQueryQuery":
+        return self.with_to_neighbor(
+            IpAddressQuery,
+            "dns_query_resolved_to",
+            "resolved_from_dns_queries",
+            dns_query_resolved_to,
+        )
+
+    def with_dns_querier(self, *dns_querier: "ProcessQuery") -> "DnsQueryQuery":
+        return self.with_to_neighbor(
+            ProcessQuery, "dns_querier", "dns_queries", dns_querier
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return DnsQuerySchema()
+
+
+class DnsQueryView(EntityView[DQV, DQQ]):
+    queryable = DnsQueryQuery
+
+    def __init__(
+        self,
+        uid: str,
+        node_key: str,
+        graph_client: Any,
+        node_types: Set[str],
+        query_name: Optional[str] = None,
+        query_status: Optional[int] = None,
+        last_seen_timestamp: Optional[int] = None,
+        dns_query_resolved_to: Optional[List["IpAddressView"]] = None,
+        dns_querier: Optional[List["ProcessView"]] = None,
+        **kwargs,
+    ):
+        super().__init__(uid, node_key, graph_client, node_types, **kwargs)
+        self.node_types = set(node_types)
+
+        self.set_predicate("query_name", query_name)
+        self.set_predicate("query_status", query_status)
+        self.set_predicate("last_seen_timestamp", last_seen_timestamp)
+        self.set_predicate("dns_query_resolved_to", dns_query_resolved_to or [])
+        self.set_predicate("dns_querier", dns_querier or [])
+
+    def get_query_name(self, cached=True):
+        return self.get_str("query_name", cached=cached)
+
+    def get_query_status(self, cached=True):
+        return self.get_int("query_status", cached=cached)
+
+    def get_last_seen_timestamp(self, cached=True):
+        return self.get_int("last_seen_timestamp", cached=cached)
+
+    def get_dns_query_resolved_to(self, *filters, cached=True):
+        return self.get_neighbor(
+            IpAddressQuery,
+            "dns_query_resolved_to",
+            "resolved_from_dns_queries",
+            filters,
+            cached=cached,
+        )
+
+    def get_dns_querier(self, *filters, cached=True):
+        return self.get_neighbor(
+            ProcessQuery, "dns_querier", "dns_queries", filters, cached=cached
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return DnsQuerySchema()
+
+
+from grapl_analyzerlib.comparators import IntOrNot, StrOrNot
+from grapl_analyzerlib.nodes.ip_address import IpAddressQuery, IpAddressView
+from grapl_analyzerlib.nodes.process import ProcessQuery, ProcessView
+
+DnsQuerySchema().init_reverse()
+
+
+class DnsQueryExtendsProcessQuery(ProcessQuery):
+    def with_dns_queries(self, *dns_queries: "DnsQueryQuery") -> "ProcessQuery":
+        return self.with_to_neighbor(
+            DnsQueryQuery, "dns_queries", "dns_querier", dns_queries
+        )
+
+
+class DnsQueryExtendsProcessView(ProcessView):
+    def get_dns_queries(self, *filters, cached=True):
+        return self.get_neighbor(
+            DnsQueryQuery, "dns_queries", "dns_querier", filters, cached=cached
+        )
+
+
+class DnsQueryExtendsIpAddressQuery(IpAddressQuery):
+    def with_resolved_from_dns_queries(
+        self, *resolved_from_dns_queries: "DnsQueryQuery"
+    ) -> "IpAddressQuery":
+        return self.with_to_neighbor(
+            DnsQueryQuery,
+            "resolved_from_dns_queries",
+            "dns_query_resolved_to",
+            resolved_from_dns_queries,
+        )
+
+
+class DnsQueryExtendsIpAddressView(IpAddressView):
+    def get_resolved_from_dns_queries(self, *filters, cached=True):
+        return self.get_neighbor(
+            DnsQueryQuery,
+            "resolved_from_dns_queries",
+            "dns_query_resolved_to",
+            filters,
+            cached=cached,
+        )
+
+
+ProcessQuery = ProcessQuery.extend_self(DnsQueryExtendsProcessQuery)
+ProcessView = ProcessView.extend_self(DnsQueryExtendsProcessView)
+
+IpAddressQuery = IpAddressQuery.extend_self(DnsQueryExtendsIpAddressQuery)
+IpAddressView = IpAddressView.extend_self(DnsQueryExtendsIpAddressView)
@@ -39,6 +39,10 @@ def default_file_edges() -> Dict[str, Tuple[EdgeT, str]]:
             EdgeT(ProcessSchema, FileSchema, EdgeRelationship.OneToMany),
             "deleted_files",
         ),
+        "file_time_modifiers": (
+            EdgeT(ProcessSchema, FileSchema, EdgeRelationship.ManyToMany),
+            "modified_file_time",
+        ),
     }
 
 
@@ -54,12 +58,15 @@ def default_file_properties() -> Dict[str, PropType]:
         "file_directory": PropType(PropPrimitive.Str, False),
         "file_hard_links": PropType(PropPrimitive.Str, False),
         "signed": PropType(PropPrimitive.Str, False),
+        "signature": PropType(PropPrimitive.Str, False),
         "signed_status": PropType(PropPrimitive.Str, False),
         "md5_hash": PropType(PropPrimitive.Str, False),
         "sha1_hash": PropType(PropPrimitive.Str, False),
         "sha256_hash": PropType(PropPrimitive.Str, False),
         "file_inode": PropType(PropPrimitive.Int, False),
         "file_size": PropType(PropPrimitive.Int, False),
+        "changed_creation_timestamp": PropType(PropPrimitive.Int, False),
+        "previous_creation_timestamp": PropType(PropPrimitive.Int, False),
     }
 
 
@@ -210,6 +217,19 @@ class FileQuery(EntityQuery[FV, FQ]):
     ) -> "FileQuery":
         pass
 
+    @with_str_prop("signature")
+    def with_signature(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ) -> "FileQuery":
+        pass
+
     @with_str_prop("signed_status")
     def with_signed_status(
         self,
@@ -284,11 +304,50 @@ class FileQuery(EntityQuery[FV, FQ]):
     ):
         pass
 
+    @with_int_prop("changed_creation_timestamp")
+    def with_changed_creation_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    @with_int_prop("previous_creation_timestamp")
+    def with_previous_creation_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
     def with_spawned_from(self, *spawned_from: Optional["ProcessQuery"]) -> "FileQuery":
         return self.with_to_neighbor(
             ProcessQuery, "spawned_from", "bin_file", *spawned_from
         )
 
+    def with_loaded_by(self, *loaded_by: Optional["ProcessQuery"]) -> "FileQuery":
+        return self.with_to_neighbor(
+            ProcessQuery, "loaded_by", "loaded_modules", loaded_by
+        )
+
+    def with_file_time_modifiers(
+        self, *file_time_modifiers: Optional["ProcessQuery"]
+    ) -> "FileQuery":
+        return self.with_to_neighbor(
+            ProcessQuery,
+            "file_time_modifiers",
+            "modified_file_time",
+            file_time_modifiers,
+        )
+
     @classmethod
     def node_schema(cls) -> "Schema":
         return FileSchema()
@@ -315,15 +374,20 @@ class FileView(EntityView[FV, FQ]):
         file_inode: Optional[int] = None,
         file_hard_links: Optional[str] = None,
         signed: Optional[str] = None,
+        signature: Optional[str] = None,
         signed_status: Optional[str] = None,
         md5_hash: Optional[str] = None,
         sha1_hash: Optional[str] = None,
         sha256_hash: Optional[str] = None,
+        changed_creation_timestamp: Optional[int] = None,
+        previous_creation_timestamp: Optional[int] = None,
         spawned_from: Optional[List["ProcessView"]] = None,
         creator: Optional["ProcessView"] = None,
         writers: Optional[List["ProcessView"]] = None,
         readers: Optional[List["ProcessView"]] = None,
         deleter: Optional["ProcessView"] = None,
+        loaded_by: Optional[List["ProcessView"]] = None,
+        file_time_modifiers: Optional[List["ProcessView"]] = None,
         **kwargs,
     ):
         super(FileView, self).__init__(uid, node_key, graph_client, node_types)
@@ -340,16 +404,21 @@ class FileView(EntityView[FV, FQ]):
         self.set_predicate("file_inode", file_inode)
         self.set_predicate("file_hard_links", file_hard_links)
         self.set_predicate("signed", signed)
+        self.set_predicate("signature", signature)
         self.set_predicate("signed_status", signed_status)
         self.set_predicate("md5_hash", md5_hash)
         self.set_predicate("sha1_hash", sha1_hash)
         self.set_predicate("sha256_hash", sha256_hash)
+        self.set_predicate("changed_creation_timestamp", changed_creation_timestamp)
+        self.set_predicate("previous_creation_timestamp", previous_creation_timestamp)
 
         self.set_predicate("spawned_from", spawned_from or [])
         self.set_predicate("creator", creator or [])
         self.set_predicate("writers", writers or [])
         self.set_predicate("readers", readers or [])
         self.set_predicate("deleter", deleter or [])
+        self.set_predicate("loaded_by", loaded_by or [])
+        self.set_predicate("file_time_modifiers", file_time_modifiers or [])
 
     def get_file_path(
         self,
@@ -421,6 +490,13 @@ class FileView(EntityView[FV, FQ]):
     ):
         return self.get_str("signed", cached=cached)
 
+    def get_signature(
+        self,
+        *,
+        cached=True,
+    ):
+        return self.get_str("signature", cached=cached)
+
     def get_signed_status(
         self,
         *,
@@ -463,11 +539,39 @@ class FileView(EntityView[FV, FQ]):
     ):
         return self.get_int("file_size", cached=cached)
 
+    def get_changed_creation_timestamp(
+        self,
+        *,
+        cached=True,
+    ):
+        return self.get_int("changed_creation_timestamp", cached=cached)
+
+    def get_previous_creation_timestamp(
+        self,
+        *,
+        cached=True,
+    ):
+        return self.get_int("previous_creation_timestamp", cached=cached)
+
     def get_spawned_from(self, *filters: "ProcessQuery", cached=True):
         return self.get_neighbor(
             ProcessQuery, "spawned_from", "bin_file", filters, cached=cached
         )
 
+    def get_loaded_by(self, *filters: "ProcessQuery", cached=True):
+        return self.get_neighbor(
+            ProcessQuery, "loaded_by", "loaded_modules", filters, cached=cached
+        )
+
+    def get_file_time_modifiers(self, *filters: "ProcessQuery", cached=True):
+        return self.get_neighbor(
+            ProcessQuery,
+            "file_time_modifiers",
+            "modified_file_time",
+            filters,
+            cached=cached,
+        )
+
     @classmethod
     def node_schema(cls) -> "Schema":
         return FileSchema()
@@ -496,6 +600,20 @@ class FileExtendsProcessQuery(ProcessQuery):
             FileQuery, "deleted_files", "deleter", deleted_files
         )
 
+    def with_loaded_modules(
+        self, *loaded_modules: Optional[FileQuery]
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            FileQuery, "loaded_modules", "loaded_by", loaded_modules
+        )
+
+    def with_modified_file_time(
+        self, *modified_file_time: Optional[FileQuery]
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            FileQuery, "modified_file_time", "file_time_modifiers", modified_file_time
+        )
+
 
 class FileExtendsProcessView(ProcessView):
     bin_file = None
@@ -503,6 +621,8 @@ class FileExtendsProcessView(ProcessView):
     wrote_files = None
     read_files = None
     deleted_files = None
+    loaded_modules = None
+    modified_file_time = None
 
     def __init__(
         self,
@@ -515,6 +635,8 @@ class FileExtendsProcessView(ProcessView):
         wrote_files: Optional[List[FileQuery]] = None,
         read_files: Optional[List[FileQuery]] = None,
         deleted_files: Optional[List[FileQuery]] = None,
+        loaded_modules: Optional[List[FileQuery]] = None,
+        modified_file_time: Optional[List[FileQuery]] = None,
         **kwargs,
     ):
         super().__init__(
@@ -531,6 +653,8 @@ class FileExtendsProcessView(ProcessView):
         self.set_predicate("wrote_files", wrote_files or [])
         self.set_predicate("read_files", read_files or [])
         self.set_predicate("deleted_files", deleted_files or [])
+        self.set_predicate("loaded_modules", loaded_modules or [])
+        self.set_predicate("modified_file_time", modified_file_time or [])
 
     def get_bin_file(self, *filters, cached=True):
         return self.get_neighbor(
@@ -557,6 +681,20 @@ class FileExtendsProcessView(ProcessView):
             FileQuery, "deleted_files", "deleter", filters, cached=cached
         )
 
+    def get_loaded_modules(self, *filters, cached=True):
+        return self.get_neighbor(
+            FileQuery, "loaded_modules", "loaded_by", filters, cached=cached
+        )
+
+    def get_modified_file_time(self, *filters, cached=True):
+        return self.get_neighbor(
+            FileQuery,
+            "modified_file_time",
+            "file_time_modifiers",
+            filters,
+            cached=cached,
+        )
+
 
 FileSchema().init_reverse()
 
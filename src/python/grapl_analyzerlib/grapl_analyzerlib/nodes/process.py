@@ -24,6 +24,11 @@ def default_process_edges() -> Dict[str, Tuple[EdgeT, str]]:
     from grapl_analyzerlib.nodes.process_inbound_connection import (
         ProcessInboundConnectionSchema,
     )
+    from grapl_analyzerlib.nodes.file import FileSchema
+    from grapl_analyzerlib.nodes.process_access import ProcessAccessSchema
+    from grapl_analyzerlib.nodes.remote_thread import RemoteThreadSchema
+    from grapl_analyzerlib.nodes.registry_key import RegistryKeySchema
+    from grapl_analyzerlib.nodes.dns_query import DnsQuerySchema
 
     return {
         "children": (
@@ -46,6 +51,34 @@ def default_process_edges() -> Dict[str, Tuple[EdgeT, str]]:
             ),
             "bound_by",
         ),
+        "loaded_modules": (
+            EdgeT(ProcessSchema, FileSchema, EdgeRelationship.ManyToMany),
+            "loaded_by",
+        ),
+        "accessed_process": (
+            EdgeT(ProcessSchema, ProcessSchema, EdgeRelationship.ManyToMany),
+            "accessed_by",
+        ),
+        "process_accesses": (
+            EdgeT(ProcessSchema, ProcessAccessSchema, EdgeRelationship.OneToMany),
+            "access_source",
+        ),
+        "injected_into": (
+            EdgeT(ProcessSchema, ProcessSchema, EdgeRelationship.ManyToMany),
+            "injected_by",
+        ),
+        "created_remote_thread": (
+            EdgeT(ProcessSchema, RemoteThreadSchema, EdgeRelationship.OneToMany),
+            "remote_thread_source",
+        ),
+        "modified_registry_key": (
+            EdgeT(ProcessSchema, RegistryKeySchema, EdgeRelationship.ManyToMany),
+            "registry_key_modifiers",
+        ),
+        "dns_queries": (
+            EdgeT(ProcessSchema, DnsQuerySchema, EdgeRelationship.ManyToMany),
+            "dns_querier",
+        ),
     }
 
 
@@ -57,6 +90,9 @@ def default_process_properties() -> Dict[str, PropType]:
         "created_timestamp": PropType(PropPrimitive.Int, False),
         "terminate_time": PropType(PropPrimitive.Int, False),
         "arguments": PropType(PropPrimitive.Str, False),
+        "process_user": PropType(PropPrimitive.Str, False),
+        "process_integrity_level": PropType(PropPrimitive.Str, False),
+        "process_current_directory": PropType(PropPrimitive.Str, False),
     }
 
 
@@ -147,6 +183,45 @@ class ProcessQuery(EntityQuery[PV, PQ]):
     ) -> "ProcessQuery":
         pass
 
+    @with_str_prop("process_user")
+    def with_process_user(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ) -> "ProcessQuery":
+        pass
+
+    @with_str_prop("process_integrity_level")
+    def with_process_integrity_level(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ) -> "ProcessQuery":
+        pass
+
+    @with_str_prop("process_current_directory")
+    def with_process_current_directory(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ) -> "ProcessQuery":
+        pass
+
     @with_to_neighbor(None, "children", "parent")
     def with_children(self, *children: PQ):
         pass
@@ -175,6 +250,9 @@ class ProcessView(EntityView[PV, PQ]):
         created_timestamp: Optional[int] = None,
         terminate_time: Optional[int] = None,
         arguments: Optional[str] = None,
+        process_user: Optional[str] = None,
+        process_integrity_level: Optional[str] = None,
+        process_current_directory: Optional[str] = None,
         children: Optional[List["ProcessView"]] = None,
         parent: Optional["ProcessView"] = None,
         **kwargs,
@@ -187,6 +265,9 @@ class ProcessView(EntityView[PV, PQ]):
         self.set_predicate("created_timestamp", created_timestamp)
         self.set_predicate("terminate_time", terminate_time)
         self.set_predicate("arguments", arguments)
+        self.set_predicate("process_user", process_user)
+        self.set_predicate("process_integrity_level", process_integrity_level)
+        self.set_predicate("process_current_directory", process_current_directory)
         self.set_predicate("children", children or [])
         self.set_predicate("parent", parent)
 
@@ -268,6 +349,15 @@ class ProcessView(EntityView[PV, PQ]):
             self.arguments = self_node.arguments
         return self.arguments
 
+    def get_process_user(self, cached=True) -> Optional[str]:
+        return self.get_str("process_user", cached=cached)
+
+    def get_process_integrity_level(self, cached=True) -> Optional[str]:
+        return self.get_str("process_integrity_level", cached=cached)
+
+    def get_process_current_directory(self, cached=True) -> Optional[str]:
+        return self.get_str("process_current_directory", cached=cached)
+
     def get_parent(self, parent=None, cached=True) -> Optional[str]:
         if cached and self.parent:
             return self.parent
@@ -0,0 +1,234 @@
+from typing import Any, TypeVar, List, Set, Dict, Tuple, Optional
+
+from grapl_analyzerlib.node_types import (
+    EdgeT,
+    PropType,
+    PropPrimitive,
+    EdgeRelationship,
+)
+from grapl_analyzerlib.nodes.entity import EntityQuery, EntityView, EntitySchema
+from grapl_analyzerlib.queryable import with_str_prop, with_int_prop
+from grapl_analyzerlib.schema import Schema
+
+PAQ = TypeVar("PAQ", bound="ProcessAccessQuery")
+PAV = TypeVar("PAV", bound="ProcessAccessView")
+
+
+def default_process_access_properties() -> Dict[str, PropType]:
+    return {
+        "source_process_guid": PropType(PropPrimitive.Str, False),
+        "target_process_guid": PropType(PropPrimitive.Str, False),
+        "granted_access": PropType(PropPrimitive.Str, False),
+        "call_trace": PropType(PropPrimitive.Str, False),
+        "last_seen_timestamp": PropType(PropPrimitive.Int, False),
+    }
+
+
+def default_process_access_edges() -> Dict[str, Tuple[EdgeT, str]]:
+    from grapl_analyzerlib.nodes.process import ProcessSchema
+
+    return {
+        "process_access_target": (
+            # The process the handle was opened to
+            EdgeT(ProcessAccessSchema, ProcessSchema, EdgeRelationship.ManyToOne),
+            "target_of_accesses",
+        ),
+    }
+
+
+class ProcessAccessSchema(EntitySchema):
+    def __init__(self):
+        super(ProcessAccessSchema, self).__init__(
+            default_process_access_properties(),
+            default_process_access_edges(),
+            lambda: ProcessAccessView,
+        )
+
+    @staticmethod
+    def self_type() -> str:
+        return "ProcessAccess"
+
+
+class ProcessAccessQuery(EntityQuery[PAV, PAQ]):
+    @with_str_prop("source_process_guid")
+    def with_source_process_guid(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("target_process_guid")
+    def with_target_process_guid(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("granted_access")
+    def with_granted_access(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("call_trace")
+    def with_call_trace(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_int_prop("last_seen_timestamp")
+    def with_last_seen_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    def with_process_access_target(
+        self, *process_access_target: "ProcessQuery"
+    ) -> "ProcessAccessQuery":
+        return self.with_to_neighbor(
+            ProcessQuery,
+            "process_access_target",
+            "target_of_accesses",
+            process_access_target,
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return ProcessAccessSchema()
+
+
+class ProcessAccessView(EntityView[PAV, PAQ]):
+    queryable = ProcessAccessQuery
+
+    def __init__(
+        self,
+        uid: str,
+        node_key: str,
+        graph_client: Any,
+        node_types: Set[str],
+        source_process_guid: Optional[str] = None,
+        target_process_guid: Optional[str] = None,
+        granted_access: Optional[str] = None,
+        call_trace: Optional[str] = None,
+        last_seen_timestamp: Optional[int] = None,
+        process_access_target: Optional["ProcessView"] = None,
+        **kwargs,
+    ):
+        super().__init__(uid, node_key, graph_client, node_types, **kwargs)
+        self.node_types = set(node_types)
+
+        self.set_predicate("source_process_guid", source_process_guid)
+        self.set_predicate("target_process_guid", target_process_guid)
+        self.set_predicate("granted_access", granted_access)
+        self.set_predicate("call_trace", call_trace)
+        self.set_predicate("last_seen_timestamp", last_seen_timestamp)
+        self.set_predicate("process_access_target", process_access_target)
+
+    def get_source_process_guid(self, cached=True):
+        return self.get_str("source_process_guid", cached=cached)
+
+    def get_target_process_guid(self, cached=True):
+        return self.get_str("target_process_guid", cached=cached)
+
+    def get_granted_access(self, cached=True):
+        return self.get_str("granted_access", cached=cached)
+
+    def get_call_trace(self, cached=True):
+        return self.get_str("call_trace", cached=cached)
+
+    def get_last_seen_timestamp(self, cached=True):
+        return self.get_int("last_seen_timestamp", cached=cached)
+
+    def get_process_access_target(self, *process_access_target, cached=True):
+        return self.get_neighbor(
+            ProcessQuery,
+            "process_access_target",
+            "target_of_accesses",
+            process_access_target,
+            cached=cached,
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return ProcessAccessSchema()
+
+
+from grapl_analyzerlib.comparators import IntOrNot, StrOrNot
+from grapl_analyzerlib.nodes.process import ProcessSchema, ProcessQuery, ProcessView
+
+ProcessAccessSchema().init_reverse()
+
+
+class ProcessAccessExtendsProcessQuery(ProcessQuery):
+    def with_process_accesses(
+        self, *process_accesses: "ProcessAccessQuery"
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            ProcessAccessQuery, "process_accesses", "access_source", process_accesses
+        )
+
+    def with_target_of_accesses(
+        self, *target_of_accesses: "ProcessAccessQuery"
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            ProcessAccessQuery,
+            "target_of_accesses",
+            "process_access_target",
+            target_of_accesses,
+        )
+
+
+class ProcessAccessExtendsProcessView(ProcessView):
+    def get_process_accesses(self, *filters, cached=True):
+        return self.get_neighbor(
+            ProcessAccessQuery,
+            "process_accesses",
+            "access_source",
+            filters,
+            cached=cached,
+        )
+
+    def get_target_of_accesses(self, *filters, cached=True):
+        return self.get_neighbor(
+            ProcessAccessQuery,
+            "target_of_accesses",
+            "process_access_target",
+            filters,
+            cached=cached,
+        )
+
+
+ProcessQuery = ProcessQuery.extend_self(ProcessAccessExtendsProcessQuery)
+ProcessView = ProcessView.extend_self(ProcessAccessExtendsProcessView)
@@ -0,0 +1,266 @@
+from typing import Any, TypeVar, List, Set, Dict, Tuple, Optional
+
+from grapl_analyzerlib.node_types import (
+    EdgeT,
+    PropType,
+    PropPrimitive,
+)
+from grapl_analyzerlib.nodes.entity import EntityQuery, EntityView, EntitySchema
+from grapl_analyzerlib.queryable import with_str_prop, with_int_prop
+from grapl_analyzerlib.schema import Schema
+
+RKQ = TypeVar("RKQ", bound="RegistryKeyQuery")
+RKV = TypeVar("RKV", bound="RegistryKeyView")
+
+
+def default_registry_key_properties() -> Dict[str, PropType]:
+    return {
+        "key_path": PropType(PropPrimitive.Str, False, index=["exact", "trigram"]),
+        "normalized_key_path": PropType(
+            PropPrimitive.Str, False, index=["exact", "trigram"]
+        ),
+        "value_data": PropType(PropPrimitive.Str, False),
+        "renamed_to": PropType(PropPrimitive.Str, False),
+        "last_event_type": PropType(PropPrimitive.Str, False),
+        "created_timestamp": PropType(PropPrimitive.Int, False),
+        "deleted_timestamp": PropType(PropPrimitive.Int, False),
+        "last_seen_timestamp": PropType(PropPrimitive.Int, False),
+    }
+
+
+def default_registry_key_edges() -> Dict[str, Tuple[EdgeT, str]]:
+    return {}
+
+
+class RegistryKeySchema(EntitySchema):
+    def __init__(self):
+        super(RegistryKeySchema, self).__init__(
+            default_registry_key_properties(),
+            default_registry_key_edges(),
+            lambda: RegistryKeyView,
+        )
+
+    @staticmethod
+    def self_type() -> str:
+        return "RegistryKey"
+
+
+class RegistryKeyQuery(EntityQuery[RKV, RKQ]):
+    @with_str_prop("key_path")
+    def with_key_path(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("normalized_key_path")
+    def with_normalized_key_path(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("value_data")
+    def with_value_data(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("renamed_to")
+    def with_renamed_to(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("last_event_type")
+    def with_last_event_type(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_int_prop("created_timestamp")
+    def with_created_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    @with_int_prop("deleted_timestamp")
+    def with_deleted_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    @with_int_prop("last_seen_timestamp")
+    def with_last_seen_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    def with_registry_key_modifiers(
+        self, *registry_key_modifiers: "ProcessQuery"
+    ) -> "RegistryKeyQuery":
+        return self.with_to_neighbor(
+            ProcessQuery,
+            "registry_key_modifiers",
+            "modified_registry_key",
+            registry_key_modifiers,
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return RegistryKeySchema()
+
+
+class RegistryKeyView(EntityView[RKV, RKQ]):
+    queryable = RegistryKeyQuery
+
+    def __init__(
+        self,
+        uid: str,
+        node_key: str,
+        graph_client: Any,
+        node_types: Set[str],
+        key_path: Optional[str] = None,
+        normalized_key_path: Optional[str] = None,
+        value_data: Optional[str] = None,
+        renamed_to: Optional[str] = None,
+        last_event_type: Optional[str] = None,
+        created_timestamp: Optional[int] = None,
+        deleted_timestamp: Optional[int] = None,
+        last_seen_timestamp: Optional[int] = None,
+        registry_key_modifiers: Optional[List["ProcessView"]] = None,
+        **kwargs,
+    ):
+        super().__init__(uid, node_key, graph_client, node_types, **kwargs)
+        self.node_types = set(node_types)
+
+        self.set_predicate("key_path", key_path)
+        self.set_predicate("normalized_key_path", normalized_key_path)
+        self.set_predicate("value_data", value_data)
+        self.set_predicate("renamed_to", renamed_to)
+        self.set_predicate("last_event_type", last_event_type)
+        self.set_predicate("created_timestamp", created_timestamp)
+        self.set_predicate("deleted_timestamp", deleted_timestamp)
+        self.set_predicate("last_seen_timestamp", last_seen_timestamp)
+        self.set_predicate("registry_key_modifiers", registry_key_modifiers or [])
+
+    def get_key_path(self, cached=True):
+        return self.get_str("key_path", cached=cached)
+
+    def get_normalized_key_path(self, cached=True):
+        return self.get_str("normalized_key_path", cached=cached)
+
+    def get_value_data(self, cached=True):
+        return self.get_str("value_data", cached=cached)
+
+    def get_renamed_to(self, cached=True):
+        return self.get_str("renamed_to", cached=cached)
+
+    def get_last_event_type(self, cached=True):
+        return self.get_str("last_event_type", cached=cached)
+
+    def get_created_timestamp(self, cached=True):
+        return self.get_int("created_timestamp", cached=cached)
+
+    def get_deleted_timestamp(self, cached=True):
+        return self.get_int("deleted_timestamp", cached=cached)
+
+    def get_last_seen_timestamp(self, cached=True):
+        return self.get_int("last_seen_timestamp", cached=cached)
+
+    def get_registry_key_modifiers(self, *filters, cached=True):
+        return self.get_neighbor(
+            ProcessQuery,
+            "registry_key_modifiers",
+            "modified_registry_key",
+            filters,
+            cached=cached,
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return RegistryKeySchema()
+
+
+from grapl_analyzerlib.comparators import IntOrNot, StrOrNot
+from grapl_analyzerlib.nodes.process import ProcessQuery, ProcessView
+
+RegistryKeySchema().init_reverse()
+
+
+class RegistryKeyExtendsProcessQuery(ProcessQuery):
+    def with_modified_registry_key(
+        self, *modified_registry_key: "RegistryKeyQuery"
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            RegistryKeyQuery,
+            "modified_registry_key",
+            "registry_key_modifiers",
+            modified_registry_key,
+        )
+
+
+class RegistryKeyExtendsProcessView(ProcessView):
+    def get_modified_registry_key(self, *filters, cached=True):
+        return self.get_neighbor(
+            RegistryKeyQuery,
+            "modified_registry_key",
+            "registry_key_modifiers",
+            filters,
+            cached=cached,
+        )
+
+
+ProcessQuery = ProcessQuery.extend_self(RegistryKeyExtendsProcessQuery)
+ProcessView = ProcessView.extend_self(RegistryKeyExtendsProcessView)
@@ -0,0 +1,274 @@
+from typing import Any, TypeVar, List, Set, Dict, Tuple, Optional
+
+from grapl_analyzerlib.node_types import (
+    EdgeT,
+    PropType,
+    PropPrimitive,
+    EdgeRelationship,
+)
+from grapl_analyzerlib.nodes.entity import EntityQuery, EntityView, EntitySchema
+from grapl_analyzerlib.queryable import with_str_prop, with_int_prop
+from grapl_analyzerlib.schema import Schema
+
+RTQ = TypeVar("RTQ", bound="RemoteThreadQuery")
+RTV = TypeVar("RTV", bound="RemoteThreadView")
+
+
+def default_remote_thread_properties() -> Dict[str, PropType]:
+    return {
+        "source_process_guid": PropType(PropPrimitive.Str, False),
+        "target_process_guid": PropType(PropPrimitive.Str, False),
+        "new_thread_id": PropType(PropPrimitive.Int, False),
+        "start_address": PropType(PropPrimitive.Str, False),
+        "start_module": PropType(PropPrimitive.Str, False),
+        "start_function": PropType(PropPrimitive.Str, False),
+        "created_timestamp": PropType(PropPrimitive.Int, False),
+    }
+
+
+def default_remote_thread_edges() -> Dict[str, Tuple[EdgeT, str]]:
+    from grapl_analyzerlib.nodes.process import ProcessSchema
+
+    return {
+        "remote_thread_target": (
+            # The process the thread was created in
+            EdgeT(RemoteThreadSchema, ProcessSchema, EdgeRelationship.ManyToOne),
+            "remote_threads_into",
+        ),
+    }
+
+
+class RemoteThreadSchema(EntitySchema):
+    def __init__(self):
+        super(RemoteThreadSchema, self).__init__(
+            default_remote_thread_properties(),
+            default_remote_thread_edges(),
+            lambda: RemoteThreadView,
+        )
+
+    @staticmethod
+    def self_type() -> str:
+        return "RemoteThread"
+
+
+class RemoteThreadQuery(EntityQuery[RTV, RTQ]):
+    @with_str_prop("source_process_guid")
+    def with_source_process_guid(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("target_process_guid")
+    def with_target_process_guid(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_int_prop("new_thread_id")
+    def with_new_thread_id(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    @with_str_prop("start_address")
+    def with_start_address(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("start_module")
+    def with_start_module(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_str_prop("start_function")
+    def with_start_function(
+        self,
+        *,
+        eq: Optional["StrOrNot"] = None,
+        contains: Optional["OneOrMany[StrOrNot]"] = None,
+        starts_with: Optional["StrOrNot"] = None,
+        ends_with: Optional["StrOrNot"] = None,
+        regexp: Optional["OneOrMany[StrOrNot]"] = None,
+        distance_lt: Optional[Tuple[str, int]] = None,
+    ):
+        pass
+
+    @with_int_prop("created_timestamp")
+    def with_created_timestamp(
+        self,
+        *,
+        eq: Optional["IntOrNot"] = None,
+        gt: Optional["IntOrNot"] = None,
+        ge: Optional["IntOrNot"] = None,
+        lt: Optional["IntOrNot"] = None,
+        le: Optional["IntOrNot"] = None,
+    ):
+        pass
+
+    def with_remote_thread_target(
+        self, *remote_thread_target: "ProcessQuery"
+    ) -> "RemoteThreadQuery":
+        return self.with_to_neighbor(
+            ProcessQuery,
+            "remote_thread_target",
+            "remote_threads_into",
+            remote_thread_target,
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return RemoteThreadSchema()
+
+
+class RemoteThreadView(EntityView[RTV, RTQ]):
+    queryable = RemoteThreadQuery
+
+    def __init__(
+        self,
+        uid: str,
+        node_key: str,
+        graph_client: Any,
+        node_types: Set[str],
+        source_process_guid: Optional[str] = None,
+        target_process_guid: Optional[str] = None,
+        new_thread_id: Optional[int] = None,
+        start_address: Optional[str] = None,
+        start_module: Optional[str] = None,
+        start_function: Optional[str] = None,
+        created_timestamp: Optional[int] = None,
+        remote_thread_target: Optional["ProcessView"] = None,
+        **kwargs,
+    ):
+        super().__init__(uid, node_key, graph_client, node_types, **kwargs)
+        self.node_types = set(node_types)
+
+        self.set_predicate("source_process_guid", source_process_guid)
+        self.set_predicate("target_process_guid", target_process_guid)
+        self.set_predicate("new_thread_id", new_thread_id)
+        self.set_predicate("start_address", start_address)
+        self.set_predicate("start_module", start_module)
+        self.set_predicate("start_function", start_function)
+        self.set_predicate("created_timestamp", created_timestamp)
+        self.set_predicate("remote_thread_target", remote_thread_target)
+
+    def get_source_process_guid(self, cached=True):
+        return self.get_str("source_process_guid", cached=cached)
+
+    def get_target_process_guid(self, cached=True):
+        return self.get_str("target_process_guid", cached=cached)
+
+    def get_new_thread_id(self, cached=True):
+        return self.get_int("new_thread_id", cached=cached)
+
+    def get_start_address(self, cached=True):
+        return self.get_str("start_address", cached=cached)
+
+    def get_start_module(self, cached=True):
+        return self.get_str("start_module", cached=cached)
+
+    def get_start_function(self, cached=True):
+        return self.get_str("start_function", cached=cached)
+
+    def get_created_timestamp(self, cached=True):
+        return self.get_int("created_timestamp", cached=cached)
+
+    def get_remote_thread_target(self, *remote_thread_target, cached=True):
+        return self.get_neighbor(
+            ProcessQuery,
+            "remote_thread_target",
+            "remote_threads_into",
+            remote_thread_target,
+            cached=cached,
+        )
+
+    @classmethod
+    def node_schema(cls) -> "Schema":
+        return RemoteThreadSchema()
+
+
+from grapl_analyzerlib.comparators import IntOrNot, StrOrNot
+from grapl_analyzerlib.nodes.process import ProcessSchema, ProcessQuery, ProcessView
+
+RemoteThreadSchema().init_reverse()
+
+
+class RemoteThreadExtendsProcessQuery(ProcessQuery):
+    def with_created_remote_thread(
+        self, *created_remote_thread: "RemoteThreadQuery"
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            RemoteThreadQuery,
+            "created_remote_thread",
+            "remote_thread_source",
+            created_remote_thread,
+        )
+
+    def with_remote_threads_into(
+        self, *remote_threads_into: "RemoteThreadQuery"
+    ) -> "ProcessQuery":
+        return self.with_to_neighbor(
+            RemoteThreadQuery,
+            "remote_threads_into",
+            "remote_thread_target",
+            remote_threads_into,
+        )
+
+
+class RemoteThreadExtendsProcessView(ProcessView):
+    def get_created_remote_thread(self, *filters, cached=True):
+        return self.get_neighbor(
+            RemoteThreadQuery,
+            "created_remote_thread",
+            "remote_thread_source",
+            filters,
+            cached=cached,
+        )
+
+    def get_remote_threads_into(self, *filters, cached=True):
+        return self.get_neighbor(
+            RemoteThreadQuery,
+            "remote_threads_into",
+            "remote_thread_target",
+            filters,
+            cached=cached,
+        )
+
+
+ProcessQuery = ProcessQuery.extend_self(RemoteThreadExtendsProcessQuery)
+ProcessView = ProcessView.extend_self(RemoteThreadExtendsProcessView)
@@ -36,6 +36,26 @@ from grapl_analyzerlib.nodes.network_connection import (
     NetworkConnectionQuery,
     NetworkConnectionSchema,
 )
+from grapl_analyzerlib.nodes.process_access import (
+    ProcessAccessView,
+    ProcessAccessQuery,
+    ProcessAccessSchema,
+)
+from grapl_analyzerlib.nodes.remote_thread import (
+    RemoteThreadView,
+    RemoteThreadQuery,
+    RemoteThreadSchema,
+)
+from grapl_analyzerlib.nodes.registry_key import (
+    RegistryKeyView,
+    RegistryKeyQuery,
+    RegistryKeySchema,
+)
+from grapl_analyzerlib.nodes.dns_query import (
+    DnsQueryView,
+    DnsQueryQuery,
+    DnsQuerySchema,
+)
 
 from grapl_analyzerlib.queryable import Queryable
 from grapl_analyzerlib.viewable import Viewable
@@ -31,6 +31,10 @@ from grapl_analyzerlib.prelude import (
     NetworkConnectionSchema,
     ProcessInboundConnectionSchema,
     ProcessOutboundConnectionSchema,
+    ProcessAccessSchema,
+    RemoteThreadSchema,
+    RegistryKeySchema,
+    DnsQuerySchema,
     LensSchema,
     RiskSchema,
 )
@@ -166,6 +170,10 @@ def provision_mg(mclient) -> None:
         NetworkConnectionSchema(),
         ProcessInboundConnectionSchema(),
         ProcessOutboundConnectionSchema(),
+        ProcessAccessSchema(),
+        RemoteThreadSchema(),
+        RegistryKeySchema(),
+        DnsQuerySchema(),
         RiskSchema(),
         LensSchema(),
     )
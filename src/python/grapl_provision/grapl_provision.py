@@ -29,6 +29,10 @@ from grapl_analyzerlib.prelude import (
     NetworkConnectionSchema,
     ProcessInboundConnectionSchema,
     ProcessOutboundConnectionSchema,
+    ProcessAccessSchema,
+    RemoteThreadSchema,
+    RegistryKeySchema,
+    DnsQuerySchema,
     LensSchema,
     RiskSchema,
 )
@@ -181,6 +185,10 @@ def provision_mg(mclient) -> None:
         NetworkConnectionSchema(),
         ProcessInboundConnectionSchema(),
         ProcessOutboundConnectionSchema(),
+        ProcessAccessSchema(),
+        RemoteThreadSchema(),
+        RegistryKeySchema(),
+        DnsQuerySchema(),
         RiskSchema(),
         LensSchema(),
     )
@@ -222,6 +230,7 @@ buckets = (
     BUCKET_PREFIX + "-sysmon-log-bucket",
     BUCKET_PREFIX + "-unid-subgraphs-generated-bucket",
     BUCKET_PREFIX + "-subgraphs-generated-bucket",
+    BUCKET_PREFIX + "-node-identifier-retry-bucket",
     BUCKET_PREFIX + "-subgraphs-merged-bucket",
     BUCKET_PREFIX + "-analyzer-dispatched-bucket",
     BUCKET_PREFIX + "-analyzers-bucket",
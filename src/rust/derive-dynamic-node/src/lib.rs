@@ -71,6 +71,7 @@ pub fn derive_dynamic_node(input: TokenStream) -> TokenStream {
                     hostname: None,
                     node_type: #struct_name_string .to_owned(),
                     id_strategy: vec![strategy],
+                    combine_id_strategies: false,
                     node_key: uuid::Uuid::new_v4().to_string(),
                     properties,
                     seen_at,
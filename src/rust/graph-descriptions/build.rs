@@ -104,6 +104,20 @@ fn main() {
     config.field_attribute(".graph_description.File.md5_hash", "#[builder(default)]");
     config.field_attribute(".graph_description.File.sha1_hash", "#[builder(default)]");
     config.field_attribute(".graph_description.File.sha256_hash", "#[builder(default)]");
+    config.field_attribute(".graph_description.File.signed", "#[builder(default)]");
+    config.field_attribute(".graph_description.File.signature", "#[builder(default)]");
+    config.field_attribute(
+        ".graph_description.File.signed_status",
+        "#[builder(default)]",
+    );
+    config.field_attribute(
+        ".graph_description.File.changed_creation_timestamp",
+        "#[builder(default)]",
+    );
+    config.field_attribute(
+        ".graph_description.File.previous_creation_timestamp",
+        "#[builder(default)]",
+    );
     config.field_attribute(".graph_description.File.asset_id", "#[builder(default)]");
     config.field_attribute(".graph_description.File.hostname", "#[builder(default)]");
     config.field_attribute(".graph_description.File.host_ip", "#[builder(default)]");
@@ -145,10 +159,18 @@ fn main() {
         ".graph_description.Process.process_command_line",
         "#[builder(default)]",
     );
+    config.field_attribute(
+        ".graph_description.Process.process_user",
+        "#[builder(default)]",
+    );
     config.field_attribute(
         ".graph_description.Process.process_integrity_level",
         "#[builder(default)]",
     );
+    config.field_attribute(
+        ".graph_description.Process.process_current_directory",
+        "#[builder(default)]",
+    );
     config.field_attribute(
         ".graph_description.Process.operating_system",
         "#[builder(default)]",
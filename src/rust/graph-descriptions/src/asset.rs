@@ -27,6 +27,7 @@ impl Asset {
             mac_address: mac_address.into(),
             first_seen_timestamp,
             last_seen_timestamp,
+            ip_address: None,
         }
     }
 
@@ -54,6 +55,10 @@ impl Asset {
             j["mac_address"] = Value::from(mac_address.clone());
         }
 
+        if let Some(ip_address) = self.ip_address {
+            j["ip_address"] = Value::from(ip_address);
+        }
+
         j
     }
 }
@@ -98,6 +103,11 @@ impl NodeT for Asset {
             self.mac_address = other.mac_address.clone();
         }
 
+        if self.ip_address.is_none() && other.ip_address.is_some() {
+            merged = true;
+            self.ip_address = other.ip_address.clone();
+        }
+
         merged
     }
 
@@ -124,6 +134,11 @@ impl NodeT for Asset {
             merged = true;
         }
 
+        if self.ip_address.is_none() && other.ip_address.is_some() {
+            self.ip_address = other.ip_address;
+            merged = true;
+        }
+
         if other.first_seen_timestamp != 0 && self.first_seen_timestamp > other.first_seen_timestamp
         {
             self.first_seen_timestamp = other.first_seen_timestamp;
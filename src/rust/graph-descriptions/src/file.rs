@@ -71,6 +71,7 @@ impl File {
             node_key: Uuid::new_v4().to_string(),
             asset_id: asset_id.into(),
             hostname: hostname.into(),
+            host_ip: None,
             state: state.clone().into(),
             created_timestamp: 0,
             deleted_timestamp: 0,
@@ -90,6 +91,11 @@ impl File {
             md5_hash,
             sha1_hash,
             sha256_hash,
+            signed: false,
+            signature: String::new(),
+            signed_status: String::new(),
+            changed_creation_timestamp: 0,
+            previous_creation_timestamp: 0,
         };
 
         match state {
@@ -167,6 +173,26 @@ impl File {
             j["sha256_hash"] = Value::from(self.sha256_hash);
         }
 
+        if self.signed {
+            j["signed"] = Value::from(self.signed);
+        }
+
+        if !self.signature.is_empty() {
+            j["signature"] = Value::from(self.signature);
+        }
+
+        if !self.signed_status.is_empty() {
+            j["signed_status"] = Value::from(self.signed_status);
+        }
+
+        if self.changed_creation_timestamp != 0 {
+            j["changed_creation_timestamp"] = self.changed_creation_timestamp.into()
+        }
+
+        if self.previous_creation_timestamp != 0 {
+            j["previous_creation_timestamp"] = self.previous_creation_timestamp.into()
+        }
+
         if self.created_timestamp != 0 {
             j["created_time"] = self.created_timestamp.into()
         }
@@ -225,6 +251,11 @@ impl NodeT for File {
             self.hostname = other.hostname.clone();
         }
 
+        if self.host_ip.is_none() && other.host_ip.is_some() {
+            merged = true;
+            self.host_ip = other.host_ip.clone();
+        }
+
         if self.file_name.is_empty() && !other.file_name.is_empty() {
             merged = true;
             self.file_name = other.file_name.clone();
@@ -300,6 +331,27 @@ impl NodeT for File {
             self.sha256_hash = other.sha256_hash.clone();
         }
 
+        if !self.signed && other.signed {
+            merged = true;
+            self.signed = other.signed;
+        }
+
+        if self.signature.is_empty() && !other.signature.is_empty() {
+            merged = true;
+            self.signature = other.signature.clone();
+        }
+
+        if self.signed_status.is_empty() && !other.signed_status.is_empty() {
+            merged = true;
+            self.signed_status = other.signed_status.clone();
+        }
+
+        if self.changed_creation_timestamp == 0 && other.changed_creation_timestamp != 0 {
+            merged = true;
+            self.changed_creation_timestamp = other.changed_creation_timestamp;
+            self.previous_creation_timestamp = other.previous_creation_timestamp;
+        }
+
         if self.created_timestamp == 0 {
             merged = true;
             self.created_timestamp = other.created_timestamp;
@@ -334,6 +386,11 @@ impl NodeT for File {
             self.hostname = other.hostname;
         }
 
+        if self.host_ip.is_none() && other.host_ip.is_some() {
+            merged = true;
+            self.host_ip = other.host_ip;
+        }
+
         if self.file_name.is_empty() && !other.file_name.is_empty() {
             merged = true;
             self.file_name = other.file_name;
@@ -409,6 +466,27 @@ impl NodeT for File {
             self.sha256_hash = other.sha256_hash;
         }
 
+        if !self.signed && other.signed {
+            merged = true;
+            self.signed = other.signed;
+        }
+
+        if self.signature.is_empty() && !other.signature.is_empty() {
+            merged = true;
+            self.signature = other.signature;
+        }
+
+        if self.signed_status.is_empty() && !other.signed_status.is_empty() {
+            merged = true;
+            self.signed_status = other.signed_status;
+        }
+
+        if self.changed_creation_timestamp == 0 && other.changed_creation_timestamp != 0 {
+            merged = true;
+            self.changed_creation_timestamp = other.changed_creation_timestamp;
+            self.previous_creation_timestamp = other.previous_creation_timestamp;
+        }
+
         if self.created_timestamp == 0 {
             merged = true;
             self.created_timestamp = other.created_timestamp;
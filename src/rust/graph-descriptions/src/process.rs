@@ -61,6 +61,7 @@ impl Process {
             node_key: Uuid::new_v4().to_string(),
             asset_id: asset_id.into(),
             hostname: hostname.into(),
+            host_ip: None,
             state: state.clone().into(),
             process_id,
             process_name,
@@ -70,6 +71,9 @@ impl Process {
             operating_system,
             process_command_line,
             process_guid,
+            process_user: String::new(),
+            process_integrity_level: String::new(),
+            process_current_directory: String::new(),
         };
 
         match state {
@@ -104,6 +108,18 @@ impl Process {
             j["process_guid"] = Value::from(self.process_guid);
         }
 
+        if !self.process_user.is_empty() {
+            j["process_user"] = Value::from(self.process_user);
+        }
+
+        if !self.process_integrity_level.is_empty() {
+            j["process_integrity_level"] = Value::from(self.process_integrity_level);
+        }
+
+        if !self.process_current_directory.is_empty() {
+            j["process_current_directory"] = Value::from(self.process_current_directory);
+        }
+
         if self.created_timestamp != 0 {
             j["created_timestamp"] = self.created_timestamp.into()
         }
@@ -164,6 +180,22 @@ impl NodeT for Process {
             merged = true;
         }
 
+        if self.process_user.is_empty() && !other.process_user.is_empty() {
+            self.process_user = other.process_user.clone();
+            merged = true;
+        }
+
+        if self.process_integrity_level.is_empty() && !other.process_integrity_level.is_empty() {
+            self.process_integrity_level = other.process_integrity_level.clone();
+            merged = true;
+        }
+
+        if self.process_current_directory.is_empty() && !other.process_current_directory.is_empty()
+        {
+            self.process_current_directory = other.process_current_directory.clone();
+            merged = true;
+        }
+
         if self.created_timestamp == 0 || other.created_timestamp < self.created_timestamp {
             self.created_timestamp = other.created_timestamp;
             merged = true;
@@ -211,6 +243,22 @@ impl NodeT for Process {
             merged = true;
         }
 
+        if self.process_user.is_empty() && !other.process_user.is_empty() {
+            self.process_user = other.process_user;
+            merged = true;
+        }
+
+        if self.process_integrity_level.is_empty() && !other.process_integrity_level.is_empty() {
+            self.process_integrity_level = other.process_integrity_level;
+            merged = true;
+        }
+
+        if self.process_current_directory.is_empty() && !other.process_current_directory.is_empty()
+        {
+            self.process_current_directory = other.process_current_directory;
+            merged = true;
+        }
+
         if self.created_timestamp == 0 || other.created_timestamp < self.created_timestamp {
             self.created_timestamp = other.created_timestamp;
             merged = true;
@@ -64,6 +64,7 @@ impl ProcessOutboundConnection {
             ip_address,
             asset_id,
             hostname,
+            host_ip: None,
             protocol,
             created_timestamp,
             terminated_timestamp,
@@ -135,6 +136,10 @@ impl NodeT for ProcessOutboundConnection {
             self.hostname = other.hostname.clone();
         }
 
+        if self.host_ip.is_none() && other.host_ip.is_some() {
+            self.host_ip = other.host_ip.clone();
+        }
+
         if self.created_timestamp != 0 && self.created_timestamp > other.created_timestamp {
             self.created_timestamp = other.created_timestamp;
             merged = true;
@@ -2,11 +2,13 @@
 
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
+use std::future::Future;
 use std::io::Cursor;
 use std::iter::FromIterator;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
+use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
@@ -17,7 +19,7 @@ use aws_lambda_events::event::s3::{
 use aws_lambda_events::event::sqs::SqsEvent;
 use chrono::Utc;
 
-use dgraph_tonic::{Client as DgraphClient, Mutate, Query};
+use dgraph_tonic::{Client as DgraphClient, ClientError, Mutate, Query, Status};
 
 use failure::{bail, Error};
 use futures::future::join_all;
@@ -27,7 +29,7 @@ use lambda_runtime::Context;
 use log::{debug, error, info, warn};
 use prost::Message;
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use rusoto_core::{HttpClient, Region};
 use rusoto_dynamodb::AttributeValue;
 use rusoto_dynamodb::DynamoDbClient;
@@ -59,101 +61,239 @@ macro_rules! log_time {
     }};
 }
 
-fn generate_edge_insert(from: &str, to: &str, edge_name: &str) -> dgraph_tonic::Mutation {
-    let mu = json!({
-        "uid": from,
-        edge_name: {
-            "uid": to
-        }
-    });
-
-    let mut mutation = dgraph_tonic::Mutation::new();
-    mutation.commit_now = true;
-    mutation.set_set_json(&mu);
+/// Nodes upserted per transaction
+const NODE_UPSERT_CHUNK_SIZE: usize = 100;
+/// Edges, reverse edges included, inserted per transaction
+const EDGE_INSERT_CHUNK_SIZE: usize = 1000;
+/// Attempts at a transaction before giving up on conflicting writers
+const MAX_TXN_ATTEMPTS: u32 = 5;
+
+/// A transaction that was still aborted by conflicts once it ran out of attempts
+#[derive(Debug)]
+struct TxnConflict {
+    attempts: u32,
+    reason: String,
+}
 
-    mutation
+impl std::fmt::Display for TxnConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Transaction aborted after {} attempts: {}",
+            self.attempts, self.reason
+        )
+    }
 }
 
-async fn node_key_to_uid(dg: &DgraphClient, node_key: &str) -> Result<Option<String>, Error> {
-    let mut txn = dg.new_read_only_txn();
+impl std::error::Error for TxnConflict {}
+
+/// Dgraph aborts a transaction that conflicts with a concurrent one, and expects it to be
+/// retried. The client reports the gRPC status of the request that failed.
+fn is_txn_conflict(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        let status = match cause.downcast_ref::<ClientError>() {
+            Some(ClientError::CannotMutate(status))
+            | Some(ClientError::CannotDoRequest(status))
+            | Some(ClientError::CannotCommitOrAbort(status)) => Some(status),
+            _ => cause.downcast_ref::<Status>(),
+        };
+        status.map_or(false, |status| status.code() == tonic::Code::Aborted)
+    })
+}
 
-    const QUERY: &str = r"
-       query q0($a: string)
-    {
-        q0(func: eq(node_key, $a), first: 1) @cascade {
-            uid,
-            dgraph.type,
+/// Runs a transaction, retrying it with backoff for as long as it's aborted by conflicts
+async fn retry_on_conflict<T, F, Fut>(mut txn: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, anyhow::Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        match txn().await {
+            Ok(res) => return Ok(res),
+            Err(e) if attempt < MAX_TXN_ATTEMPTS && is_txn_conflict(&e) => {
+                let backoff = 10 * 2u64.pow(attempt) + thread_rng().gen_range(0, 10);
+                warn!(
+                    "Transaction aborted, retrying in {}ms (attempt {}): {}",
+                    backoff, attempt, e
+                );
+                tokio::time::delay_for(Duration::from_millis(backoff)).await;
+                attempt += 1;
+            }
+            Err(e) if is_txn_conflict(&e) => {
+                return Err(TxnConflict {
+                    attempts: attempt,
+                    reason: e.to_string(),
+                }
+                .into())
+            }
+            Err(e) => return Err(e.into_failure()),
         }
-    }";
+    }
+}
 
-    let mut vars = HashMap::new();
-    vars.insert("$a".to_string(), node_key.to_string());
+/// A query with a block `q{i}` per node key, each selecting `block_body(i)` of the node
+fn node_key_query(
+    node_keys: &[&str],
+    block_body: impl Fn(usize) -> String,
+) -> (String, HashMap<String, String>) {
+    let mut params = Vec::with_capacity(node_keys.len());
+    let mut blocks = String::new();
+    let mut vars = HashMap::with_capacity(node_keys.len());
+
+    for (i, node_key) in node_keys.iter().enumerate() {
+        params.push(format!("$k{}: string", i));
+        blocks.push_str(&format!(
+            "q{i}(func: eq(node_key, $k{i}), first: 1) {{ {body} }}\n",
+            i = i,
+            body = block_body(i),
+        ));
+        vars.insert(format!("$k{}", i), node_key.to_string());
+    }
 
-    let query_res: Value = txn
-        .query_with_vars(QUERY, vars)
-        .await
-        .map(|res| serde_json::from_slice(&res.json))
-        .map_err(AnyhowFailure::into_failure)??;
+    (
+        format!("query q({}) {{\n{}}}", params.join(", "), blocks),
+        vars,
+    )
+}
 
-    let uid = query_res
-        .get("q0")
+fn query_block_uid(res: &Value, i: usize) -> Option<&str> {
+    res.get(format!("q{}", i))
         .and_then(|res| res.get(0))
-        .and_then(|uid| uid.get("uid"))
+        .and_then(|node| node.get("uid"))
         .and_then(|uid| uid.as_str())
-        .map(String::from);
+}
 
-    Ok(uid)
+/// The uid of the `i`th node of an upsert: found by its query block if it already existed,
+/// or else assigned to `uid(n{i})` by the mutation
+fn upserted_uid<'a>(
+    query_res: &'a Value,
+    assigned: &'a HashMap<String, String>,
+    i: usize,
+) -> Option<&'a str> {
+    query_block_uid(query_res, i)
+        .or_else(|| assigned.get(&format!("uid(n{})", i)).map(String::as_str))
 }
 
-async fn upsert_node(dg: &DgraphClient, node: Node) -> Result<String, Error> {
-    let query = format!(
-        r#"
-                {{
-                  p as var(func: eq(node_key, "{}"), first: 1)
-                }}
-                "#,
-        node.get_node_key()
-    );
+/// Looks up the uids of nodes by their node keys. Keys without a node are left out.
+async fn node_keys_to_uids(
+    dg: &DgraphClient,
+    node_keys: &[&str],
+) -> Result<HashMap<String, String>, Error> {
+    let mut uids = HashMap::with_capacity(node_keys.len());
+
+    for chunk in node_keys.chunks(NODE_UPSERT_CHUNK_SIZE) {
+        let (query, vars) = node_key_query(chunk, |_| "uid".to_owned());
+
+        let mut txn = dg.new_read_only_txn();
+        let query_res: Value = txn
+            .query_with_vars(query, vars)
+            .await
+            .map(|res| serde_json::from_slice(&res.json))
+            .map_err(AnyhowFailure::into_failure)??;
+
+        for (i, node_key) in chunk.iter().enumerate() {
+            if let Some(uid) = query_block_uid(&query_res, i) {
+                uids.insert(node_key.to_string(), uid.to_owned());
+            }
+        }
+    }
+
+    Ok(uids)
+}
 
-    let node_key = node.clone_node_key();
-    let mut set_json: serde_json::Value = node.into_json();
-    let mut node_types = vec![set_json["dgraph.type"].as_str().unwrap().clone()];
-    node_types.extend_from_slice(&["Entity", "Base"]);
+fn node_set_json(node: &Node, uid: String, last_index_time: u64) -> Value {
+    let mut set_json: Value = node.clone().into_json();
+    let mut node_types = vec![set_json["dgraph.type"].as_str().unwrap().to_owned()];
+    node_types.extend_from_slice(&["Entity".to_owned(), "Base".to_owned()]);
     set_json["dgraph.type"] = node_types.into();
 
-    set_json["uid"] = "uid(p)".into();
-    set_json["last_index_time"] = (SystemTime::now()
+    set_json["uid"] = uid.into();
+    set_json["last_index_time"] = last_index_time.into();
+    set_json
+}
+
+/// Upserts a chunk of nodes in a single transaction, returning their uids by node key.
+///
+/// Each node gets a query block that binds its existing uid, if any, to `n{i}`. The uids of
+/// existing nodes come back in the query results, and those of new nodes in the mutation's
+/// assigned uids, so no follow up read is needed.
+async fn upsert_nodes(
+    dg: &DgraphClient,
+    nodes: &[&Node],
+) -> Result<HashMap<String, String>, Error> {
+    let node_keys: Vec<&str> = nodes.iter().map(|node| node.get_node_key()).collect();
+    let (query, vars) = node_key_query(&node_keys, |i| format!("n{} as uid", i));
+
+    let last_index_time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .expect("Something is very wrong with the system clock")
-        .as_millis() as u64)
-        .into();
+        .as_millis() as u64;
+    let set_json: Vec<Value> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| node_set_json(node, format!("uid(n{})", i), last_index_time))
+        .collect();
 
     let mut mu = dgraph_tonic::Mutation::new();
-    mu.commit_now = true;
-    mu.set_set_json(&set_json);
-
-    let mut txn = dg.new_mutated_txn();
-    let upsert_res = match txn.upsert(query, mu).await {
-        Ok(res) => res,
-        Err(e) => {
-            txn.discard().await.map_err(AnyhowFailure::into_failure)?;
-            return Err(e.into_failure().into());
+    mu.set_set_json(&set_json)?;
+
+    let upsert_res = retry_on_conflict(|| {
+        let txn = dg.new_mutated_txn();
+        let (query, vars, mu) = (query.clone(), vars.clone(), mu.clone());
+        async move { txn.upsert_with_vars_and_commit_now(query, vars, mu).await }
+    })
+    .await?;
+
+    let query_res: Value = serde_json::from_slice(&upsert_res.json)?;
+
+    let mut uids = HashMap::with_capacity(nodes.len());
+    let mut missing = vec![];
+    for (i, node_key) in node_keys.iter().enumerate() {
+        match upserted_uid(&query_res, &upsert_res.uids, i) {
+            Some(uid) => {
+                uids.insert(node_key.to_string(), uid.to_owned());
+            }
+            None => missing.push(*node_key),
         }
-    };
+    }
 
-    txn.commit().await.map_err(AnyhowFailure::into_failure)?;
+    if !missing.is_empty() {
+        warn!("Upsert did not return uids for {} nodes", missing.len());
+        let found = node_keys_to_uids(dg, &missing).await?;
+        if let Some(node_key) = missing.iter().find(|key| !found.contains_key(**key)) {
+            bail!("Could not retrieve uid after upsert for {}", node_key);
+        }
+        uids.extend(found);
+    }
 
-    info!(
-        "Upsert res for {}, set_json: {} upsert_res: {:?}",
-        node_key,
-        set_json.to_string(),
-        upsert_res,
-    );
+    info!("Upserted {} nodes", uids.len());
 
-    match node_key_to_uid(dg, &node_key).await? {
-        Some(uid) => Ok(uid),
-        None => bail!("Could not retrieve uid after upsert for {}", &node_key),
-    }
+    Ok(uids)
+}
+
+fn edge_set_json(from: &str, to: &str, edge_name: &str) -> Value {
+    json!({
+        "uid": from,
+        edge_name: {
+            "uid": to
+        }
+    })
+}
+
+/// Inserts a chunk of edges in a single transaction
+async fn insert_edges(dg: &DgraphClient, edges: &[Value]) -> Result<(), Error> {
+    let mut mu = dgraph_tonic::Mutation::new();
+    mu.set_set_json(&edges)?;
+
+    retry_on_conflict(|| {
+        let txn = dg.new_mutated_txn();
+        let mu = mu.clone();
+        async move { txn.mutate_and_commit_now(mu).await }
+    })
+    .await?;
+
+    Ok(())
 }
 
 fn chunk<T, U>(data: U, count: usize) -> Vec<U>
@@ -197,18 +337,6 @@ where
     }
 }
 
-async fn upsert_edge(
-    mg_client: &DgraphClient,
-    mu: dgraph_tonic::Mutation,
-) -> Result<(), failure::Error> {
-    let mut txn = mg_client.new_mutated_txn();
-    txn.mutate(mu).await.map_err(AnyhowFailure::into_failure)?;
-
-    txn.commit().await.map_err(AnyhowFailure::into_failure)?;
-
-    Ok(())
-}
-
 #[derive(Debug, Clone, Default)]
 pub struct ZstdProtoDecoder;
 
@@ -456,7 +584,6 @@ where
         let mut edge_res = None;
 
         let mut node_key_to_uid_map = HashMap::new();
-        use futures::future::FutureExt;
         let mut upserts = Vec::with_capacity(subgraph.nodes.len());
         for node in subgraph.nodes.values() {
             match self
@@ -476,28 +603,42 @@ where
                 Err(e) => warn!("Failed to retrieve from cache: {:?}", e),
                 _ => (),
             };
-            upserts.push(
-                upsert_node(&self.mg_client, node.clone())
-                    .map(move |u| (node.clone_node_key(), u))
-                    .await,
-            )
+            upserts.push(node);
         }
 
-        for (node_key, upsert) in upserts.into_iter() {
-            let new_uid = match upsert {
-                Ok(new_uid) => {
-                    let identity = subgraph.nodes[&node_key].clone().into_json().to_string();
-                    identities.push(identity);
-                    new_uid
-                }
-                Err(e) => {
-                    error!("{}", e);
-                    upsert_res = Some(e);
-                    continue;
+        for chunk in upserts.chunks(NODE_UPSERT_CHUNK_SIZE) {
+            let upserted = match upsert_nodes(&self.mg_client, chunk).await {
+                // A single node that can't be written shouldn't fail the rest of its chunk.
+                // Conflicts are left alone, as they've been retried already.
+                Err(e) if chunk.len() > 1 && e.downcast_ref::<TxnConflict>().is_none() => {
+                    warn!(
+                        "Failed to upsert {} nodes, upserting them one at a time: {}",
+                        chunk.len(),
+                        e
+                    );
+                    let mut upserted = Vec::with_capacity(chunk.len());
+                    for node in chunk.chunks(1) {
+                        upserted.push((node, upsert_nodes(&self.mg_client, node).await));
+                    }
+                    upserted
                 }
+                res => vec![(chunk, res)],
             };
 
-            node_key_to_uid_map.insert(node_key, new_uid);
+            for (nodes, res) in upserted {
+                match res {
+                    Ok(uids) => {
+                        for node in nodes {
+                            identities.push((*node).clone().into_json().to_string());
+                        }
+                        node_key_to_uid_map.extend(uids);
+                    }
+                    Err(e) => {
+                        error!("Failed to upsert {} nodes: {}", nodes.len(), e);
+                        upsert_res = Some(e);
+                    }
+                }
+            }
         }
 
         if node_key_to_uid_map.is_empty() && upsert_res.is_some() {
@@ -514,14 +655,32 @@ where
         info!("Inserting edges {}", subgraph.edges.len());
         let dynamodb = init_dynamodb_client();
 
-        let mut edge_mutations: Vec<_> = vec![];
-
         let flattened_edges: Vec<_> = subgraph
             .edges
             .values()
             .map(|e| &e.edges)
             .flatten()
             .collect();
+
+        // Nodes that were skipped as cache hits, or that failed to upsert, may still be in
+        // the graph from an earlier merge
+        let unmapped: HashSet<&str> = flattened_edges
+            .iter()
+            .flat_map(|edge| vec![&edge.from[..], &edge.to[..]])
+            .filter(|node_key| !node_key_to_uid_map.contains_key(*node_key))
+            .collect();
+        if !unmapped.is_empty() {
+            let unmapped: Vec<&str> = unmapped.into_iter().collect();
+            match node_keys_to_uids(&self.mg_client, &unmapped).await {
+                Ok(uids) => node_key_to_uid_map.extend(uids),
+                Err(e) => {
+                    error!("Failed to look up edge uids: {}", e);
+                    edge_res = Some(e.to_string());
+                }
+            }
+        }
+
+        let mut edge_mutations: Vec<_> = vec![];
         for edge in flattened_edges.into_iter() {
             match (
                 node_key_to_uid_map.get(&edge.from[..]),
@@ -540,58 +699,16 @@ where
                     edge_res = Some(err);
                 }
                 (Some(from), Some(to)) => {
-                    info!("Upserting edge: {} {} {}", &from, &to, &edge.edge_name);
-                    edge_mutations.push((from.to_owned(), to.to_owned(), &edge.edge_name));
-                }
-                (Some(from), None) => {
-                    match node_key_to_uid(&self.mg_client, &edge.from[..]).await {
-                        Ok(Some(to)) => {
-                            edge_mutations.push((from.to_owned(), to.to_owned(), &edge.edge_name))
-                        }
-                        Ok(None) => edge_res = Some("Edge to uid failed".to_string()),
-                        Err(e) => edge_res = Some(e.to_string()),
-                    }
-                }
-                (None, Some(to)) => match node_key_to_uid(&self.mg_client, &edge.to[..]).await {
-                    Ok(Some(from)) => {
-                        edge_mutations.push((from.to_owned(), to.to_owned(), &edge.edge_name))
-                    }
-                    Ok(None) => edge_res = Some("Edge to uid failed".to_string()),
-                    Err(e) => edge_res = Some(e.to_string()),
-                },
-                (None, None) => {
-                    let from = match node_key_to_uid(&self.mg_client, &edge.from[..]).await {
-                        Ok(Some(from)) => from,
-                        Ok(None) => {
-                            edge_res = Some("Edge to uid failed".to_string());
-                            continue;
-                        }
-                        Err(e) => {
-                            edge_res = Some(e.to_string());
-                            continue;
-                        }
-                    };
-
-                    let to = match node_key_to_uid(&self.mg_client, &edge.to[..]).await {
-                        Ok(Some(to)) => to,
-                        Ok(None) => {
-                            edge_res = Some("Edge to uid failed".to_string());
-                            continue;
-                        }
-                        Err(e) => {
-                            edge_res = Some(e.to_string());
-                            continue;
-                        }
-                    };
-
-                    edge_mutations.push((from.to_owned(), to.to_owned(), &edge.edge_name));
+                    debug!("Upserting edge: {} {} {}", &from, &to, &edge.edge_name);
+                    edge_mutations.push((from, to, &edge.edge_name));
                 }
+                _ => edge_res = Some("Edge to uid failed".to_string()),
             }
         }
 
         let mut r_edge_cache: HashMap<String, String> = HashMap::with_capacity(2);
 
-        let mut mutations = Vec::with_capacity(edge_mutations.len());
+        let mut edge_inserts = Vec::with_capacity(edge_mutations.len() * 2);
         for (from, to, edge_name) in edge_mutations {
             let r_edge = match r_edge_cache.get(&edge_name.to_string()) {
                 r_edge @ Some(_) => Ok(r_edge.map(String::from)),
@@ -600,9 +717,8 @@ where
 
             match r_edge {
                 Ok(Some(r_edge)) if !r_edge.is_empty() => {
-                    r_edge_cache.insert(edge_name.to_owned(), r_edge.to_string());
-                    let mu = generate_edge_insert(&to, &from, &r_edge);
-                    mutations.push(upsert_edge(&self.mg_client, mu).await)
+                    edge_inserts.push(edge_set_json(to, from, &r_edge));
+                    r_edge_cache.insert(edge_name.to_owned(), r_edge);
                 }
                 Err(e) => {
                     error!("get_r_edge failed: {:?}", e);
@@ -611,13 +727,14 @@ where
                 _ => warn!("Missing r_edge for f_edge {}", edge_name),
             }
 
-            let mu = generate_edge_insert(&from, &to, &edge_name);
-            mutations.push(upsert_edge(&self.mg_client, mu).await);
+            edge_inserts.push(edge_set_json(from, to, edge_name));
         }
 
-        if let Some(e) = mutations.iter().find(|e| e.is_err()) {
-            error!("Failed to upsert edge: {:?}", e);
-            edge_res = Some(format!("Failed to upsert edge: {:?}", e));
+        for edges in edge_inserts.chunks(EDGE_INSERT_CHUNK_SIZE) {
+            if let Err(e) = insert_edges(&self.mg_client, edges).await {
+                error!("Failed to upsert {} edges: {:?}", edges.len(), e);
+                edge_res = Some(format!("Failed to upsert edges: {:?}", e));
+            }
         }
 
         let mut completed = match (upsert_res, edge_res) {
@@ -888,3 +1005,58 @@ impl AnyhowFailure for anyhow::Error {
         failure::Error::from_boxed_compat(From::from(self))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use dgraph_tonic::DgraphError;
+
+    #[test]
+    fn node_key_query_has_a_block_per_node_key() {
+        let (query, vars) = node_key_query(&["key-a", "key-b"], |i| format!("n{} as uid", i));
+
+        assert_eq!(
+            query,
+            "query q($k0: string, $k1: string) {\n\
+             q0(func: eq(node_key, $k0), first: 1) { n0 as uid }\n\
+             q1(func: eq(node_key, $k1), first: 1) { n1 as uid }\n\
+             }"
+        );
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars["$k0"], "key-a");
+        assert_eq!(vars["$k1"], "key-b");
+    }
+
+    #[test]
+    fn upserted_uids_come_from_query_blocks_or_assigned_uids() {
+        // The first node already existed, the second was created by the mutation
+        let query_res = json!({
+            "q0": [{"uid": "0x1"}],
+            "q1": [],
+        });
+        let mut assigned = HashMap::new();
+        assigned.insert("uid(n1)".to_owned(), "0x2".to_owned());
+
+        assert_eq!(query_block_uid(&query_res, 0), Some("0x1"));
+        assert_eq!(query_block_uid(&query_res, 1), None);
+        assert_eq!(upserted_uid(&query_res, &assigned, 0), Some("0x1"));
+        assert_eq!(upserted_uid(&query_res, &assigned, 1), Some("0x2"));
+        assert_eq!(upserted_uid(&query_res, &assigned, 2), None);
+    }
+
+    #[test]
+    fn only_aborted_requests_are_conflicts() {
+        let grpc_error = |status: Status| -> anyhow::Error {
+            DgraphError::GrpcError(ClientError::CannotDoRequest(status).into()).into()
+        };
+
+        assert!(is_txn_conflict(&grpc_error(Status::aborted(
+            "Transaction has been aborted. Please retry"
+        ))));
+        assert!(!is_txn_conflict(&grpc_error(Status::invalid_argument(
+            "predicate aborted_at is not indexed"
+        ))));
+        assert!(!is_txn_conflict(&anyhow::anyhow!("aborted")));
+    }
+}
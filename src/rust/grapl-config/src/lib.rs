@@ -213,3 +213,41 @@ pub fn ip_connection_history_table_name() -> String {
 pub fn asset_id_mappings_table_name() -> String {
     return std::env::var("ASSET_ID_MAPPINGS").expect("ASSET_ID_MAPPINGS");
 }
+
+/// Reads a comma or newline separated list from the environment variable `name`, or from the
+/// file at the path held by `{name}_FILE`. The environment variable takes precedence.
+///
+/// Returns `None` if neither is set, so that callers can fall back to their defaults.
+pub fn list_from_env_or_file(name: &str) -> Option<Vec<String>> {
+    let raw = match std::env::var(name) {
+        Ok(raw) => raw,
+        Err(_) => {
+            let path = std::env::var(format!("{}_FILE", name)).ok()?;
+            std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Could not read {}_FILE: {}: {}", name, path, e))
+        }
+    };
+
+    Some(
+        raw.split(|c| c == ',' || c == '\n')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// The Sysmon event ids the sysmon-subgraph-generator should turn into subgraphs, as set by
+/// `SYSMON_ENABLED_EVENT_IDS` or `SYSMON_ENABLED_EVENT_IDS_FILE`.
+pub fn sysmon_enabled_event_ids() -> Option<Vec<u8>> {
+    list_from_env_or_file("SYSMON_ENABLED_EVENT_IDS").map(|event_ids| {
+        event_ids
+            .iter()
+            .map(|event_id| {
+                event_id
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Invalid Sysmon event id: {}: {}", event_id, e))
+            })
+            .collect()
+    })
+}
@@ -1,17 +1,52 @@
+use std::fmt;
+
 use failure::{bail, Error};
-use hmap::hmap;
-use log::info;
-use rusoto_dynamodb::AttributeValue;
-use rusoto_dynamodb::{DynamoDb, PutItemInput, QueryInput};
 use serde::{Deserialize, Serialize};
 
 use grapl_graph_descriptions::graph_description::host::*;
 use grapl_graph_descriptions::graph_description::node::WhichNode;
 use grapl_graph_descriptions::graph_description::*;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ResolvedAssetId {
+use crate::storage::AssetIdStorage;
+
+/// A host id claimed by an asset from `c_timestamp` until the next mapping, or until
+/// `end_timestamp` if the mapping was retired
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetMapping {
     pub asset_id: String,
+    pub c_timestamp: u64,
+    #[serde(default)]
+    pub end_timestamp: Option<u64>,
+}
+
+impl AssetMapping {
+    /// Whether the mapping had not been retired by `ts`
+    pub fn is_live_at(&self, ts: u64) -> bool {
+        self.end_timestamp
+            .map_or(true, |end_timestamp| ts <= end_timestamp)
+    }
+}
+
+/// A clock skew learned for an asset, along with the timestamp of the event it was learned from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AssetSkew {
+    pub skew_ms: u64,
+    /// Estimates stored before they could expire have no timestamp, and are treated as expired
+    #[serde(default)]
+    pub observed_at: u64,
+}
+
+impl AssetSkew {
+    /// Whether the estimate is stale by `at`, having been observed more than `ttl_ms` before
+    pub fn is_expired(&self, at: u64, ttl_ms: u64) -> bool {
+        at.saturating_sub(self.observed_at) > ttl_ms
+    }
+
+    /// Whether `self` should replace the stored estimate `other`: it's at least as wide, or
+    /// `other` had expired by the time `self` was observed
+    pub fn supersedes(&self, other: &AssetSkew, ttl_ms: u64) -> bool {
+        self.skew_ms >= other.skew_ms || other.is_expired(self.observed_at, ttl_ms)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,20 +57,43 @@ pub struct AssetIdMapping<'a> {
     pub c_timestamp: u64,
 }
 
+/// Returned by `AssetIdDb::create_mapping` when an asset claims a host id back from the asset
+/// that took it over, while that asset still holds it. Both assets are using the same host
+/// id at once, so neither claim can be trusted until one of them is retired.
+#[derive(Debug)]
+pub struct MappingConflict {
+    pub pseudo_key: String,
+    pub claimed_by: String,
+    pub held_by: String,
+    pub timestamp: u64,
+}
+
+impl fmt::Display for MappingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} claimed {} at {}, which is still held by {}",
+            self.claimed_by, self.pseudo_key, self.timestamp, self.held_by
+        )
+    }
+}
+
+impl std::error::Error for MappingConflict {}
+
 #[derive(Debug, Clone)]
-pub struct AssetIdDb<D>
+pub struct AssetIdDb<S>
 where
-    D: DynamoDb,
+    S: AssetIdStorage,
 {
-    dynamo: D,
+    storage: S,
 }
 
-impl<D> AssetIdDb<D>
+impl<S> AssetIdDb<S>
 where
-    D: DynamoDb,
+    S: AssetIdStorage,
 {
-    pub fn new(dynamo: D) -> Self {
-        Self { dynamo }
+    pub fn new(storage: S) -> Self {
+        Self { storage }
     }
 
     pub async fn find_first_mapping_after(
@@ -43,175 +101,261 @@ where
         host_id: &HostId,
         ts: u64,
     ) -> Result<Option<String>, Error> {
-        let (table_key, pseudo_key) = match host_id {
-            HostId::AssetId(asset_id) => return Ok(Some(asset_id.to_owned())),
-            HostId::Hostname(hostname) => ("hostname", hostname.as_str()),
-        };
-
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            table_name: grapl_config::asset_id_mappings_table_name(),
-            key_condition_expression: Some(
-                "pseudo_key = :pkey_val AND c_timestamp >= :c_timestamp".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pkey_val".to_owned() => AttributeValue {
-                    s: format!("{}{}", table_key, pseudo_key).into(),
-                    ..Default::default()
-                },
-                ":c_timestamp".to_owned() => AttributeValue {
-                    n: ts.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
+        let pseudo_key = match mapping_key(host_id) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(asset_id_of(host_id)),
         };
 
-        let res = wait_on!(self.dynamo.query(query))?;
-
-        match res.items {
-            Some(mut items) if items.len() == 1 => {
-                let item = items.remove(0);
-                let asset_id: ResolvedAssetId = serde_dynamodb::from_hashmap(item)?;
-                Ok(Some(asset_id.asset_id))
-            }
-            Some(items) if items.is_empty() => Ok(None),
-            Some(items) => bail!("Unexpected number of items returned: {}", items.len()),
-            None => Ok(None),
-        }
+        let mapping = self
+            .storage
+            .find_first_asset_mapping_after(&pseudo_key, ts)
+            .await?;
+        Ok(mapping.map(|mapping| mapping.asset_id))
     }
 
+    /// The asset holding `host_id` at `ts`, unless its mapping was retired before then
     pub async fn find_last_mapping_before(
         &self,
         host_id: &HostId,
         ts: u64,
     ) -> Result<Option<String>, Error> {
-        //        info!("Finding last mapping before");
-        let (table_key, pseudo_key) = match host_id {
-            HostId::AssetId(asset_id) => return Ok(Some(asset_id.to_owned())),
-            HostId::Hostname(hostname) => ("hostname", hostname.as_str()),
-        };
-
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            scan_index_forward: Some(false),
-            table_name: grapl_config::asset_id_mappings_table_name(),
-            key_condition_expression: Some(
-                "pseudo_key = :pseudo_key AND c_timestamp <= :c_timestamp".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pseudo_key".to_owned() => AttributeValue {
-                    s: format!("{}{}", table_key, pseudo_key).into(),
-                    ..Default::default()
-                },
-                ":c_timestamp".to_owned() => AttributeValue {
-                    n: ts.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
+        let pseudo_key = match mapping_key(host_id) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(asset_id_of(host_id)),
         };
 
-        let res = wait_on!(self.dynamo.query(query))?;
-
-        if let Some(items) = res.items {
-            match &items[..] {
-                [] => Ok(None),
-                [item] => {
-                    let asset_id: ResolvedAssetId = serde_dynamodb::from_hashmap(item.clone())?;
-                    Ok(Some(asset_id.asset_id))
-                }
-                _ => bail!("Unexpected number of items returned"),
-            }
-        } else {
-            Ok(None)
-        }
+        let mapping = self
+            .storage
+            .find_last_asset_mapping_before(&pseudo_key, ts)
+            .await?;
+        Ok(mapping
+            .filter(|mapping| mapping.is_live_at(ts))
+            .map(|mapping| mapping.asset_id))
     }
 
+    /// Falls back to the first mapping after `ts` only when `host_id` was never mapped before
+    /// it. If it was, but that mapping has been retired, the host id was unclaimed at `ts`;
+    /// a later mapping most likely belongs to a new machine that reused the hostname.
     pub async fn resolve_asset_id(
         &self,
         host_id: &HostId,
         ts: u64,
     ) -> Result<Option<String>, Error> {
-        if let Some(session_id) = self.find_last_mapping_before(host_id, ts).await? {
-            Ok(Some(session_id))
-        } else {
-            self.find_first_mapping_after(host_id, ts).await
+        let pseudo_key = match mapping_key(host_id) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(asset_id_of(host_id)),
+        };
+
+        match self
+            .storage
+            .find_last_asset_mapping_before(&pseudo_key, ts)
+            .await?
+        {
+            Some(mapping) if mapping.is_live_at(ts) => Ok(Some(mapping.asset_id)),
+            Some(_) => Ok(None),
+            None => self.find_first_mapping_after(host_id, ts).await,
         }
     }
 
+    /// Maps `host_id` to `asset_id` from `ts` onwards, until the next mapping for `host_id`.
+    ///
+    /// Fails with `MappingConflict` if `asset_id` had handed `host_id` over to another asset
+    /// that still holds it.
     pub async fn create_mapping(
         &self,
         host_id: &HostId,
         asset_id: String,
         ts: u64,
     ) -> Result<(), Error> {
-        let (table_key, host_id) = match host_id {
-            HostId::AssetId(_id) => return Ok(()),
-            HostId::Hostname(hostname) => ("hostname", hostname.as_str()),
+        let pseudo_key = match mapping_key(host_id) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(()),
         };
 
-        let mapping = AssetIdMapping {
-            pseudo_key: format!("{}{}", table_key, host_id),
-            asset_id: &asset_id,
-            c_timestamp: ts,
+        match self
+            .storage
+            .find_last_asset_mapping_before(&pseudo_key, ts)
+            .await?
+        {
+            // Already mapped, so another mapping wouldn't change any lookup
+            Some(held) if held.is_live_at(ts) && held.asset_id == asset_id => return Ok(()),
+            Some(held) if held.is_live_at(ts) => {
+                if self.handed_over(&pseudo_key, &asset_id, &held).await? {
+                    return Err(MappingConflict {
+                        pseudo_key,
+                        claimed_by: asset_id,
+                        held_by: held.asset_id,
+                        timestamp: ts,
+                    }
+                    .into());
+                }
+            }
+            _ => (),
+        }
+
+        self.storage
+            .create_asset_mapping(pseudo_key, asset_id, ts)
+            .await
+    }
+
+    /// Whether `asset_id` still held `pseudo_key` when `held` took it over
+    async fn handed_over(
+        &self,
+        pseudo_key: &str,
+        asset_id: &str,
+        held: &AssetMapping,
+    ) -> Result<bool, Error> {
+        if held.c_timestamp == 0 {
+            return Ok(false);
+        }
+
+        let previous = self
+            .storage
+            .find_last_asset_mapping_before(pseudo_key, held.c_timestamp - 1)
+            .await?;
+
+        Ok(match previous {
+            Some(previous) => previous.asset_id == asset_id && previous.end_timestamp.is_none(),
+            None => false,
+        })
+    }
+
+    /// Ends `asset_id`'s claim on `host_id` at `ts`, ie: when the machine was terminated,
+    /// so that `host_id` is no longer attributed to it after `ts`
+    pub async fn retire_mapping(
+        &self,
+        host_id: &HostId,
+        asset_id: &str,
+        ts: u64,
+    ) -> Result<(), Error> {
+        let pseudo_key = match mapping_key(host_id) {
+            Some(pseudo_key) => pseudo_key,
+            None => bail!("Can not retire a mapping for an asset id"),
         };
 
-        let put_req = PutItemInput {
-            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
-            table_name: grapl_config::asset_id_mappings_table_name(),
-            ..Default::default()
+        match self
+            .storage
+            .find_last_asset_mapping_before(&pseudo_key, ts)
+            .await?
+        {
+            Some(mapping) if mapping.asset_id == asset_id => {
+                self.storage
+                    .end_asset_mapping(pseudo_key, mapping.c_timestamp, ts)
+                    .await
+            }
+            _ => bail!("{} was not mapped to {} at {}", pseudo_key, asset_id, ts),
+        }
+    }
+
+    /// Records that `mac_address` was leased `ip_address` at `ts`, ie: by a DHCP server,
+    /// mapping the IP to whichever asset held the MAC at the time.
+    ///
+    /// Returns the asset the IP was mapped to, or None if the MAC isn't known yet.
+    pub async fn record_lease(
+        &self,
+        ip_address: &str,
+        mac_address: &str,
+        ts: u64,
+    ) -> Result<Option<String>, Error> {
+        let mac_address = HostId::MacAddress(mac_address.to_owned());
+        let asset_id = match self.resolve_asset_id(&mac_address, ts).await? {
+            Some(asset_id) => asset_id,
+            None => return Ok(None),
         };
 
-        let put_item_response = wait_on!(self.dynamo.put_item(put_req))?;
+        self.create_mapping(&HostId::HostIp(ip_address.to_owned()), asset_id.clone(), ts)
+            .await?;
+
+        Ok(Some(asset_id))
+    }
+
+    /// The clock skew learned for `asset_id` as of `at`, or 0 if none has been observed
+    /// within `ttl_ms` of it
+    pub async fn asset_skew(&self, asset_id: &str, at: u64, ttl_ms: u64) -> Result<u64, Error> {
+        match self.storage.get_asset_skew(asset_id).await? {
+            Some(skew) if !skew.is_expired(at, ttl_ms) => Ok(skew.skew_ms),
+            _ => Ok(0),
+        }
+    }
+
+    pub async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error> {
+        self.storage.record_asset_skew(asset_id, skew, ttl_ms).await
+    }
+}
+
+/// The key that mappings for `host_id` are stored under. Each kind of host id gets its own
+/// prefix, so that a hostname can never collide with an IP or MAC address.
+fn mapping_key(host_id: &HostId) -> Option<String> {
+    let (table_key, host_id) = match host_id {
+        HostId::AssetId(_) => return None,
+        HostId::Hostname(hostname) => ("hostname", hostname),
+        HostId::HostIp(host_ip) => ("host_ip", host_ip),
+        HostId::MacAddress(mac_address) => ("mac_address", mac_address),
+    };
 
-        info!(
-            "PutItemResponse for {:?} {}: {:?}",
-            host_id, asset_id, put_item_response
-        );
+    Some(format!("{}{}", table_key, host_id))
+}
 
-        Ok(())
+fn asset_id_of(host_id: &HostId) -> Option<String> {
+    match host_id {
+        HostId::AssetId(asset_id) => Some(asset_id.to_owned()),
+        _ => None,
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct AssetIdentifier<D>
+pub struct AssetIdentifier<S>
 where
-    D: DynamoDb,
+    S: AssetIdStorage,
 {
-    assetdb: AssetIdDb<D>,
+    assetdb: AssetIdDb<S>,
 }
 
-impl<D> AssetIdentifier<D>
+impl<S> AssetIdentifier<S>
 where
-    D: DynamoDb,
+    S: AssetIdStorage,
 {
-    pub fn new(assetdb: AssetIdDb<D>) -> Self {
+    pub fn new(assetdb: AssetIdDb<S>) -> Self {
         Self { assetdb }
     }
 
     pub async fn attribute_asset_id(&self, node: &Node) -> Result<String, Error> {
         let ids = match &node.which_node {
-            Some(WhichNode::AssetNode(ref node)) => {
-                (&node.asset_id, &node.hostname, node.first_seen_timestamp)
-            }
-            Some(WhichNode::ProcessNode(ref node)) => {
-                (&node.asset_id, &node.hostname, node.created_timestamp)
-            }
-            Some(WhichNode::FileNode(ref node)) => {
-                (&node.asset_id, &node.hostname, node.created_timestamp)
-            }
-            Some(WhichNode::ProcessOutboundConnectionNode(ref node)) => {
-                (&node.asset_id, &node.hostname, node.created_timestamp)
-            }
-            Some(WhichNode::DynamicNode(ref node)) => {
-                (&node.asset_id, &node.hostname, node.seen_at)
-            }
-            Some(WhichNode::ProcessInboundConnectionNode(ref node)) => {
-                (&node.asset_id, &node.hostname, node.created_timestamp)
-            }
+            Some(WhichNode::AssetNode(ref node)) => (
+                &node.asset_id,
+                host_ids(&node.hostname, &node.mac_address, &node.ip_address),
+                node.first_seen_timestamp,
+            ),
+            Some(WhichNode::ProcessNode(ref node)) => (
+                &node.asset_id,
+                host_ids(&node.hostname, &None, &node.host_ip),
+                node.created_timestamp,
+            ),
+            Some(WhichNode::FileNode(ref node)) => (
+                &node.asset_id,
+                host_ids(&node.hostname, &None, &node.host_ip),
+                node.created_timestamp,
+            ),
+            Some(WhichNode::ProcessOutboundConnectionNode(ref node)) => (
+                &node.asset_id,
+                host_ids(&node.hostname, &None, &node.host_ip),
+                node.created_timestamp,
+            ),
+            Some(WhichNode::DynamicNode(ref node)) => (
+                &node.asset_id,
+                host_ids(&node.hostname, &None, &node.host_ip),
+                node.seen_at,
+            ),
+            Some(WhichNode::ProcessInboundConnectionNode(ref node)) => (
+                &node.asset_id,
+                host_ids(&node.hostname, &None, &node.host_ip),
+                node.created_timestamp,
+            ),
             Some(WhichNode::IpAddressNode(_)) => {
                 bail!("Can not call attribute_asset_id with IpAddressNode")
             }
@@ -227,22 +371,41 @@ where
             None => bail!("Could not determine node variant"),
         };
 
-        let (host_id, timestamp) = match ids {
+        let (host_ids, timestamp) = match ids {
             (Some(asset_id), _, _timestamp) => return Ok(asset_id.clone()),
-            (_, Some(hostname), timestamp) => (HostId::Hostname(hostname.clone()), timestamp),
+            (_, host_ids, timestamp) if !host_ids.is_empty() => (host_ids, timestamp),
             (_, _, _) => {
-                bail!("Must provide at least one of: asset_id, hostname, host_ip");
+                bail!("Must provide at least one of: asset_id, hostname, host_ip, mac_address");
             }
         };
 
-        // map host_id to asset_id
+        // map host_id to asset_id, trying the most specific host id first
         // If we don't find an asset id we'll have to mark the node as dead
-        let asset_id = self.assetdb.resolve_asset_id(&host_id, timestamp).await;
-
-        match asset_id {
-            Ok(Some(asset_id)) => Ok(asset_id),
-            Ok(None) => bail!("Failed to resolve assetid"),
-            Err(e) => bail!("Failed to resolve assetid {}", e),
+        for host_id in host_ids.iter() {
+            match self.assetdb.resolve_asset_id(host_id, timestamp).await {
+                Ok(Some(asset_id)) => return Ok(asset_id),
+                Ok(None) => continue,
+                Err(e) => bail!("Failed to resolve assetid {}", e),
+            }
         }
+
+        bail!("Failed to resolve assetid")
     }
 }
+
+/// The host ids a node carries, in the order they should be resolved. A hostname is the
+/// most stable of these, while an IP address is only ever leased to a host for a while.
+fn host_ids(
+    hostname: &Option<String>,
+    mac_address: &Option<String>,
+    host_ip: &Option<String>,
+) -> Vec<HostId> {
+    let hostname = hostname.clone().map(HostId::Hostname);
+    let mac_address = mac_address.clone().map(HostId::MacAddress);
+    let host_ip = host_ip.clone().map(HostId::HostIp);
+
+    vec![hostname, mac_address, host_ip]
+        .into_iter()
+        .flatten()
+        .collect()
+}
@@ -0,0 +1,84 @@
+use log::info;
+
+use node_identifier::compaction::{
+    compact_asset_mappings, compact_sessions, CompactionOptions, CompactionStats,
+};
+#[cfg(feature = "sqlite")]
+use node_identifier::storage::SqliteStorage;
+use node_identifier::storage::Storage;
+use node_identifier::{init_dynamodb_client, sqlite_storage_path};
+
+use rusoto_dynamodb::DynamoDbClient;
+use tokio::runtime::Runtime;
+
+fn session_tables() -> Vec<String> {
+    vec![
+        grapl_config::process_history_table_name(),
+        grapl_config::file_history_table_name(),
+        grapl_config::inbound_connection_history_table_name(),
+        grapl_config::outbound_connection_history_table_name(),
+        grapl_config::network_connection_history_table_name(),
+        grapl_config::ip_connection_history_table_name(),
+        grapl_config::dynamic_session_table_name(),
+    ]
+}
+
+fn report(table_name: &str, stats: &CompactionStats, dry_run: bool) {
+    info!(
+        "{}{}: scanned {}, merged {}, expired {}, conflicts {}",
+        if dry_run { "(dry run) " } else { "" },
+        table_name,
+        stats.scanned,
+        stats.merged,
+        stats.expired,
+        stats.conflicts
+    );
+}
+
+async fn compact(
+    storage: impl Storage,
+    options: CompactionOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for table_name in session_tables() {
+        let stats = compact_sessions(&storage, &table_name, &options).await?;
+        report(&table_name, &stats, options.dry_run);
+    }
+
+    let stats = compact_asset_mappings(&storage, &options).await?;
+    report(
+        &grapl_config::asset_id_mappings_table_name(),
+        &stats,
+        options.dry_run,
+    );
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let env = grapl_config::init_grapl_env!();
+
+    let mut options = CompactionOptions::from_env();
+    options.dry_run = std::env::args().any(|arg| arg == "--dry-run");
+    match options.expire_before {
+        Some(expire_before) => info!("Expiring rows that ended before {}", expire_before),
+        None => info!("NODE_IDENTIFIER_RETENTION_DAYS is unset, so nothing will expire"),
+    }
+
+    let mut runtime = Runtime::new()?;
+    match sqlite_storage_path() {
+        #[cfg(feature = "sqlite")]
+        Some(path) => {
+            info!("Compacting SQLite storage at {}", path);
+            runtime.block_on(compact(SqliteStorage::open(path)?, options))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Some(_) => {
+            Err("NODE_IDENTIFIER_SQLITE_PATH is set, but the sqlite feature is disabled".into())
+        }
+        None if env.is_local => runtime.block_on(compact(init_dynamodb_client(), options)),
+        None => runtime.block_on(compact(
+            DynamoDbClient::new(grapl_config::region()),
+            options,
+        )),
+    }
+}
@@ -3,7 +3,7 @@
 use log::{error, info};
 use std::time::Duration;
 
-use node_identifier::{init_dynamodb_client, local_handler, retry_handler};
+use node_identifier::{init_dynamodb_client, local_handler, retry_handler, sqlite_storage_path};
 
 use lambda_runtime::lambda;
 use rusoto_core::RusotoError;
@@ -22,15 +22,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         info!("Running locally");
         let mut runtime = Runtime::new().unwrap();
 
-        let dynamodb_client = init_dynamodb_client();
-        loop {
-            if let Err(e) = runtime.block_on(dynamodb_client.describe_endpoints()) {
-                match e {
-                    RusotoError::HttpDispatch(_) => {
-                        info!("Waiting for DynamoDB to become available");
-                        std::thread::sleep(Duration::new(2, 0));
+        // SQLite storage needs no external service to wait on
+        if sqlite_storage_path().is_none() {
+            let dynamodb_client = init_dynamodb_client();
+            loop {
+                if let Err(e) = runtime.block_on(dynamodb_client.describe_endpoints()) {
+                    match e {
+                        RusotoError::HttpDispatch(_) => {
+                            info!("Waiting for DynamoDB to become available");
+                            std::thread::sleep(Duration::new(2, 0));
+                        }
+                        _ => break,
                     }
-                    _ => break,
                 }
             }
         }
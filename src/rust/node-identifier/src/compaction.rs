@@ -0,0 +1,281 @@
+//! Compacts the session and asset id mapping tables, which otherwise grow without bound, as
+//! every guessed session and every hostname handover adds a row.
+//!
+//! Tables are scanned a page at a time through the storage layer, so compaction works the
+//! same against every backend. Each pseudo key's rows arrive together and in order, which lets
+//! adjacent rows be compared without holding the whole table in memory.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::Error;
+use log::*;
+
+use crate::assetdb::AssetMapping;
+use crate::env_or;
+use crate::sessions::Session;
+use crate::storage::{AssetIdStorage, ScanCursor, SessionStorage, VersionConflict};
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone)]
+pub struct CompactionOptions {
+    /// Rows that stopped applying before this time are deleted. Nothing expires when unset.
+    pub expire_before: Option<u64>,
+    /// Counts what would change, without writing anything
+    pub dry_run: bool,
+    /// The number of rows read per scan request
+    pub page_size: usize,
+}
+
+impl Default for CompactionOptions {
+    fn default() -> Self {
+        Self {
+            expire_before: None,
+            dry_run: false,
+            page_size: 100,
+        }
+    }
+}
+
+impl CompactionOptions {
+    /// Reads the retention window, in days, from `NODE_IDENTIFIER_RETENTION_DAYS`, where 0 keeps
+    /// everything, and the page size from `NODE_IDENTIFIER_COMPACTION_PAGE_SIZE`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("SystemTime before UNIX EPOCH!")
+            .as_millis() as u64;
+        let retention_days: u64 = env_or("NODE_IDENTIFIER_RETENTION_DAYS", 0);
+
+        Self {
+            expire_before: match retention_days {
+                0 => None,
+                days => Some(now_ms.saturating_sub(days.saturating_mul(MS_PER_DAY))),
+            },
+            dry_run: defaults.dry_run,
+            page_size: env_or("NODE_IDENTIFIER_COMPACTION_PAGE_SIZE", defaults.page_size).max(1),
+        }
+    }
+
+    fn is_expired(&self, ended_at: u64) -> bool {
+        match self.expire_before {
+            Some(expire_before) => ended_at < expire_before,
+            None => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionStats {
+    pub scanned: u64,
+    /// Rows folded into the row before them
+    pub merged: u64,
+    /// Rows deleted for falling outside of the retention window
+    pub expired: u64,
+    /// Merges skipped because the session was written to while compacting
+    pub conflicts: u64,
+}
+
+/// Merges adjacent sessions whose shared boundary was guessed on both sides, and expires
+/// sessions that ended before the retention window.
+///
+/// A merged session keeps the earlier session's id, so lookups that would have landed in the
+/// later session resolve to the earlier one from then on.
+pub async fn compact_sessions(
+    storage: &impl SessionStorage,
+    table_name: &str,
+    options: &CompactionOptions,
+) -> Result<CompactionStats, Error> {
+    let mut stats = CompactionStats::default();
+    let mut prev: Option<Session> = None;
+    let mut start: Option<ScanCursor> = None;
+
+    loop {
+        let page = storage
+            .scan_sessions(table_name, start.as_ref(), options.page_size)
+            .await?;
+
+        for session in page.items {
+            stats.scanned += 1;
+
+            if options.is_expired(session.end_time) {
+                if !options.dry_run {
+                    storage.delete_session(table_name, &session).await?;
+                }
+                stats.expired += 1;
+                continue;
+            }
+
+            let mergeable = match &prev {
+                Some(prev) => {
+                    prev.pseudo_key == session.pseudo_key
+                        && !prev.is_end_canon
+                        && !session.is_create_canon
+                }
+                None => false,
+            };
+            if !mergeable {
+                prev = Some(session);
+                continue;
+            }
+            let merges_into = prev.as_mut().unwrap();
+
+            let end_time = merges_into.end_time.max(session.end_time);
+            if !options.dry_run {
+                let updated = storage
+                    .update_session_end_time(
+                        table_name,
+                        merges_into,
+                        end_time,
+                        session.is_end_canon,
+                    )
+                    .await;
+                match updated {
+                    Ok(()) => storage.delete_session(table_name, &session).await?,
+                    Err(e) if e.downcast_ref::<VersionConflict>().is_some() => {
+                        warn!("Skipping merge into modified session: {}", e);
+                        stats.conflicts += 1;
+                        prev = Some(session);
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            merges_into.end_time = end_time;
+            merges_into.is_end_canon = session.is_end_canon;
+            merges_into.version += 1;
+            stats.merged += 1;
+        }
+
+        match page.next {
+            Some(next) => start = Some(next),
+            None => break,
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Merges consecutive mappings of a pseudo key to the same asset id, and expires mappings that
+/// were retired, or handed over to a newer mapping, before the retention window.
+///
+/// The newest mapping of a pseudo key only expires once it's been retired, as it still applies
+/// otherwise.
+pub async fn compact_asset_mappings(
+    storage: &impl AssetIdStorage,
+    options: &CompactionOptions,
+) -> Result<CompactionStats, Error> {
+    let mut stats = CompactionStats::default();
+    let mut prev: Option<(String, AssetMapping)> = None;
+    let mut start: Option<ScanCursor> = None;
+
+    loop {
+        let page = storage
+            .scan_asset_mappings(start.as_ref(), options.page_size)
+            .await?;
+
+        for (pseudo_key, mapping) in page.items {
+            stats.scanned += 1;
+
+            let (prev_key, prev_mapping) = match prev.take() {
+                Some((prev_key, prev_mapping)) if prev_key == pseudo_key => {
+                    (prev_key, prev_mapping)
+                }
+                finished => {
+                    // The previous pseudo key has no newer mappings
+                    if let Some((prev_key, prev_mapping)) = finished {
+                        if let Some(end) = prev_mapping.end_timestamp {
+                            expire_asset_mapping(
+                                storage,
+                                prev_key,
+                                &prev_mapping,
+                                end,
+                                options,
+                                &mut stats,
+                            )
+                            .await?;
+                        }
+                    }
+                    prev = Some((pseudo_key, mapping));
+                    continue;
+                }
+            };
+
+            if prev_mapping.end_timestamp.is_none() && prev_mapping.asset_id == mapping.asset_id {
+                if !options.dry_run {
+                    storage
+                        .delete_asset_mapping(pseudo_key.clone(), mapping.c_timestamp)
+                        .await?;
+                    if let Some(end) = mapping.end_timestamp {
+                        storage
+                            .end_asset_mapping(prev_key.clone(), prev_mapping.c_timestamp, end)
+                            .await?;
+                    }
+                }
+                stats.merged += 1;
+
+                let merged = AssetMapping {
+                    end_timestamp: mapping.end_timestamp,
+                    ..prev_mapping
+                };
+                prev = Some((prev_key, merged));
+                continue;
+            }
+
+            // Handed over, so the mapping stopped applying when its successor was created
+            let ended_at = prev_mapping
+                .end_timestamp
+                .unwrap_or(mapping.c_timestamp)
+                .min(mapping.c_timestamp);
+            expire_asset_mapping(
+                storage,
+                prev_key,
+                &prev_mapping,
+                ended_at,
+                options,
+                &mut stats,
+            )
+            .await?;
+            prev = Some((pseudo_key, mapping));
+        }
+
+        match page.next {
+            Some(next) => start = Some(next),
+            None => break,
+        }
+    }
+
+    if let Some((prev_key, prev_mapping)) = prev {
+        if let Some(end) = prev_mapping.end_timestamp {
+            expire_asset_mapping(storage, prev_key, &prev_mapping, end, options, &mut stats)
+                .await?;
+        }
+    }
+
+    Ok(stats)
+}
+
+async fn expire_asset_mapping(
+    storage: &impl AssetIdStorage,
+    pseudo_key: String,
+    mapping: &AssetMapping,
+    ended_at: u64,
+    options: &CompactionOptions,
+    stats: &mut CompactionStats,
+) -> Result<(), Error> {
+    if !options.is_expired(ended_at) {
+        return Ok(());
+    }
+
+    if !options.dry_run {
+        storage
+            .delete_asset_mapping(pseudo_key, mapping.c_timestamp)
+            .await?;
+    }
+    stats.expired += 1;
+
+    Ok(())
+}
@@ -2,7 +2,6 @@ use std::collections::{HashMap, HashSet};
 
 use failure::{bail, Error};
 use log::{info, warn};
-use rusoto_dynamodb::{AttributeValue, DynamoDb, GetItemInput, PutItemInput};
 use serde::{Deserialize, Serialize};
 
 use grapl_graph_descriptions::graph_description::id_strategy;
@@ -11,15 +10,162 @@ use grapl_graph_descriptions::graph_description::*;
 use grapl_graph_descriptions::node::NodeT;
 
 use crate::assetdb::AssetIdentifier;
+use crate::provenance::{Provenance, Strategy};
 use crate::sessiondb::SessionDb;
 use crate::sessions::UnidSession;
+use crate::storage::{MappingStorage, Storage};
+use crate::{remap_edges, remap_nodes, remove_dead_edges, remove_dead_nodes};
+
+/// The first of the properties that `strategy` identifies nodes by, which `node` lacks. Nodes
+/// without an asset id must have a host id to attribute one from.
+fn missing_property<'a>(
+    node: &DynamicNode,
+    strategy: &'a id_strategy::Strategy,
+) -> Option<&'a str> {
+    let (requires_asset_id, properties) = match strategy {
+        id_strategy::Strategy::Session(strategy) => (
+            strategy.primary_key_requires_asset_id,
+            &strategy.primary_key_properties,
+        ),
+        id_strategy::Strategy::Static(strategy) => (
+            strategy.primary_key_requires_asset_id,
+            &strategy.primary_key_properties,
+        ),
+    };
+
+    if requires_asset_id
+        && node.asset_id.is_none()
+        && node.hostname.is_none()
+        && node.host_ip.is_none()
+    {
+        return Some("asset_id");
+    }
+
+    properties
+        .iter()
+        .find(|property| !node.properties.contains_key(*property))
+        .map(String::as_str)
+}
+
+/// The pseudo key of a dynamic node's sessions or static mapping: its asset id, when the
+/// strategy requires one, followed by the strategy's primary key properties.
+fn primary_key(
+    node: &DynamicNode,
+    asset_id: Option<&str>,
+    properties: &[String],
+) -> Result<String, Error> {
+    let mut primary_key = String::with_capacity(32);
+
+    if let Some(asset_id) = asset_id {
+        primary_key.push_str(asset_id);
+    }
+
+    for prop_name in properties {
+        let prop_val = node.properties.get(prop_name);
+
+        match prop_val {
+            Some(val) => primary_key.push_str(&val.to_string()),
+            None => bail!(format!(
+                "Node is missing required property {} for identity",
+                prop_name
+            )),
+        }
+    }
+
+    // Push node type, as a natural partition. Sessions stored before this were suffixed by
+    // the unidentified node key, which no two nodes share, so none of them could be matched
+    // again anyway; they're left to expire with the retention window of compaction.
+    primary_key.push_str(&node.node_type);
+
+    Ok(primary_key)
+}
+
+/// The session lookup that `strategy` makes under `pseudo_key`
+fn session_lookup(strategy: &SessionStrategy, pseudo_key: String) -> Result<UnidSession, Error> {
+    let (timestamp, is_creation, is_termination) = match (
+        strategy.created_time != 0,
+        strategy.last_seen_time != 0,
+        strategy.terminated_time != 0,
+    ) {
+        (true, _, _) => (strategy.created_time, true, false),
+        (_, _, true) => (strategy.terminated_time, false, true),
+        (_, true, _) => (strategy.last_seen_time, false, false),
+        _ => bail!("At least one timestamp must be set"),
+    };
+
+    Ok(UnidSession {
+        pseudo_key,
+        timestamp,
+        is_creation,
+        is_termination,
+    })
+}
+
+/// The session lookup that identifying `node` makes, if it's identified by session and
+/// that lookup can be known without attributing it an asset id
+pub(crate) fn into_dynamic_unid_session(node: &DynamicNode) -> Option<UnidSession> {
+    let key = |requires_asset_id: bool, properties: &[String]| {
+        let asset_id = match requires_asset_id {
+            true => Some(node.get_asset_id()?),
+            false => None,
+        };
+        primary_key(node, asset_id, properties).ok()
+    };
+
+    if node.combine_id_strategies {
+        let mut session_strategy = None;
+        let mut partition = String::new();
+
+        for strategy in node.get_id_strategies() {
+            match strategy.strategy.as_ref()? {
+                id_strategy::Strategy::Session(strategy) => {
+                    if session_strategy.replace(strategy).is_some() {
+                        return None;
+                    }
+                }
+                id_strategy::Strategy::Static(strategy) => partition.push_str(&key(
+                    strategy.primary_key_requires_asset_id,
+                    &strategy.primary_key_properties,
+                )?),
+            }
+        }
+
+        let strategy = session_strategy?;
+        partition.push_str(&key(
+            strategy.primary_key_requires_asset_id,
+            &strategy.primary_key_properties,
+        )?);
+        return session_lookup(strategy, partition).ok();
+    }
+
+    // The first strategy that the node has every property for identifies it
+    for strategy in node.get_id_strategies() {
+        let strategy = strategy.strategy.as_ref()?;
+        if missing_property(node, strategy).is_some() {
+            continue;
+        }
+
+        return match strategy {
+            id_strategy::Strategy::Session(strategy) => {
+                let pseudo_key = key(
+                    strategy.primary_key_requires_asset_id,
+                    &strategy.primary_key_properties,
+                )?;
+                session_lookup(strategy, pseudo_key).ok()
+            }
+            id_strategy::Strategy::Static(_) => None,
+        };
+    }
+
+    None
+}
 
 #[derive(Debug, Clone)]
-pub struct DynamicMappingDb<D>
+pub struct DynamicMappingDb<S>
 where
-    D: DynamoDb,
+    S: MappingStorage,
 {
-    dyn_mapping_db: D,
+    dyn_mapping_db: S,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,81 +179,70 @@ pub struct DirectMapping {
     pub mapping: String,
 }
 
-impl<D> DynamicMappingDb<D>
+impl<S> DynamicMappingDb<S>
 where
-    D: DynamoDb,
+    S: MappingStorage,
 {
-    pub fn new(dyn_mapping_db: D) -> Self {
+    pub fn new(dyn_mapping_db: S) -> Self {
         Self { dyn_mapping_db }
     }
 
     pub async fn direct_map(&self, input: &str) -> Result<Option<String>, Error> {
-        let mut key: HashMap<String, AttributeValue> = HashMap::new();
-
-        key.insert(
-            "pseudo_key".to_owned(),
-            AttributeValue {
-                s: Some(input.to_owned()),
-                ..Default::default()
-            },
-        );
-
-        let query = GetItemInput {
-            consistent_read: Some(true),
-            table_name: grapl_config::static_mapping_table_name(),
-            key,
-            ..Default::default()
-        };
+        self.dyn_mapping_db.get_mapping(input).await
+    }
 
-        let item = wait_on!(self.dyn_mapping_db.get_item(query))?.item;
+    /// Maps `input` to `maps_to`, unless another writer mapped it first. Returns the node key
+    /// that `input` ends up mapped to, so that racing writers agree on one.
+    pub async fn create_mapping(&self, input: String, maps_to: String) -> Result<String, Error> {
+        info!("Creating dynamic mapping for: {} {}", input, maps_to);
+        if self
+            .dyn_mapping_db
+            .put_mapping(input.clone(), maps_to.clone())
+            .await?
+        {
+            return Ok(maps_to);
+        }
 
-        match item {
-            Some(item) => {
-                let mapping: ResolvedMapping = serde_dynamodb::from_hashmap(item.clone())?;
-                Ok(Some(mapping.mapping))
+        match self.dyn_mapping_db.get_mapping(&input).await? {
+            Some(existing) => {
+                info!("Lost the race to map {}, using {}", input, existing);
+                Ok(existing)
             }
-            None => Ok(None),
+            None => bail!("Mapping for {} was neither written nor found", input),
         }
     }
+}
 
-    pub async fn create_mapping(&self, input: String, maps_to: String) -> Result<(), Error> {
-        info!("Creating dynamic mapping for: {} {}", input, maps_to);
-        let mapping = DirectMapping {
-            pseudo_key: input,
-            mapping: maps_to,
-        };
-
-        let put_req = PutItemInput {
-            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
-            table_name: grapl_config::static_mapping_table_name(),
-            ..Default::default()
-        };
-
-        let _put_item_response = wait_on!(self.dyn_mapping_db.put_item(put_req))?;
-
-        Ok(())
-    }
+/// The outcome of identifying the dynamic nodes of a graph
+#[derive(Debug, Clone)]
+pub struct DynamicAttribution {
+    /// The graph with its dynamic nodes, and their edges, under their identified node keys
+    pub graph: Graph,
+    /// The node keys that dynamic nodes were identified as, keyed by their unid node keys
+    pub unid_id_map: HashMap<String, String>,
+    /// The dynamic nodes that failed identification, and why
+    pub dead_nodes: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone)]
-pub struct DynamicNodeIdentifier<D>
+pub struct DynamicNodeIdentifier<S>
 where
-    D: DynamoDb,
+    S: Storage,
 {
-    asset_identifier: AssetIdentifier<D>,
-    dyn_session_db: SessionDb<D>,
-    dyn_mapping_db: DynamicMappingDb<D>,
+    asset_identifier: AssetIdentifier<S>,
+    dyn_session_db: SessionDb<S>,
+    dyn_mapping_db: DynamicMappingDb<S>,
     should_guess: bool,
 }
 
-impl<D> DynamicNodeIdentifier<D>
+impl<S> DynamicNodeIdentifier<S>
 where
-    D: DynamoDb,
+    S: Storage,
 {
     pub fn new(
-        asset_identifier: AssetIdentifier<D>,
-        dyn_session_db: SessionDb<D>,
-        dyn_mapping_db: DynamicMappingDb<D>,
+        asset_identifier: AssetIdentifier<S>,
+        dyn_session_db: SessionDb<S>,
+        dyn_mapping_db: DynamicMappingDb<S>,
         should_guess: bool,
     ) -> Self {
         Self {
@@ -123,38 +258,10 @@ where
         node: &mut DynamicNode,
         strategy: &SessionStrategy,
     ) -> Result<String, Error> {
-        let mut primary_key = String::with_capacity(32);
-
-        if strategy.primary_key_requires_asset_id {
-            let asset_id = match node.get_asset_id() {
-                Some(asset_id) => asset_id.to_owned(),
-                None => {
-                    self.asset_identifier
-                        .attribute_asset_id(&node.clone().into())
-                        .await?
-                }
-            };
-
-            primary_key.push_str(&asset_id);
-            node.set_asset_id(asset_id);
-        }
-
-        for prop_name in &strategy.primary_key_properties {
-            let prop_val = node.properties.get(prop_name);
-
-            match prop_val {
-                Some(val) => primary_key.push_str(&val.to_string()),
-                None => bail!(format!(
-                    "Node is missing required property {} for identity",
-                    prop_name
-                )),
-            }
-        }
-
-        // Push node type, as a natural partition
-        primary_key.push_str(&node.node_key);
-
-        Ok(primary_key)
+        let asset_id = self
+            .required_asset_id(node, strategy.primary_key_requires_asset_id)
+            .await?;
+        primary_key(node, asset_id.as_deref(), &strategy.primary_key_properties)
     }
 
     async fn primary_mapping_key(
@@ -162,84 +269,85 @@ where
         node: &mut DynamicNode,
         strategy: &Static,
     ) -> Result<String, Error> {
-        let mut primary_key = String::with_capacity(32);
-
-        if strategy.primary_key_requires_asset_id {
-            let asset_id = match node.get_asset_id() {
-                Some(asset_id) => asset_id.to_owned(),
-                None => {
-                    self.asset_identifier
-                        .attribute_asset_id(&node.clone().into())
-                        .await?
-                }
-            };
+        let asset_id = self
+            .required_asset_id(node, strategy.primary_key_requires_asset_id)
+            .await?;
+        primary_key(node, asset_id.as_deref(), &strategy.primary_key_properties)
+    }
 
-            primary_key.push_str(&asset_id);
-            node.set_asset_id(asset_id);
+    /// Attributes `node` an asset id, if it needs one for identity and doesn't have one yet
+    async fn required_asset_id(
+        &self,
+        node: &mut DynamicNode,
+        requires_asset_id: bool,
+    ) -> Result<Option<String>, Error> {
+        if !requires_asset_id {
+            return Ok(None);
         }
 
-        for prop_name in &strategy.primary_key_properties {
-            let prop_val = node.properties.get(prop_name);
-
-            match prop_val {
-                Some(val) => primary_key.push_str(&val.to_string()),
-                None => bail!(format!(
-                    "Node is missing required property {} for identity",
-                    prop_name
-                )),
+        let asset_id = match node.get_asset_id() {
+            Some(asset_id) => asset_id.to_owned(),
+            None => {
+                self.asset_identifier
+                    .attribute_asset_id(&node.clone().into())
+                    .await?
             }
-        }
-        primary_key.push_str(&node.node_type);
+        };
 
-        Ok(primary_key)
+        node.set_asset_id(asset_id.clone());
+        Ok(Some(asset_id))
     }
 
     pub async fn attribute_dynamic_session(
         &self,
         node: DynamicNode,
         strategy: &SessionStrategy,
-    ) -> Result<DynamicNode, Error> {
+    ) -> Result<(DynamicNode, Provenance), Error> {
+        self.attribute_partitioned_session(node, strategy, String::new())
+            .await
+    }
+
+    /// Attributes `node` to a session whose pseudo key starts with `partition`
+    async fn attribute_partitioned_session(
+        &self,
+        node: DynamicNode,
+        strategy: &SessionStrategy,
+        partition: String,
+    ) -> Result<(DynamicNode, Provenance), Error> {
         let mut attributed_node = node.clone();
 
-        let primary_key = self
-            .primary_session_key(&mut attributed_node, strategy)
-            .await?;
+        let mut primary_key = partition;
+        primary_key.push_str(
+            &self
+                .primary_session_key(&mut attributed_node, strategy)
+                .await?,
+        );
 
-        let unid = match (strategy.created_time != 0, strategy.last_seen_time != 0) {
-            (true, _) => UnidSession {
-                pseudo_key: primary_key,
-                timestamp: strategy.created_time,
-                is_creation: true,
-            },
-            (_, true) => UnidSession {
-                pseudo_key: primary_key,
-                timestamp: strategy.last_seen_time,
-                is_creation: false,
-            },
-            _ => bail!("Terminating sessions not yet supported"),
-        };
+        let unid = session_lookup(strategy, primary_key)?;
 
-        let session_id = self
+        let pseudo_key = unid.pseudo_key.clone();
+        let session = self
             .dyn_session_db
-            .handle_unid_session(unid, self.should_guess)
+            .match_unid_session(unid, self.should_guess)
             .await?;
 
-        attributed_node.set_key(session_id);
+        attributed_node.set_key(session.session_id.clone());
 
-        Ok(attributed_node)
+        Ok((attributed_node, Provenance::session(pseudo_key, session)))
     }
 
     pub async fn attribute_static_mapping(
         &self,
         node: DynamicNode,
         strategy: &Static,
-    ) -> Result<DynamicNode, Error> {
+    ) -> Result<(DynamicNode, Provenance), Error> {
         let mut attributed_node = node.clone();
         let key = self
             .primary_mapping_key(&mut attributed_node, strategy)
             .await?;
 
         let node_key = self.dyn_mapping_db.direct_map(&key).await?;
+        let provenance = Provenance::static_mapping(Strategy::StaticMapping, key.clone());
 
         match node_key {
             Some(node_key) => attributed_node.set_key(node_key),
@@ -248,53 +356,123 @@ where
                 // we don't find it just make it
                 let new_id = uuid::Uuid::new_v4().to_string();
                 info!("Creating static mapping for dynamic node");
-                self.dyn_mapping_db
-                    .create_mapping(key, new_id.clone())
-                    .await?;
-                attributed_node.set_key(new_id)
+                let node_key = self.dyn_mapping_db.create_mapping(key, new_id).await?;
+                attributed_node.set_key(node_key)
             }
         }
 
-        Ok(attributed_node)
+        Ok((attributed_node, provenance))
     }
 
     pub async fn attribute_dynamic_node(&self, node: &DynamicNode) -> Result<DynamicNode, Error> {
-        let mut attributed_node = node.clone();
-        for strategy in node.get_id_strategies() {
-            match strategy.strategy.as_ref().unwrap() {
+        let (attributed_node, _provenance) = self.identify_dynamic_node(node).await?;
+        Ok(attributed_node)
+    }
+
+    /// Attributes `node` like `attribute_dynamic_node`, also describing how its node key was
+    /// chosen
+    pub async fn identify_dynamic_node(
+        &self,
+        node: &DynamicNode,
+    ) -> Result<(DynamicNode, Provenance), Error> {
+        if node.combine_id_strategies {
+            return self.identify_by_combined_strategies(node).await;
+        }
+
+        let strategies = node.get_id_strategies();
+        if strategies.is_empty() {
+            return Ok((node.clone(), Provenance::intrinsic()));
+        }
+
+        // The first strategy that the node has every property for identifies it
+        for strategy in strategies {
+            let strategy = match strategy.strategy.as_ref() {
+                Some(strategy) => strategy,
+                None => bail!("IdStrategy does not specify a strategy"),
+            };
+
+            if let Some(missing) = missing_property(node, strategy) {
+                info!(
+                    "Dynamic node is missing {}, falling back to its next strategy",
+                    missing
+                );
+                continue;
+            }
+
+            return match strategy {
                 id_strategy::Strategy::Session(ref strategy) => {
                     info!("Attributing dynamic node via session");
-                    attributed_node = self
-                        .attribute_dynamic_session(attributed_node, &strategy)
-                        .await?;
+                    self.attribute_dynamic_session(node.clone(), strategy).await
                 }
                 id_strategy::Strategy::Static(ref strategy) => {
                     info!("Attributing dynamic node via static mapping");
-                    attributed_node = self
-                        .attribute_static_mapping(attributed_node, &strategy)
+                    self.attribute_static_mapping(node.clone(), strategy).await
+                }
+            };
+        }
+
+        bail!(
+            "Node is missing required properties for each of its {} id strategies",
+            strategies.len()
+        )
+    }
+
+    /// Identifies `node` by its one session strategy, keeping the sessions of nodes whose
+    /// static strategies differ apart
+    async fn identify_by_combined_strategies(
+        &self,
+        node: &DynamicNode,
+    ) -> Result<(DynamicNode, Provenance), Error> {
+        let mut attributed_node = node.clone();
+        let mut session_strategy = None;
+        let mut partition = String::new();
+
+        for strategy in node.get_id_strategies() {
+            match strategy.strategy.as_ref() {
+                Some(id_strategy::Strategy::Session(strategy)) => {
+                    if session_strategy.replace(strategy).is_some() {
+                        bail!("Combined id strategies may only include one session strategy");
+                    }
+                }
+                Some(id_strategy::Strategy::Static(strategy)) => {
+                    let key = self
+                        .primary_mapping_key(&mut attributed_node, strategy)
                         .await?;
+                    partition.push_str(&key);
                 }
+                None => bail!("IdStrategy does not specify a strategy"),
             }
         }
 
-        Ok(attributed_node)
+        match session_strategy {
+            Some(strategy) => {
+                info!("Attributing dynamic node via combined strategies");
+                self.attribute_partitioned_session(attributed_node, strategy, partition)
+                    .await
+            }
+            None => bail!("Combined id strategies must include a session strategy"),
+        }
     }
 
-    pub async fn attribute_dynamic_nodes(
-        &self,
-        unid_graph: Graph,
-        _unid_id_map: &mut HashMap<String, String>,
-    ) -> Result<Graph, Graph> {
+    /// Identifies every dynamic node in `unid_graph`, leaving its other nodes as they are.
+    ///
+    /// Dynamic nodes that are identified as the same node are merged, and edges follow their
+    /// nodes to their new node keys. Nodes that fail identification are removed, along with
+    /// any edge touching them, and reported in `DynamicAttribution::dead_nodes`.
+    pub async fn attribute_dynamic_nodes(&self, unid_graph: &Graph) -> DynamicAttribution {
         let mut unid_id_map = HashMap::new();
-        let mut dead_nodes = HashSet::new();
+        // Other nodes keep their node keys
+        let mut node_keys = HashMap::with_capacity(unid_graph.nodes.len());
+        let mut dead_nodes = HashMap::new();
         let mut output_graph = Graph::new(unid_graph.timestamp);
-        output_graph.edges = unid_graph.edges;
+        output_graph.edges = unid_graph.edges.clone();
 
-        for node in unid_graph.nodes.values() {
+        for (node_key, node) in unid_graph.nodes.iter() {
             let dynamic_node = match node.as_dynamic_node() {
                 Some(n) => n,
                 _ => {
-                    output_graph.add_node(node.clone());
+                    node_keys.insert(node_key.clone(), node_key.clone());
+                    output_graph.nodes.insert(node_key.clone(), node.clone());
                     continue;
                 }
             };
@@ -303,28 +481,35 @@ where
                 Ok(node) => node,
                 Err(e) => {
                     warn!("Failed to attribute dynamic node: {}", e);
-                    dead_nodes.insert(node.get_node_key());
+                    dead_nodes.insert(node_key.clone(), e.to_string());
                     continue;
                 }
             };
 
             info!("Attributed DynamicNode");
 
-            unid_id_map.insert(node.clone_node_key(), new_node.clone_node_key());
-            output_graph.add_node(new_node);
+            unid_id_map.insert(node_key.clone(), new_node.clone_node_key());
+            node_keys.insert(node_key.clone(), new_node.clone_node_key());
+            // Keyed by its unid node key until it's remapped
+            output_graph.nodes.insert(node_key.clone(), new_node.into());
         }
 
-        //
-        //        remap_nodes(&mut output_graph, &unid_id_map);
-        //        remap_edges(&mut output_graph, &unid_id_map);
-        //        remove_dead_edges(&mut output_graph);
+        let dead_node_keys: HashSet<&str> = dead_nodes.keys().map(String::as_str).collect();
+        remove_dead_nodes(&mut output_graph, &dead_node_keys);
+        remap_nodes(&mut output_graph, &node_keys);
+        remap_edges(&mut output_graph, &node_keys);
+        remove_dead_edges(&mut output_graph);
 
         if dead_nodes.is_empty() {
             info!("Attributed all dynamic nodes");
-            Ok(output_graph)
         } else {
             warn!("Failed to attribute {} dynamic nodes", dead_nodes.len());
-            Err(output_graph)
+        }
+
+        DynamicAttribution {
+            graph: output_graph,
+            unid_id_map,
+            dead_nodes,
         }
     }
 }
@@ -0,0 +1,138 @@
+//! File sessions are keyed by path, but the same file is often reported under different
+//! spellings of its path, ie: `C:\Windows\System32\CMD.EXE` and `c:/windows/system32/cmd.exe`.
+//! Paths are normalised before keying, and then hashed, so that long paths don't blow past
+//! DynamoDB's key size limits. The node itself keeps the path as it was reported, for display.
+//!
+//! File nodes don't say which OS they came from, so Windows paths are recognized by their
+//! shape. Anything else is treated as a case sensitive POSIX path.
+
+use sha2::Digest;
+
+/// Machine wide environment variables, at the defaults that nearly every host keeps. Variables
+/// that differ per user can't be expanded, and are left in place.
+const WINDOWS_ENV_VARS: &[(&str, &str)] = &[
+    ("%systemroot%", r"c:\windows"),
+    ("%windir%", r"c:\windows"),
+    ("%systemdrive%", "c:"),
+    ("%programfiles%", r"c:\program files"),
+    ("%programfiles(x86)%", r"c:\program files (x86)"),
+    ("%programw6432%", r"c:\program files"),
+    ("%commonprogramfiles%", r"c:\program files\common files"),
+    ("%programdata%", r"c:\programdata"),
+    ("%allusersprofile%", r"c:\programdata"),
+];
+
+/// The alternate data streams that browsers and mail clients tag downloads with
+const ZONE_IDENTIFIER_STREAMS: &[&str] = &[":zone.identifier:$data", ":zone.identifier"];
+
+/// The key a file's sessions are stored under, on the asset it was seen on
+pub fn file_path_key(path: &str) -> String {
+    let mut hasher = sha2::Sha256::default();
+    hasher.input(normalize_file_path(path).as_bytes());
+    hex::encode(hasher.result())
+}
+
+/// Reduces each spelling of a path to one form
+pub fn normalize_file_path(path: &str) -> String {
+    if is_windows_path(path) {
+        normalize_windows_path(path)
+    } else {
+        collapse_separators(path, '/', "")
+    }
+}
+
+fn is_windows_path(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    let has_drive_letter = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+
+    has_drive_letter || path.contains('\\') || path.starts_with('%')
+}
+
+fn normalize_windows_path(path: &str) -> String {
+    // Windows paths are case insensitive
+    let mut path = path.to_lowercase().replace('/', r"\");
+
+    // Strip the prefixes that bypass path parsing, or name the NT object namespace
+    if let Some(share) = path.strip_prefix(r"\\?\unc\") {
+        path = format!(r"\\{}", share);
+    } else if let Some(rest) = path
+        .strip_prefix(r"\\?\")
+        .or_else(|| path.strip_prefix(r"\??\"))
+    {
+        path = rest.to_owned();
+    }
+    if let Some(rest) = path.strip_prefix(r"\systemroot\") {
+        path = format!(r"c:\windows\{}", rest);
+    }
+
+    for (var, value) in WINDOWS_ENV_VARS {
+        if path.contains(var) {
+            path = path.replace(var, value);
+        }
+    }
+
+    for stream in ZONE_IDENTIFIER_STREAMS {
+        if let Some(rest) = path.strip_suffix(stream) {
+            path = rest.to_owned();
+            break;
+        }
+    }
+
+    // UNC paths keep their leading pair of separators
+    let root = if path.starts_with(r"\\") { r"\\" } else { "" };
+    collapse_separators(&path[root.len()..], '\\', root)
+}
+
+/// Squashes runs of `separator` into one, after `root`
+fn collapse_separators(path: &str, separator: char, root: &str) -> String {
+    let mut collapsed = String::with_capacity(root.len() + path.len());
+    collapsed.push_str(root);
+
+    let mut last = None;
+    for c in path.chars() {
+        if c == separator && last == Some(separator) {
+            continue;
+        }
+        collapsed.push(c);
+        last = Some(c);
+    }
+
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_spellings_share_a_key() {
+        let key = file_path_key(r"C:\Windows\System32\CMD.EXE");
+
+        for path in &[
+            r"c:\windows\system32\cmd.exe",
+            "C:/Windows/System32/cmd.exe",
+            r"C:\Windows\\System32\cmd.exe",
+            r"\\?\C:\Windows\System32\cmd.exe",
+            r"\??\C:\Windows\System32\cmd.exe",
+            r"%SystemRoot%\System32\cmd.exe",
+            r"\SystemRoot\System32\cmd.exe",
+            r"C:\Windows\System32\cmd.exe:Zone.Identifier",
+        ] {
+            assert_eq!(file_path_key(path), key, "{}", path);
+        }
+    }
+
+    #[test]
+    fn posix_paths_stay_case_sensitive() {
+        assert_eq!(normalize_file_path("/usr//bin/ls"), "/usr/bin/ls");
+        assert_ne!(file_path_key("/tmp/a"), file_path_key("/tmp/A"));
+    }
+
+    #[test]
+    fn unc_paths_keep_their_root() {
+        assert_eq!(
+            normalize_file_path(r"\\?\UNC\Server\Share\\File.txt"),
+            r"\\server\share\file.txt"
+        );
+    }
+}
@@ -23,7 +23,7 @@ use lambda_runtime::Context;
 use log::*;
 use prost::Message;
 use rusoto_core::{HttpClient, Region};
-use rusoto_dynamodb::{DynamoDb, DynamoDbClient};
+use rusoto_dynamodb::DynamoDbClient;
 use rusoto_s3::S3Client;
 use rusoto_sqs::{SendMessageRequest, Sqs, SqsClient};
 use sha2::Digest;
@@ -35,8 +35,9 @@ use sqs_lambda::local_sqs_service::local_sqs_service_with_options;
 use sqs_lambda::local_sqs_service_options::LocalSqsServiceOptionsBuilder;
 use sqs_lambda::redis_cache::RedisCache;
 
-use assetdb::{AssetIdDb, AssetIdentifier};
+use assetdb::{AssetIdDb, AssetIdentifier, AssetSkew, MappingConflict};
 use dynamic_sessiondb::{DynamicMappingDb, DynamicNodeIdentifier};
+use futures::stream::StreamExt;
 use grapl_graph_descriptions::file::FileState;
 use grapl_graph_descriptions::graph_description::host::*;
 use grapl_graph_descriptions::graph_description::node::WhichNode;
@@ -46,8 +47,20 @@ use grapl_graph_descriptions::network_connection::NetworkConnectionState;
 use grapl_graph_descriptions::node::NodeT;
 use grapl_graph_descriptions::process_inbound_connection::ProcessInboundConnectionState;
 use grapl_graph_descriptions::process_outbound_connection::ProcessOutboundConnectionState;
-use sessiondb::SessionDb;
-use sessions::UnidSession;
+use lookup::{group_lookups, LookupGroup, LookupOptions};
+use metrics::NodeIdentifierMetrics;
+use provenance::{Provenance, Strategy};
+use retry::{
+    IdentifierInput, IdentifierInputDecoder, RetryEmitter, RetryPayload, RetryPolicy,
+    S3RetryEmitter,
+};
+use sessiondb::{SessionDb, SkewTolerance};
+use sessions::{SessionMatchKind, UnidSession};
+#[cfg(feature = "sqlite")]
+use storage::SqliteStorage;
+use storage::{
+    AssetIdStorage, CacheOptions, CachingStorage, CountingStorage, LookupCache, Storage,
+};
 
 macro_rules! wait_on {
     ($x:expr) => {{
@@ -57,52 +70,362 @@ macro_rules! wait_on {
 }
 
 pub mod assetdb;
+pub mod compaction;
 pub mod dynamic_sessiondb;
-
+pub mod file_paths;
+mod lookup;
+mod metrics;
+pub mod provenance;
+pub mod retry;
 pub mod sessiondb;
 pub mod sessions;
+pub mod storage;
+
+/// Tunables of the node identifier, read from the environment
+#[derive(Debug, Clone)]
+struct IdentifierOptions {
+    lookup: LookupOptions,
+    skew: SkewTolerance,
+    /// Whether to log how each node key was chosen, see `provenance`
+    record_provenance: bool,
+    retry: RetryPolicy,
+}
+
+impl IdentifierOptions {
+    fn from_env() -> Self {
+        Self {
+            lookup: LookupOptions::from_env(),
+            skew: SkewTolerance::from_env(),
+            record_provenance: env_or("NODE_IDENTIFIER_RECORD_PROVENANCE", false),
+            retry: RetryPolicy::from_env(),
+        }
+    }
+}
 
 #[derive(Clone)]
-struct NodeIdentifier<D, CacheT>
+struct NodeIdentifier<S, CacheT, R>
 where
-    D: DynamoDb + Clone + Send + Sync + 'static,
+    S: Storage,
     CacheT: Cache + Clone + Send + Sync + 'static,
+    R: RetryEmitter,
 {
-    asset_mapping_db: AssetIdDb<D>,
-    dynamic_identifier: DynamicNodeIdentifier<D>,
-    asset_identifier: AssetIdentifier<D>,
-    node_id_db: D,
+    node_id_db: S,
     should_default: bool,
     cache: CacheT,
-    region: Region,
+    options: IdentifierOptions,
+    lookup_cache: LookupCache,
+    retry_emitter: R,
+    metrics: NodeIdentifierMetrics,
 }
 
-impl<D, CacheT> NodeIdentifier<D, CacheT>
+impl<S, CacheT, R> NodeIdentifier<S, CacheT, R>
 where
-    D: DynamoDb + Clone + Send + Sync + 'static,
+    S: Storage,
     CacheT: Cache + Clone + Send + Sync + 'static,
+    R: RetryEmitter,
 {
     pub fn new(
-        asset_mapping_db: AssetIdDb<D>,
-        dynamic_identifier: DynamicNodeIdentifier<D>,
-        asset_identifier: AssetIdentifier<D>,
-        node_id_db: D,
+        node_id_db: S,
         should_default: bool,
         cache: CacheT,
-        region: Region,
+        options: IdentifierOptions,
+        lookup_cache: LookupCache,
+        retry_emitter: R,
+        metrics: NodeIdentifierMetrics,
     ) -> Self {
         Self {
-            asset_mapping_db,
-            dynamic_identifier,
-            asset_identifier,
             node_id_db,
             should_default,
             cache,
-            region,
+            options,
+            lookup_cache,
+            retry_emitter,
+            metrics,
+        }
+    }
+
+    async fn identify_subgraphs(
+        &mut self,
+        attributor: &NodeKeyAttributor<impl Storage>,
+        input: IdentifierInput,
+    ) -> OutputEvent<GeneratedSubgraphs, sqs_lambda::error::Error> {
+        warn!("node-identifier.handle_event");
+
+        info!("Handling raw event");
+
+        // Retried nodes arrive with the node keys their neighbours were already identified as
+        let (subgraphs, attempts, known_node_keys) = match input {
+            IdentifierInput::Subgraphs(subgraphs) => (subgraphs.subgraphs, 0, HashMap::new()),
+            IdentifierInput::Retry(payload) => {
+                info!(
+                    "Retrying {} nodes, previously attempted {} times",
+                    payload.failures.len(),
+                    payload.attempts
+                );
+                (
+                    vec![payload.subgraph],
+                    payload.attempts,
+                    payload.identified_node_keys,
+                )
+            }
+        };
+
+        if subgraphs.is_empty() {
+            warn!("Received empty unid subgraph");
+            return OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![])));
+        }
+
+        // Merge all of the subgraphs into one subgraph to avoid
+        // redundant work
+        let unid_subgraph =
+            subgraphs
+                .into_iter()
+                .fold(Graph::new(0), |mut total_graph, subgraph| {
+                    info!(
+                        "Merging subgraph with: {} nodes {} edges",
+                        subgraph.nodes.len(),
+                        subgraph.edges.len()
+                    );
+                    total_graph.merge(&subgraph);
+                    total_graph
+                });
+
+        if unid_subgraph.is_empty() {
+            warn!("Received empty subgraph");
+            return OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![])));
+        }
+
+        info!(
+            "unid_subgraph: {} nodes {} edges",
+            unid_subgraph.nodes.len(),
+            unid_subgraph.edges.len(),
+        );
+
+        // Create any implicit asset id mappings
+        if let Err(e) = create_asset_id_mappings(&attributor.asset_mapping_db, &unid_subgraph).await
+        {
+            error!("Asset mapping creation failed with {}", e);
+            return OutputEvent::new(Completion::Error(
+                sqs_lambda::error::Error::ProcessingError(e.to_string()),
+            ));
+        }
+
+        // Map all host_ids into asset_ids. This has to happen before node key
+        // identification.
+        // Nodes that fail are retried on their own, along with any that fail identification,
+        // while we continue with whatever subgraph has succeeded
+        let (output_subgraph, mut failures) =
+            attribute_asset_ids(&attributor.asset_identifier, &unid_subgraph).await;
+
+        let mut unid_id_map = HashMap::new();
+
+        let mut identified_graph = Graph::new(output_subgraph.timestamp);
+        let mut uncached_nodes = Vec::with_capacity(output_subgraph.nodes.len());
+        for (old_node_key, old_node) in output_subgraph.nodes.iter() {
+            match self.cache.get(old_node_key.clone()).await {
+                Ok(CacheResponse::Hit) => {
+                    info!("Got cache hit for old_node_key, skipping node.");
+                    continue;
+                }
+                Err(e) => warn!("Failed to retrieve from cache: {:?}", e),
+                _ => (),
+            };
+            uncached_nodes.push((old_node_key.to_owned(), old_node.clone()));
+        }
+
+        let node_count = uncached_nodes.len();
+        let chains = group_lookups(uncached_nodes, self.options.skew.precision_ms);
+        let group_count = chains.iter().map(Vec::len).sum();
+        info!("Grouped {} nodes into {} lookups", node_count, group_count);
+        self.metrics.report_lookups(node_count, group_count);
+
+        // Chains never share a session timeline, so they're safe to resolve concurrently
+        let attributed_chains: Vec<_> = futures::stream::iter(chains)
+            .map(|chain| attributor.attribute_chain(chain))
+            .buffer_unordered(self.options.lookup.concurrency)
+            .collect()
+            .await;
+
+        for (group, attributed) in attributed_chains.into_iter().flatten() {
+            let (attributed, provenance) = match attributed {
+                Ok(attributed) => attributed,
+                Err(e) => {
+                    warn!("Failed to attribute node_key with: {}", e);
+                    for (old_node_key, _) in group.members {
+                        failures.insert(old_node_key, e.to_string());
+                    }
+                    continue;
+                }
+            };
+
+            let representative = group.representative;
+            for (index, (old_node_key, mut node)) in group.members.into_iter().enumerate() {
+                if index == representative {
+                    node = attributed.clone();
+                } else {
+                    node.set_node_key(attributed.clone_node_key());
+                }
+                if self.options.record_provenance {
+                    provenance::record(&old_node_key, node.get_node_key(), &provenance);
+                }
+                unid_id_map.insert(old_node_key, node.clone_node_key());
+                identified_graph.add_node(node);
+            }
+        }
+
+        info!(
+            "PRE: identified_graph.edges.len() {}",
+            identified_graph.edges.len()
+        );
+
+        let identified = |node_key| {
+            unid_id_map
+                .get(node_key)
+                .or_else(|| known_node_keys.get(node_key))
+        };
+        for (old_key, edge_list) in unid_subgraph.edges.iter() {
+            if failures.contains_key(old_key) {
+                continue;
+            };
+
+            for edge in &edge_list.edges {
+                let (from_key, to_key) = match (identified(&edge.from), identified(&edge.to)) {
+                    (Some(from_key), Some(to_key)) => (from_key, to_key),
+                    _ => continue,
+                };
+
+                identified_graph.add_edge(
+                    edge.edge_name.to_owned(),
+                    from_key.to_owned(),
+                    to_key.to_owned(),
+                );
+            }
+        }
+
+        info!(
+            "POST: identified_graph.edges.len() {}",
+            identified_graph.edges.len()
+        );
+
+        // Failed nodes are retried on their own. Only if that isn't possible is the event
+        // left to be redelivered as a whole.
+        let mut unretried_failure = None;
+        if !failures.is_empty() {
+            let known_node_keys = unid_id_map
+                .iter()
+                .chain(known_node_keys.iter())
+                .map(|(unid, identified)| (unid.clone(), identified.clone()))
+                .collect();
+            let payload =
+                RetryPayload::new(attempts + 1, &unid_subgraph, failures, &known_node_keys);
+            unretried_failure = self.retry(payload).await;
+        }
+
+        if identified_graph.is_empty() {
+            if let Some(failure) = unretried_failure {
+                return OutputEvent::new(Completion::Error(
+                    sqs_lambda::error::Error::ProcessingError(format!(
+                        "All nodes failed to identify: {}",
+                        failure
+                    )),
+                ));
+            }
+        }
+
+        let identities: Vec<_> = unid_id_map.keys().cloned().collect();
+
+        let mut completed = if let Some(failure) = unretried_failure {
+            info!("Partial Success, identified {} nodes", identities.len());
+            OutputEvent::new(Completion::Partial((
+                GeneratedSubgraphs::new(vec![identified_graph]),
+                sqs_lambda::error::Error::ProcessingError(failure),
+            )))
+        } else {
+            info!("Identified {} nodes", identities.len());
+            OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![
+                identified_graph,
+            ])))
+        };
+
+        identities
+            .into_iter()
+            .for_each(|identity| completed.add_identity(identity));
+
+        completed
+    }
+
+    /// Emits `payload` to be retried, or drops it once it has used up its attempts. Returns
+    /// the reason the nodes failed if they could be neither.
+    async fn retry(&mut self, payload: RetryPayload) -> Option<String> {
+        let failed = payload.failures.len();
+        if payload.attempts >= self.options.retry.max_attempts {
+            for failure in &payload.failures {
+                error!(
+                    "Dropping {} after {} attempts, last failed with: {}",
+                    failure.node_key, payload.attempts, failure.reason
+                );
+            }
+            self.metrics.report_failed_nodes(0, failed);
+            return None;
+        }
+
+        match self.retry_emitter.emit_retry(&payload).await {
+            Ok(()) => {
+                self.metrics.report_failed_nodes(failed, 0);
+                None
+            }
+            Err(e) => {
+                error!("Failed to emit {} nodes for retry: {}", failed, e);
+                Some(format!(
+                    "{} nodes failed identification, and could not be retried: {}",
+                    failed, e
+                ))
+            }
+        }
+    }
+}
+
+/// Attributes node keys against a single storage backend
+pub struct NodeKeyAttributor<S>
+where
+    S: Storage,
+{
+    asset_mapping_db: AssetIdDb<S>,
+    dynamic_identifier: DynamicNodeIdentifier<S>,
+    asset_identifier: AssetIdentifier<S>,
+    node_id_db: S,
+    should_default: bool,
+    skew: SkewTolerance,
+}
+
+impl<S> NodeKeyAttributor<S>
+where
+    S: Storage,
+{
+    pub fn new(node_id_db: S, should_default: bool, skew: SkewTolerance) -> Self {
+        let dynamic_identifier = DynamicNodeIdentifier::new(
+            AssetIdentifier::new(AssetIdDb::new(node_id_db.clone())),
+            SessionDb::new(
+                node_id_db.clone(),
+                grapl_config::dynamic_session_table_name(),
+            )
+            .with_skew(skew, 0),
+            DynamicMappingDb::new(node_id_db.clone()),
+            should_default,
+        );
+
+        Self {
+            asset_mapping_db: AssetIdDb::new(node_id_db.clone()),
+            dynamic_identifier,
+            asset_identifier: AssetIdentifier::new(AssetIdDb::new(node_id_db.clone())),
+            node_id_db,
+            should_default,
+            skew,
         }
     }
 
-    async fn attribute_node_key(&self, node: Node) -> Result<Node, Error> {
+    /// Attributes `node`, along with how its node key was chosen
+    pub async fn attribute_node_key(&self, node: Node) -> Result<(Node, Provenance), Error> {
         let unid = into_unid_session(&node)?;
 
         match node.which_node {
@@ -112,17 +435,17 @@ where
                     Some(unid) => unid,
                     None => bail!("Could not identify ProcessNode"),
                 };
-                let session_db = SessionDb::new(
-                    self.node_id_db.clone(),
-                    grapl_config::process_history_table_name(),
-                );
-                let node_key = session_db
-                    .handle_unid_session(unid, self.should_default)
-                    .await?;
+
+                let (node_key, provenance) = if process_node.process_guid.is_empty() {
+                    self.attribute_process_session(process_node.get_asset_id(), unid)
+                        .await?
+                } else {
+                    self.attribute_process_guid(&process_node, unid).await?
+                };
 
                 info!("Mapped Process {:?} to {}", process_node, &node_key,);
                 process_node.set_node_key(node_key);
-                Ok(process_node.into())
+                Ok((process_node.into(), provenance))
             }
             Some(WhichNode::FileNode(mut file_node)) => {
                 info!("Attributing FileNode");
@@ -130,16 +453,16 @@ where
                     Some(unid) => unid,
                     None => bail!("Could not identify FileNode"),
                 };
-                let session_db = SessionDb::new(
-                    self.node_id_db.clone(),
-                    grapl_config::file_history_table_name(),
-                );
-                let node_key = session_db
-                    .handle_unid_session(unid, self.should_default)
+                let table_name = grapl_config::file_history_table_name();
+                let unid = self
+                    .with_legacy_file_key(&table_name, &file_node, unid)
                     .await?;
+                // A file's sessions are ordered by whichever processes touched it, so no
+                // clock skew is learned or applied for them
+                let (node_key, provenance) = self.attribute_session(table_name, None, unid).await?;
 
                 file_node.set_node_key(node_key);
-                Ok(file_node.into())
+                Ok((file_node.into(), provenance))
             }
             Some(WhichNode::ProcessInboundConnectionNode(mut inbound_node)) => {
                 info!("Attributing ProcessInboundConnectionNode");
@@ -147,16 +470,16 @@ where
                     Some(unid) => unid,
                     None => bail!("Could not identify ProcessInboundConnectionNode"),
                 };
-                let session_db = SessionDb::new(
-                    self.node_id_db.clone(),
-                    grapl_config::inbound_connection_history_table_name(),
-                );
-                let node_key = session_db
-                    .handle_unid_session(unid, self.should_default)
+                let (node_key, provenance) = self
+                    .attribute_session(
+                        grapl_config::inbound_connection_history_table_name(),
+                        inbound_node.get_asset_id(),
+                        unid,
+                    )
                     .await?;
 
                 inbound_node.set_node_key(node_key);
-                Ok(inbound_node.into())
+                Ok((inbound_node.into(), provenance))
             }
             Some(WhichNode::ProcessOutboundConnectionNode(mut outbound_node)) => {
                 info!("Attributing ProcessOutboundConnectionNode");
@@ -164,16 +487,16 @@ where
                     Some(unid) => unid,
                     None => bail!("Could not identify ProcessOutboundConnectionNode"),
                 };
-                let session_db = SessionDb::new(
-                    self.node_id_db.clone(),
-                    grapl_config::outbound_connection_history_table_name(),
-                );
-                let node_key = session_db
-                    .handle_unid_session(unid, self.should_default)
+                let (node_key, provenance) = self
+                    .attribute_session(
+                        grapl_config::outbound_connection_history_table_name(),
+                        outbound_node.get_asset_id(),
+                        unid,
+                    )
                     .await?;
 
                 outbound_node.set_node_key(node_key);
-                Ok(outbound_node.into())
+                Ok((outbound_node.into(), provenance))
             }
             Some(WhichNode::AssetNode(mut asset_node)) => {
                 info!("Attributing AssetNode");
@@ -184,13 +507,13 @@ where
 
                 // AssetNodes have a node_key equal to their asset_id
                 asset_node.set_node_key(asset_id);
-                Ok(asset_node.into())
+                Ok((asset_node.into(), Provenance::intrinsic()))
             }
             // IpAddress nodes are identified at construction
             Some(WhichNode::IpAddressNode(mut ip_node)) => {
                 ip_node.set_node_key(ip_node.ip_address.clone());
                 info!("Attributing IpAddressNode");
-                Ok(ip_node.into())
+                Ok((ip_node.into(), Provenance::intrinsic()))
             }
             // The identity of an IpPortNode is the hash of its ip, port, and protocol
             Some(WhichNode::IpPortNode(mut ip_port)) => {
@@ -206,7 +529,7 @@ where
 
                 ip_port.set_node_key(node_key);
 
-                Ok(ip_port.into())
+                Ok((ip_port.into(), Provenance::intrinsic()))
             }
             Some(WhichNode::NetworkConnectionNode(mut network_connection_node)) => {
                 info!("Attributing NetworkConnectionNode");
@@ -214,16 +537,16 @@ where
                     Some(unid) => unid,
                     None => bail!("Could not identify NetworkConnectionNode"),
                 };
-                let session_db = SessionDb::new(
-                    self.node_id_db.clone(),
-                    grapl_config::network_connection_history_table_name(),
-                );
-                let node_key = session_db
-                    .handle_unid_session(unid, self.should_default)
+                let (node_key, provenance) = self
+                    .attribute_session(
+                        grapl_config::network_connection_history_table_name(),
+                        None,
+                        unid,
+                    )
                     .await?;
 
                 network_connection_node.set_node_key(node_key);
-                Ok(network_connection_node.into())
+                Ok((network_connection_node.into(), provenance))
             }
             Some(WhichNode::IpConnectionNode(mut ip_connection_node)) => {
                 info!("Attributing IpConnectionNode");
@@ -231,41 +554,207 @@ where
                     Some(unid) => unid,
                     None => bail!("Could not identify IpConnectionNode"),
                 };
-                let session_db = SessionDb::new(
-                    self.node_id_db.clone(),
-                    grapl_config::ip_connection_history_table_name(),
-                );
-                let node_key = session_db
-                    .handle_unid_session(unid, self.should_default)
+                let (node_key, provenance) = self
+                    .attribute_session(grapl_config::ip_connection_history_table_name(), None, unid)
                     .await?;
 
                 ip_connection_node.set_node_key(node_key);
-                Ok(ip_connection_node.into())
+                Ok((ip_connection_node.into(), provenance))
             }
             Some(WhichNode::DynamicNode(ref dynamic_node)) => {
                 info!("Attributing DynamicNode");
-                let new_node = self
+                let (new_node, provenance) = self
                     .dynamic_identifier
-                    .attribute_dynamic_node(&dynamic_node)
+                    .identify_dynamic_node(dynamic_node)
                     .await?;
-                Ok(new_node.into())
+                Ok((new_node.into(), provenance))
             }
             None => bail!("Unknown Node Variant"),
         }
     }
+
+    /// Attributes the representative of each group in turn, as later lookups against a session
+    /// timeline depend on the sessions created or extended by earlier ones
+    async fn attribute_chain(
+        &self,
+        chain: Vec<LookupGroup>,
+    ) -> Vec<(LookupGroup, Result<(Node, Provenance), Error>)> {
+        let mut attributed = Vec::with_capacity(chain.len());
+        for group in chain {
+            let node = self
+                .attribute_node_key(group.representative().clone())
+                .await;
+            attributed.push((group, node));
+        }
+        attributed
+    }
+
+    /// File sessions used to be keyed by the raw file path. Until a file has a session under
+    /// its normalised key, events that fall within a live session stored under the raw path
+    /// keep resolving to it, so that files seen before the change keep their node keys.
+    async fn with_legacy_file_key(
+        &self,
+        table_name: &str,
+        file_node: &File,
+        unid: UnidSession,
+    ) -> Result<UnidSession, Error> {
+        let session_db = SessionDb::new(self.node_id_db.clone(), table_name);
+        if session_db.find_last_session_before(&unid).await?.is_some() {
+            return Ok(unid);
+        }
+
+        let legacy = UnidSession {
+            pseudo_key: format!(
+                "{}{}",
+                file_node
+                    .get_asset_id()
+                    .expect("FileNode must have asset_id"),
+                file_node.file_path
+            ),
+            ..unid.clone()
+        };
+
+        match session_db.find_last_session_before(&legacy).await? {
+            Some(session) if !session.is_end_canon || legacy.timestamp <= session.end_time => {
+                info!("Found file session under its legacy pseudo key");
+                Ok(legacy)
+            }
+            _ => Ok(unid),
+        }
+    }
+
+    /// Identifies `unid` within the sessions in `table_name`, allowing for the clock skew
+    /// learned for `asset_id`, and learning from any skew that the event itself reveals
+    async fn attribute_session(
+        &self,
+        table_name: String,
+        asset_id: Option<&str>,
+        unid: UnidSession,
+    ) -> Result<(String, Provenance), Error> {
+        let seen_at = unid.timestamp;
+        let asset_skew = match asset_id {
+            Some(asset_id) => {
+                self.asset_mapping_db
+                    .asset_skew(asset_id, seen_at, self.skew.learned_ttl_ms)
+                    .await?
+            }
+            None => 0,
+        };
+
+        let session_db =
+            SessionDb::new(self.node_id_db.clone(), table_name).with_skew(self.skew, asset_skew);
+        let pseudo_key = unid.pseudo_key.clone();
+        let session = session_db
+            .match_unid_session(unid, self.should_default)
+            .await?;
+
+        // Recording the skew again, even if it's no wider, keeps it from expiring
+        if let (Some(asset_id), Some(observed)) = (asset_id, session_db.observed_skew()) {
+            info!("Learned clock skew of {}ms for {}", observed, asset_id);
+            let skew = AssetSkew {
+                skew_ms: observed,
+                observed_at: seen_at,
+            };
+            if let Err(e) = self
+                .asset_mapping_db
+                .record_asset_skew(asset_id.to_owned(), skew, self.skew.learned_ttl_ms)
+                .await
+            {
+                warn!("Failed to record clock skew for {}: {}", asset_id, e);
+            }
+        }
+
+        Ok((
+            session.session_id.clone(),
+            Provenance::session(pseudo_key, session),
+        ))
+    }
+
+    async fn attribute_process_session(
+        &self,
+        asset_id: Option<&str>,
+        unid: UnidSession,
+    ) -> Result<(String, Provenance), Error> {
+        self.attribute_session(grapl_config::process_history_table_name(), asset_id, unid)
+            .await
+    }
+
+    /// Processes with a GUID (such as Sysmon's ProcessGuid) are mapped straight to a node key
+    /// through the static mapping table, which is immune to pid reuse and clock skew.
+    ///
+    /// The first time a GUID is seen through its process's creation, its node key is taken from
+    /// the pid session that the creation started, so that nodes for the same process that
+    /// arrive without a GUID still resolve to the same node. Any other first sighting could
+    /// match the session of an earlier process that reused the pid, so it gets a fresh key.
+    async fn attribute_process_guid(
+        &self,
+        process_node: &Process,
+        unid: UnidSession,
+    ) -> Result<(String, Provenance), Error> {
+        let mapping_db = DynamicMappingDb::new(self.node_id_db.clone());
+
+        let pseudo_key = format!(
+            "{}{}process_guid",
+            process_node
+                .get_asset_id()
+                .expect("ProcessNode must have asset_id"),
+            process_node.process_guid
+        );
+
+        if let Some(node_key) = mapping_db.direct_map(&pseudo_key).await? {
+            let provenance = Provenance::static_mapping(Strategy::ProcessGuid, pseudo_key);
+            return Ok((node_key, provenance));
+        }
+
+        let seed = if unid.is_creation {
+            let (node_key, session_provenance) = self
+                .attribute_process_session(process_node.get_asset_id(), unid)
+                .await?;
+            match session_provenance.session {
+                Some(session)
+                    if session.kind == SessionMatchKind::Canonical
+                        || session.kind == SessionMatchKind::Created =>
+                {
+                    Some((node_key, session))
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let proposed = match &seed {
+            Some((node_key, _)) => node_key.clone(),
+            None => uuid::Uuid::new_v4().to_string(),
+        };
+        // Another identifier may have mapped the GUID first, in which case its key wins
+        let node_key = mapping_db
+            .create_mapping(pseudo_key.clone(), proposed.clone())
+            .await?;
+
+        // When the node key came from the pid session, that match is kept as well
+        let session = seed
+            .filter(|_| node_key == proposed)
+            .map(|(_, session)| session);
+        let provenance = Provenance {
+            session,
+            ..Provenance::static_mapping(Strategy::ProcessGuid, pseudo_key)
+        };
+        Ok((node_key, provenance))
+    }
 }
 
 fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
     match &node.which_node {
         Some(WhichNode::ProcessNode(node)) => {
-            let (is_creation, timestamp) = match (
+            let (is_creation, is_termination, timestamp) = match (
                 node.created_timestamp != 0,
                 node.last_seen_timestamp != 0,
                 node.terminated_timestamp != 0,
             ) {
-                (true, _, _) => (true, node.created_timestamp),
-                (_, _, true) => (false, node.terminated_timestamp),
-                (_, true, _) => (false, node.last_seen_timestamp),
+                (true, _, _) => (true, false, node.created_timestamp),
+                (_, _, true) => (false, true, node.terminated_timestamp),
+                (_, true, _) => (false, false, node.last_seen_timestamp),
                 _ => bail!("At least one timestamp must be set"),
             };
 
@@ -277,15 +766,17 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
                 ),
                 timestamp,
                 is_creation,
+                is_termination,
             }))
         }
         Some(WhichNode::FileNode(node)) => {
-            let (is_creation, timestamp) = match FileState::try_from(node.state)? {
-                FileState::Created => (true, node.created_timestamp),
-                _ => (false, node.last_seen_timestamp),
+            let (is_creation, is_termination, timestamp) = match FileState::try_from(node.state)? {
+                FileState::Created => (true, false, node.created_timestamp),
+                FileState::Deleted => (false, true, node.deleted_timestamp),
+                FileState::Existing => (false, false, node.last_seen_timestamp),
             };
-            // TODO: Hash the path
-            let key = &node.file_path;
+            // The node keeps its file_path as reported, only the key is normalised
+            let key = file_paths::file_path_key(&node.file_path);
 
             Ok(Some(UnidSession {
                 pseudo_key: format!(
@@ -295,6 +786,7 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
                 ),
                 timestamp,
                 is_creation,
+                is_termination,
             }))
         }
         Some(WhichNode::ProcessOutboundConnectionNode(node)) => {
@@ -313,6 +805,7 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
                 ),
                 timestamp,
                 is_creation,
+                is_termination: false,
             }))
         }
         Some(WhichNode::ProcessInboundConnectionNode(node)) => {
@@ -330,6 +823,7 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
                 ),
                 timestamp,
                 is_creation,
+                is_termination: false,
             }))
         }
 
@@ -351,6 +845,7 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
                 pseudo_key,
                 timestamp,
                 is_creation,
+                is_termination: false,
             }))
         }
 
@@ -368,6 +863,7 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
                 pseudo_key,
                 timestamp,
                 is_creation,
+                is_termination: false,
             }))
         }
         // IpAddressNode is not a session
@@ -385,6 +881,7 @@ fn into_unid_session(node: &Node) -> Result<Option<UnidSession>, Error> {
     }
 }
 
+/// Removes `dead_nodes`, along with the edges leaving them
 fn remove_dead_nodes(graph: &mut Graph, dead_nodes: &HashSet<impl Deref<Target = str>>) {
     for dead_node in dead_nodes {
         graph.nodes.remove(dead_node.deref());
@@ -392,6 +889,7 @@ fn remove_dead_nodes(graph: &mut Graph, dead_nodes: &HashSet<impl Deref<Target =
     }
 }
 
+/// Removes every edge to or from a node that isn't in the graph
 fn remove_dead_edges(graph: &mut Graph) {
     let edges = &mut graph.edges;
     let nodes = &graph.nodes;
@@ -407,66 +905,69 @@ fn remove_dead_edges(graph: &mut Graph) {
     }
 }
 
+/// Points each edge at the identified node keys of its ends, keying it by its new `from`.
+/// Ends missing from `unid_id_map` are left as they are.
 fn remap_edges(graph: &mut Graph, unid_id_map: &HashMap<String, String>) {
-    for (_node_key, edge_list) in graph.edges.iter_mut() {
-        for edge in edge_list.edges.iter_mut() {
+    let mut edges: HashMap<String, EdgeList> = HashMap::with_capacity(graph.edges.len());
+
+    for (node_key, edge_list) in graph.edges.drain() {
+        let node_key = unid_id_map.get(&node_key).cloned().unwrap_or(node_key);
+        let remapped = &mut edges.entry(node_key).or_default().edges;
+
+        for edge in edge_list.edges {
             let from = match unid_id_map.get(&edge.from) {
-                Some(from) => from,
+                Some(from) => from.to_owned(),
                 None => {
                     warn!(
                         "Failed to lookup from node in unid_id_map {}",
                         &edge.edge_name
                     );
-                    continue;
+                    edge.from
                 }
             };
 
             let to = match unid_id_map.get(&edge.to) {
-                Some(to) => to,
+                Some(to) => to.to_owned(),
                 None => {
                     warn!(
                         "Failed to lookup to node in unid_id_map {}",
                         &edge.edge_name
                     );
-                    continue;
+                    edge.to
                 }
             };
 
-            *edge = Edge {
-                from: from.to_owned(),
-                to: to.to_owned(),
-                edge_name: edge.edge_name.clone(),
-            };
+            remapped.push(Edge {
+                from,
+                to,
+                edge_name: edge.edge_name,
+            });
         }
     }
+
+    graph.edges = edges;
 }
 
+/// Moves each node to the node key it was identified as, dropping nodes missing from
+/// `unid_id_map`. Nodes are expected to already carry their identified node key.
 fn remap_nodes(graph: &mut Graph, unid_id_map: &HashMap<String, String>) {
-    let mut nodes = HashMap::with_capacity(graph.nodes.len());
-
-    for (_node_key, node) in graph.nodes.iter_mut() {
-        // DynamicNodes are identified in-place
-        if let Some(_n) = node.as_dynamic_node() {
-            let old_node = nodes.insert(node.clone_node_key(), node.clone());
-            if let Some(ref old_node) = old_node {
-                NodeT::merge(
-                    nodes
-                        .get_mut(node.get_node_key())
-                        .expect("node key not in map"),
-                    old_node,
-                );
+    let mut nodes: HashMap<String, Node> = HashMap::with_capacity(graph.nodes.len());
+
+    for (node_key, mut node) in graph.nodes.drain() {
+        let new_key = match unid_id_map.get(&node_key) {
+            Some(new_key) => new_key,
+            None => continue,
+        };
+        node.set_node_key(new_key.to_owned());
+
+        // We may have actually had nodes with different unid node_keys that map to the
+        // same node_key. Therefor we must merge any nodes when there is a collision.
+        match nodes.get_mut(new_key) {
+            Some(existing) => {
+                NodeT::merge(existing, &node);
             }
-        } else if let Some(new_key) = unid_id_map.get(node.get_node_key()) {
-            node.set_node_key(new_key.to_owned());
-
-            // We may have actually had nodes with different unid node_keys that map to the
-            // same node_key. Therefor we must merge any nodes when there is a collision.
-            let old_node = nodes.insert(new_key.to_owned(), node.clone());
-            if let Some(ref old_node) = old_node {
-                NodeT::merge(
-                    nodes.get_mut(new_key).expect("New key not in map"),
-                    old_node,
-                );
+            None => {
+                nodes.insert(new_key.to_owned(), node);
             }
         }
     }
@@ -474,7 +975,7 @@ fn remap_nodes(graph: &mut Graph, unid_id_map: &HashMap<String, String>) {
 }
 
 async fn create_asset_id_mappings(
-    assetid_db: &AssetIdDb<impl DynamoDb>,
+    assetid_db: &AssetIdDb<impl AssetIdStorage>,
     unid_graph: &Graph,
 ) -> Result<(), Error> {
     for node in unid_graph.nodes.values() {
@@ -492,6 +993,7 @@ async fn create_asset_id_mappings(
                 (&node.asset_id, &node.hostname, node.created_timestamp)
             }
             Some(WhichNode::AssetNode(ref node)) => {
+                create_address_mappings(assetid_db, node).await?;
                 (&node.asset_id, &node.hostname, node.first_seen_timestamp)
             }
             Some(WhichNode::NetworkConnectionNode(ref _node)) => {
@@ -506,8 +1008,20 @@ async fn create_asset_id_mappings(
             Some(WhichNode::IpPortNode(ref _node)) => {
                 continue;
             }
-            Some(WhichNode::DynamicNode(ref _node)) => {
-                continue;
+            Some(WhichNode::DynamicNode(ref node)) => {
+                if let (Some(asset_id), Some(host_ip)) = (&node.asset_id, &node.host_ip) {
+                    info!("Creating asset id {} mapping for: {}", asset_id, host_ip);
+                    warn_on_conflict(
+                        assetid_db
+                            .create_mapping(
+                                &HostId::HostIp(host_ip.clone()),
+                                asset_id.clone(),
+                                node.seen_at,
+                            )
+                            .await,
+                    )?;
+                }
+                (&node.asset_id, &node.hostname, node.seen_at)
             }
             None => bail!("Failed to handle node variant"),
         };
@@ -515,13 +1029,15 @@ async fn create_asset_id_mappings(
         match ids {
             (Some(asset_id), Some(hostname), timestamp) => {
                 info!("Creating asset id {} mapping for: {}", asset_id, hostname);
-                assetid_db
-                    .create_mapping(
-                        &HostId::AssetId(asset_id.clone()),
-                        hostname.clone(),
-                        timestamp,
-                    )
-                    .await?;
+                warn_on_conflict(
+                    assetid_db
+                        .create_mapping(
+                            &HostId::Hostname(hostname.clone()),
+                            asset_id.clone(),
+                            timestamp,
+                        )
+                        .await,
+                )?;
             }
             _ => continue,
         };
@@ -530,21 +1046,92 @@ async fn create_asset_id_mappings(
     Ok(())
 }
 
+/// Asset nodes also carry the addresses that the asset held when it was seen. With an asset
+/// id, those addresses are mapped to it directly. Without one, a MAC and an IP together
+/// describe a lease, ie: from a DHCP server, and the IP is mapped to the MAC's asset.
+async fn create_address_mappings(
+    assetid_db: &AssetIdDb<impl AssetIdStorage>,
+    node: &Asset,
+) -> Result<(), Error> {
+    let timestamp = node.first_seen_timestamp;
+
+    match (&node.asset_id, &node.mac_address, &node.ip_address) {
+        (Some(asset_id), mac_address, ip_address) => {
+            if let Some(mac_address) = mac_address {
+                info!(
+                    "Creating asset id {} mapping for: {}",
+                    asset_id, mac_address
+                );
+                warn_on_conflict(
+                    assetid_db
+                        .create_mapping(
+                            &HostId::MacAddress(mac_address.clone()),
+                            asset_id.clone(),
+                            timestamp,
+                        )
+                        .await,
+                )?;
+            }
+            if let Some(ip_address) = ip_address {
+                info!("Creating asset id {} mapping for: {}", asset_id, ip_address);
+                warn_on_conflict(
+                    assetid_db
+                        .create_mapping(
+                            &HostId::HostIp(ip_address.clone()),
+                            asset_id.clone(),
+                            timestamp,
+                        )
+                        .await,
+                )?;
+            }
+        }
+        (None, Some(mac_address), Some(ip_address)) => {
+            let leased = assetid_db
+                .record_lease(ip_address, mac_address, timestamp)
+                .await;
+            match leased {
+                Ok(Some(asset_id)) => {
+                    info!("Mapped leased ip {} to asset id {}", ip_address, asset_id)
+                }
+                Ok(None) => warn!(
+                    "Could not map leased ip {}, mac address {} is not known",
+                    ip_address, mac_address
+                ),
+                Err(e) => warn_on_conflict(Err(e))?,
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// A conflicting claim on a host id won't be resolved by retrying the event, so it's logged
+/// rather than failing the event
+fn warn_on_conflict(result: Result<(), Error>) -> Result<(), Error> {
+    match result {
+        Err(e) if e.downcast_ref::<MappingConflict>().is_some() => {
+            warn!("Ignoring conflicting asset id mapping: {}", e);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
 // Takes a Graph, attributes all nodes with an asset id
 // When atribution fails, attribution continues, but the Graph returned will contain
-// only the nodes that were successful
+// only the nodes that were successful, and the rest are returned with the reason they failed
 // Edges will also be fixed up
 async fn attribute_asset_ids(
-    asset_identifier: &AssetIdentifier<impl DynamoDb>,
-    unid_graph: Graph,
-) -> Result<Graph, (Error, Graph)> {
+    asset_identifier: &AssetIdentifier<impl AssetIdStorage>,
+    unid_graph: &Graph,
+) -> (Graph, HashMap<String, String>) {
     info!("Attributing asset ids");
-    let mut dead_nodes = HashSet::new();
+    let mut dead_nodes = HashMap::new();
     let mut output_graph = Graph::new(unid_graph.timestamp);
-    output_graph.edges = unid_graph.edges;
+    output_graph.edges = unid_graph.edges.clone();
 
     let _node_asset_ids: HashMap<String, String> = HashMap::new();
-    let mut err = None;
 
     for node in unid_graph.nodes.values() {
         match &node.which_node {
@@ -579,8 +1166,7 @@ async fn attribute_asset_ids(
             Ok(asset_id) => asset_id,
             Err(e) => {
                 warn!("Failed to attribute to asset id: {:?} {}", node, e);
-                err = Some(e);
-                dead_nodes.insert(node.clone_node_key());
+                dead_nodes.insert(node.clone_node_key(), e.to_string());
                 continue;
             }
         };
@@ -595,180 +1181,44 @@ async fn attribute_asset_ids(
 
     if dead_nodes.is_empty() {
         info!("Attributed all asset ids");
-        Ok(output_graph)
     } else {
         warn!("Attributed asset ids");
-        Err((err.unwrap(), output_graph))
     }
+    (output_graph, dead_nodes)
 }
 
 #[async_trait]
-impl<D, CacheT> EventHandler for NodeIdentifier<D, CacheT>
+impl<S, CacheT, R> EventHandler for NodeIdentifier<S, CacheT, R>
 where
-    D: DynamoDb + Clone + Send + Sync + 'static,
+    S: Storage,
     CacheT: Cache + Clone + Send + Sync + 'static,
+    R: RetryEmitter,
 {
-    type InputEvent = GeneratedSubgraphs;
+    type InputEvent = IdentifierInput;
     type OutputEvent = GeneratedSubgraphs;
     type Error = sqs_lambda::error::Error;
 
     async fn handle_event(
         &mut self,
-        subgraphs: GeneratedSubgraphs,
+        input: IdentifierInput,
     ) -> OutputEvent<Self::OutputEvent, Self::Error> {
-        warn!("node-identifier.handle_event");
-        let _region = self.region.clone();
-
-        let mut attribution_failure = None;
-
-        info!("Handling raw event");
-
-        if subgraphs.subgraphs.is_empty() {
-            warn!("Received empty unid subgraph");
-            return OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![])));
-        }
-
-        // Merge all of the subgraphs into one subgraph to avoid
-        // redundant work
-        let unid_subgraph =
-            subgraphs
-                .subgraphs
-                .into_iter()
-                .fold(Graph::new(0), |mut total_graph, subgraph| {
-                    info!(
-                        "Merging subgraph with: {} nodes {} edges",
-                        subgraph.nodes.len(),
-                        subgraph.edges.len()
-                    );
-                    total_graph.merge(&subgraph);
-                    total_graph
-                });
-
-        if unid_subgraph.is_empty() {
-            warn!("Received empty subgraph");
-            return OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![])));
-        }
-
-        info!(
-            "unid_subgraph: {} nodes {} edges",
-            unid_subgraph.nodes.len(),
-            unid_subgraph.edges.len(),
+        // Each subgraph counts its own storage calls and cache hits, even when identified
+        // concurrently, while the cache itself is shared
+        let counted = CountingStorage::new(self.node_id_db.clone());
+        let storage = CachingStorage::new(counted.clone(), self.lookup_cache.clone());
+        let attributor =
+            NodeKeyAttributor::new(storage.clone(), self.should_default, self.options.skew);
+
+        let completed = self.identify_subgraphs(&attributor, input).await;
+
+        self.metrics
+            .report_storage_calls(counted.reads(), counted.writes());
+        self.metrics.report_cache_lookups(
+            storage.session_hits(),
+            storage.session_misses(),
+            storage.asset_id_hits(),
+            storage.asset_id_misses(),
         );
-
-        // Create any implicit asset id mappings
-        if let Err(e) = create_asset_id_mappings(&self.asset_mapping_db, &unid_subgraph).await {
-            error!("Asset mapping creation failed with {}", e);
-            return OutputEvent::new(Completion::Error(
-                sqs_lambda::error::Error::ProcessingError(e.to_string()),
-            ));
-        }
-
-        // Map all host_ids into asset_ids. This has to happen before node key
-        // identification.
-        // If there is a failure, we'll mark this execute as failed, but continue
-        // with whatever subgraph has succeeded
-
-        let output_subgraph = match attribute_asset_ids(&self.asset_identifier, unid_subgraph).await
-        {
-            Ok(unid_subgraph) => unid_subgraph,
-            Err((e, unid_subgraph)) => {
-                attribution_failure = Some(e);
-                unid_subgraph
-            }
-        };
-
-        let mut dead_node_ids = HashSet::new();
-        let mut unid_id_map = HashMap::new();
-
-        // new method
-        let mut identified_graph = Graph::new(output_subgraph.timestamp);
-        for (old_node_key, old_node) in output_subgraph.nodes.iter() {
-            let node = old_node.clone();
-
-            match self.cache.get(old_node_key.clone()).await {
-                Ok(CacheResponse::Hit) => {
-                    info!("Got cache hit for old_node_key, skipping node.");
-                    continue;
-                }
-                Err(e) => warn!("Failed to retrieve from cache: {:?}", e),
-                _ => (),
-            };
-
-            let node = match self.attribute_node_key(node.clone()).await {
-                Ok(node) => node,
-                Err(e) => {
-                    warn!("Failed to attribute node_key with: {}", e);
-                    dead_node_ids.insert(node.clone_node_key());
-                    attribution_failure = Some(e);
-                    continue;
-                }
-            };
-            unid_id_map.insert(old_node_key.to_owned(), node.clone_node_key());
-            identified_graph.add_node(node);
-        }
-
-        info!(
-            "PRE: identified_graph.edges.len() {}",
-            identified_graph.edges.len()
-        );
-
-        for (old_key, edge_list) in output_subgraph.edges.iter() {
-            if dead_node_ids.contains(old_key) {
-                continue;
-            };
-
-            for edge in &edge_list.edges {
-                let from_key = unid_id_map.get(&edge.from);
-                let to_key = unid_id_map.get(&edge.to);
-
-                let (from_key, to_key) = match (from_key, to_key) {
-                    (Some(from_key), Some(to_key)) => (from_key, to_key),
-                    _ => continue,
-                };
-
-                identified_graph.add_edge(
-                    edge.edge_name.to_owned(),
-                    from_key.to_owned(),
-                    to_key.to_owned(),
-                );
-            }
-        }
-
-        info!(
-            "POST: identified_graph.edges.len() {}",
-            identified_graph.edges.len()
-        );
-
-        // Remove dead nodes and edges from output_graph
-        let dead_node_ids: HashSet<&str> = dead_node_ids.iter().map(String::as_str).collect();
-
-        if identified_graph.is_empty() {
-            return OutputEvent::new(Completion::Error(
-                sqs_lambda::error::Error::ProcessingError(
-                    "All nodes failed to identify".to_string(),
-                ),
-            ));
-        }
-
-        let identities: Vec<_> = unid_id_map.keys().cloned().collect();
-
-        let mut completed = if !dead_node_ids.is_empty() || attribution_failure.is_some() {
-            info!("Partial Success, identified {} nodes", identities.len());
-            OutputEvent::new(Completion::Partial((
-                GeneratedSubgraphs::new(vec![identified_graph]),
-                sqs_lambda::error::Error::ProcessingError(attribution_failure.unwrap().to_string()),
-            )))
-        } else {
-            info!("Identified all nodes");
-            OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![
-                identified_graph,
-            ])))
-        };
-
-        identities
-            .into_iter()
-            .for_each(|identity| completed.add_identity(identity));
-
         completed
     }
 }
@@ -925,6 +1375,7 @@ fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), H
                 )
             };
 
+            let retry_bucket = bucket_prefix.clone() + "-node-identifier-retry-bucket";
             let bucket = bucket_prefix + "-subgraphs-generated-bucket";
             info!("Output events to: {}", bucket);
             let region = grapl_config::region();
@@ -932,34 +1383,15 @@ fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), H
                 .await
                 .expect("Could not create redis client");
 
-            let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
-
             let dynamo = DynamoDbClient::new(region.clone());
-            let dyn_session_db =
-                SessionDb::new(dynamo.clone(), grapl_config::dynamic_session_table_name());
-            let dyn_mapping_db = DynamicMappingDb::new(DynamoDbClient::new(region.clone()));
-            let asset_identifier = AssetIdentifier::new(asset_id_db);
-
-            let dyn_node_identifier = DynamicNodeIdentifier::new(
-                asset_identifier,
-                dyn_session_db,
-                dyn_mapping_db,
-                should_default,
-            );
-
-            let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
-
-            let asset_identifier = AssetIdentifier::new(asset_id_db);
-
-            let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
             let node_identifier = NodeIdentifier::new(
-                asset_id_db,
-                dyn_node_identifier,
-                asset_identifier,
                 dynamo.clone(),
                 should_default,
                 cache.clone(),
-                region.clone(),
+                IdentifierOptions::from_env(),
+                LookupCache::new(CacheOptions::from_env()),
+                S3RetryEmitter::new(S3Client::new(region.clone()), retry_bucket),
+                NodeIdentifierMetrics::new(service_name(should_default)),
             );
 
             let initial_messages: Vec<_> = event.records.into_iter().map(map_sqs_message).collect();
@@ -972,7 +1404,7 @@ fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), H
                 |region_str| S3Client::new(Region::from_str(&region_str).expect("region_str")),
                 S3Client::new(region.clone()),
                 SqsClient::new(region.clone()),
-                ZstdProtoDecoder::default(),
+                IdentifierInputDecoder::default(),
                 SubgraphSerializer {
                     proto: Vec::with_capacity(1024),
                 },
@@ -1027,6 +1459,41 @@ fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), H
     }
 }
 
+/// The notification S3 would send when `key` is written to `bucket`
+fn s3_event(bucket: String, key: String) -> S3Event {
+    S3Event {
+        records: vec![S3EventRecord {
+            event_version: None,
+            event_source: None,
+            aws_region: Some("us-east-1".to_owned()),
+            event_time: chrono::Utc::now(),
+            event_name: None,
+            principal_id: S3UserIdentity { principal_id: None },
+            request_parameters: S3RequestParameters {
+                source_ip_address: None,
+            },
+            response_elements: Default::default(),
+            s3: S3Entity {
+                schema_version: None,
+                configuration_id: None,
+                bucket: S3Bucket {
+                    name: Some(bucket),
+                    owner_identity: S3UserIdentity { principal_id: None },
+                    arn: None,
+                },
+                object: S3Object {
+                    key: Some(key),
+                    size: 0,
+                    url_decoded_key: None,
+                    version_id: None,
+                    e_tag: None,
+                    sequencer: None,
+                },
+            },
+        }],
+    }
+}
+
 pub fn init_sqs_client() -> SqsClient {
     info!("Connecting to local us-east-1 http://sqs.us-east-1.amazonaws.com:9324");
 
@@ -1100,53 +1567,69 @@ impl sqs_lambda::cache::Cache for HashCache {
     }
 }
 
-pub async fn local_handler(should_default: bool) -> Result<(), Box<dyn std::error::Error>> {
-    let cache = HashCache::default();
-
-    info!("region");
-    let region = Region::Custom {
-        name: "dynamo".to_string(),
-        endpoint: "http://dynamo:8222".to_string(),
-    };
+/// The retry handler is the only one that defaults sessions, and reports as its own service
+fn service_name(should_default: bool) -> &'static str {
+    if should_default {
+        "node_identifier_retry_handler"
+    } else {
+        "node_identifier"
+    }
+}
 
-    info!("asset_id_db");
-    let asset_id_db = AssetIdDb::new(init_dynamodb_client());
-
-    info!("dynamo");
-    let dynamo = init_dynamodb_client();
-    info!("dyn_session_db");
-    let dyn_session_db = SessionDb::new(dynamo.clone(), grapl_config::dynamic_session_table_name());
-    info!("dyn_mapping_db");
-    let dyn_mapping_db = DynamicMappingDb::new(init_dynamodb_client());
-    info!("asset_identifier");
-    let asset_identifier = AssetIdentifier::new(asset_id_db);
-
-    info!("dyn_node_identifier");
-    let dyn_node_identifier = DynamicNodeIdentifier::new(
-        asset_identifier,
-        dyn_session_db,
-        dyn_mapping_db,
-        should_default,
-    );
+/// Parses the environment variable `name`, falling back to `default` when it's unset or invalid
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            warn!("Ignoring invalid {}: {}", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
 
-    info!("asset_id_db");
-    let asset_id_db = AssetIdDb::new(init_dynamodb_client());
+/// When set, the local node identifier keeps its state in a SQLite database at this path
+/// rather than in DynamoDB. Requires the `sqlite` feature
+pub fn sqlite_storage_path() -> Option<String> {
+    std::env::var("NODE_IDENTIFIER_SQLITE_PATH").ok()
+}
 
-    info!("asset_identifier");
-    let asset_identifier = AssetIdentifier::new(asset_id_db);
+pub async fn local_handler(should_default: bool) -> Result<(), Box<dyn std::error::Error>> {
+    match sqlite_storage_path() {
+        #[cfg(feature = "sqlite")]
+        Some(path) => {
+            info!("Using SQLite storage at {}", path);
+            local_handler_with_storage(SqliteStorage::open(path)?, should_default).await
+        }
+        #[cfg(not(feature = "sqlite"))]
+        Some(_) => {
+            Err("NODE_IDENTIFIER_SQLITE_PATH is set, but the sqlite feature is disabled".into())
+        }
+        None => local_handler_with_storage(init_dynamodb_client(), should_default).await,
+    }
+}
 
-    info!("asset_id_db");
-    let asset_id_db = AssetIdDb::new(init_dynamodb_client());
+async fn local_handler_with_storage(
+    storage: impl Storage,
+    should_default: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cache = HashCache::default();
 
     info!("node_identifier");
+    // Locally, S3 doesn't notify the retry queue itself
+    let retry_emitter =
+        S3RetryEmitter::new(init_s3_client(), "local-grapl-node-identifier-retry-bucket")
+            .with_notification(
+                init_sqs_client(),
+                std::env::var("RETRY_QUEUE_URL").expect("RETRY_QUEUE_URL"),
+            );
     let node_identifier = NodeIdentifier::new(
-        asset_id_db,
-        dyn_node_identifier,
-        asset_identifier,
-        dynamo.clone(),
+        storage,
         should_default,
         cache.clone(),
-        region.clone(),
+        IdentifierOptions::from_env(),
+        LookupCache::new(CacheOptions::from_env()),
+        retry_emitter,
+        NodeIdentifierMetrics::new(service_name(should_default)),
     );
 
     let source_queue_url = std::env::var("SOURCE_QUEUE_URL").expect("SOURCE_QUEUE_URL");
@@ -1168,7 +1651,7 @@ pub async fn local_handler(should_default: bool) -> Result<(), Box<dyn std::erro
         |_| init_s3_client(),
         init_s3_client(),
         init_sqs_client(),
-        ZstdProtoDecoder::default(),
+        IdentifierInputDecoder::default(),
         SubgraphSerializer {
             proto: Vec::with_capacity(1024),
         },
@@ -1178,37 +1661,7 @@ pub async fn local_handler(should_default: bool) -> Result<(), Box<dyn std::erro
             dbg!(event_result);
         },
         move |bucket, key| async move {
-            let output_event = S3Event {
-                records: vec![S3EventRecord {
-                    event_version: None,
-                    event_source: None,
-                    aws_region: Some("us-east-1".to_owned()),
-                    event_time: chrono::Utc::now(),
-                    event_name: None,
-                    principal_id: S3UserIdentity { principal_id: None },
-                    request_parameters: S3RequestParameters {
-                        source_ip_address: None,
-                    },
-                    response_elements: Default::default(),
-                    s3: S3Entity {
-                        schema_version: None,
-                        configuration_id: None,
-                        bucket: S3Bucket {
-                            name: Some(bucket),
-                            owner_identity: S3UserIdentity { principal_id: None },
-                            arn: None,
-                        },
-                        object: S3Object {
-                            key: Some(key),
-                            size: 0,
-                            url_decoded_key: None,
-                            version_id: None,
-                            e_tag: None,
-                            sequencer: None,
-                        },
-                    },
-                }],
-            };
+            let output_event = s3_event(bucket, key);
 
             let sqs_client = init_sqs_client();
 
@@ -0,0 +1,292 @@
+//! Nodes in a subgraph frequently resolve to the same session, ie: a parent process shows up
+//! once for every child it spawned. Rather than identifying each node on its own, nodes are
+//! grouped by the session lookup they would perform, and each group is resolved once.
+
+use std::collections::HashMap;
+
+use grapl_graph_descriptions::graph_description::node::WhichNode;
+use grapl_graph_descriptions::graph_description::*;
+
+use crate::dynamic_sessiondb::into_dynamic_unid_session;
+use crate::sessions::UnidSession;
+use crate::{env_or, into_unid_session};
+
+/// How many groups may be resolved at once
+#[derive(Debug, Clone)]
+pub struct LookupOptions {
+    /// The number of independent session timelines that may be resolved concurrently
+    pub concurrency: usize,
+}
+
+impl Default for LookupOptions {
+    fn default() -> Self {
+        Self { concurrency: 10 }
+    }
+}
+
+impl LookupOptions {
+    /// Reads `NODE_IDENTIFIER_LOOKUP_CONCURRENCY`, falling back to the default
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            concurrency: env_or("NODE_IDENTIFIER_LOOKUP_CONCURRENCY", defaults.concurrency).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LookupKind {
+    Creation,
+    LastSeen,
+    Termination,
+}
+
+/// Lookups against the same session timeline depend on one another, so they're resolved
+/// in order rather than concurrently
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TimelineKey {
+    history: &'static str,
+    pseudo_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct GroupKey {
+    // Processes with different GUIDs are never the same node, even within a bucket
+    process_guid: String,
+    kind: LookupKind,
+    bucket: u64,
+}
+
+/// Nodes, keyed by their unidentified node key, that all resolve to the same node key
+#[derive(Debug)]
+pub(crate) struct LookupGroup {
+    pub members: Vec<(String, Node)>,
+    /// The index of the member that is actually identified; the earliest one
+    pub representative: usize,
+    earliest: u64,
+}
+
+impl LookupGroup {
+    fn new(old_node_key: String, node: Node, timestamp: u64) -> Self {
+        Self {
+            members: vec![(old_node_key, node)],
+            representative: 0,
+            earliest: timestamp,
+        }
+    }
+
+    fn push(&mut self, old_node_key: String, node: Node, timestamp: u64) {
+        if timestamp < self.earliest {
+            self.representative = self.members.len();
+            self.earliest = timestamp;
+        }
+        self.members.push((old_node_key, node));
+    }
+
+    pub fn representative(&self) -> &Node {
+        &self.members[self.representative].1
+    }
+}
+
+/// The session lookup that identifying `node` makes, along with the name of the history
+/// table it's made against
+fn session_lookup(node: &Node) -> Option<(&'static str, UnidSession)> {
+    let history = match node.which_node.as_ref()? {
+        WhichNode::ProcessNode(_) => "process",
+        WhichNode::FileNode(_) => "file",
+        WhichNode::ProcessInboundConnectionNode(_) => "inbound_connection",
+        WhichNode::ProcessOutboundConnectionNode(_) => "outbound_connection",
+        WhichNode::NetworkConnectionNode(_) => "network_connection",
+        WhichNode::IpConnectionNode(_) => "ip_connection",
+        WhichNode::DynamicNode(dynamic_node) => {
+            return Some(("dynamic", into_dynamic_unid_session(dynamic_node)?));
+        }
+        _ => return None,
+    };
+
+    Some((history, into_unid_session(node).ok()??))
+}
+
+/// Groups nodes into chains of lookups. Each chain touches a single session timeline, and
+/// holds its groups in timestamp order; separate chains can be resolved concurrently.
+///
+/// Nodes are bucketed by `precision_ms`, the same precision their timestamps are truncated
+/// to before matching, so that no group spans timestamps the session db would tell apart.
+///
+/// Nodes that aren't identified by session, or whose session can't be determined up front,
+/// don't depend on one another's lookups, so each gets a chain of its own.
+pub(crate) fn group_lookups(
+    nodes: impl IntoIterator<Item = (String, Node)>,
+    precision_ms: u64,
+) -> Vec<Vec<LookupGroup>> {
+    let mut timelines: HashMap<TimelineKey, HashMap<GroupKey, LookupGroup>> = HashMap::new();
+    let mut chains = vec![];
+
+    for (old_node_key, node) in nodes {
+        let (history, unid) = match session_lookup(&node) {
+            Some(lookup) => lookup,
+            None => {
+                chains.push(vec![LookupGroup::new(old_node_key, node, 0)]);
+                continue;
+            }
+        };
+
+        let kind = match (unid.is_creation, unid.is_termination) {
+            (true, _) => LookupKind::Creation,
+            (_, true) => LookupKind::Termination,
+            _ => LookupKind::LastSeen,
+        };
+        let process_guid = match &node.which_node {
+            Some(WhichNode::ProcessNode(process_node)) => process_node.process_guid.clone(),
+            _ => String::new(),
+        };
+
+        let timeline_key = TimelineKey {
+            history,
+            pseudo_key: unid.pseudo_key,
+        };
+        let group_key = GroupKey {
+            process_guid,
+            kind,
+            bucket: unid.timestamp / precision_ms.max(1),
+        };
+
+        let groups = timelines.entry(timeline_key).or_default();
+        match groups.get_mut(&group_key) {
+            Some(group) => group.push(old_node_key, node, unid.timestamp),
+            None => {
+                groups.insert(
+                    group_key,
+                    LookupGroup::new(old_node_key, node, unid.timestamp),
+                );
+            }
+        }
+    }
+
+    chains.extend(timelines.into_values().map(|groups| {
+        let mut groups: Vec<_> = groups.into_values().collect();
+        groups.sort_by_key(|group| group.earliest);
+        groups
+    }));
+
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grapl_graph_descriptions::node::NodeT;
+    use grapl_graph_descriptions::process::ProcessState;
+
+    fn process(pid: u64, created: u64, last_seen: u64) -> (String, Node) {
+        let process = ProcessBuilder::default()
+            .asset_id("asset".to_owned())
+            .state(ProcessState::Existing)
+            .process_id(pid)
+            .created_timestamp(created)
+            .last_seen_timestamp(last_seen)
+            .build()
+            .unwrap();
+        let node: Node = process.into();
+        (node.clone_node_key(), node)
+    }
+
+    fn dynamic(node_key: &str, name: &str, strategy: IdStrategy) -> (String, Node) {
+        let mut node = DynamicNode {
+            node_key: node_key.to_owned(),
+            node_type: "Service".to_owned(),
+            id_strategy: vec![strategy],
+            ..Default::default()
+        };
+        node.set_property("name", name.to_owned());
+        (node_key.to_owned(), node.into())
+    }
+
+    fn last_seen(last_seen_time: u64) -> IdStrategy {
+        Session {
+            primary_key_properties: vec!["name".to_owned()],
+            primary_key_requires_asset_id: false,
+            created_time: 0,
+            last_seen_time,
+            terminated_time: 0,
+        }
+        .into()
+    }
+
+    fn by_name() -> IdStrategy {
+        Static {
+            primary_key_properties: vec!["name".to_owned()],
+            primary_key_requires_asset_id: false,
+        }
+        .into()
+    }
+
+    #[test]
+    fn repeated_parent_resolves_once() {
+        let mut nodes = vec![process(100, 1_000, 0)];
+        for ts in 0..50 {
+            nodes.push(process(1, 0, 5_000 + ts));
+        }
+
+        let chains = group_lookups(nodes, 100);
+        assert_eq!(chains.len(), 2);
+
+        let parent = chains
+            .iter()
+            .find(|chain| chain[0].members.len() > 1)
+            .expect("parent lookups were not grouped");
+        assert_eq!(parent.len(), 1);
+        assert_eq!(parent[0].members.len(), 50);
+        assert_eq!(parent[0].earliest, 5_000);
+    }
+
+    #[test]
+    fn groups_are_split_by_kind_and_bucket() {
+        let nodes = vec![
+            process(1, 0, 250),
+            process(1, 1_000, 0),
+            process(1, 0, 1_050),
+            process(1, 0, 1_010),
+        ];
+
+        let chains = group_lookups(nodes, 100);
+        assert_eq!(chains.len(), 1);
+
+        let timestamps: Vec<_> = chains[0].iter().map(|group| group.earliest).collect();
+        assert_eq!(timestamps, vec![250, 1_000, 1_010]);
+        assert_eq!(chains[0][2].representative, 1);
+    }
+
+    #[test]
+    fn dynamic_sessions_are_chained_by_pseudo_key() {
+        let nodes = vec![
+            dynamic("a", "svc", last_seen(1_000)),
+            dynamic("b", "svc", last_seen(500)),
+            dynamic("c", "other", last_seen(1_000)),
+        ];
+
+        let chains = group_lookups(nodes, 100);
+        assert_eq!(chains.len(), 2);
+
+        let svc = chains
+            .iter()
+            .find(|chain| chain.len() == 2)
+            .expect("sessions sharing a pseudo key were not chained");
+        let timestamps: Vec<_> = svc.iter().map(|group| group.earliest).collect();
+        assert_eq!(timestamps, vec![500, 1_000]);
+    }
+
+    #[test]
+    fn unsessioned_nodes_are_chained_alone() {
+        let nodes = vec![
+            dynamic("a", "svc", by_name()),
+            dynamic("b", "svc", by_name()),
+            process(1, 0, 1_000),
+        ];
+
+        let chains = group_lookups(nodes, 100);
+        assert_eq!(chains.len(), 3);
+        assert!(chains.iter().all(|chain| chain.len() == 1));
+    }
+}
@@ -0,0 +1,70 @@
+use grapl_observe::metric_reporter::MetricReporter;
+use log::*;
+use std::io::Stdout;
+
+#[derive(Clone)]
+pub struct NodeIdentifierMetrics {
+    metric_reporter: MetricReporter<Stdout>,
+}
+
+impl NodeIdentifierMetrics {
+    pub fn new(service_name: &str) -> NodeIdentifierMetrics {
+        NodeIdentifierMetrics {
+            metric_reporter: MetricReporter::<Stdout>::new(service_name),
+        }
+    }
+}
+
+impl NodeIdentifierMetrics {
+    /// The number of storage reads and writes it took to identify a single subgraph
+    pub fn report_storage_calls(&mut self, reads: u64, writes: u64) {
+        self.metric_reporter
+            .histogram("node-identifier-dynamodb-reads", reads as f64)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e));
+        self.metric_reporter
+            .histogram("node-identifier-dynamodb-writes", writes as f64)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+    }
+
+    /// How many nodes needed identifying, and how many lookups they were grouped into
+    pub fn report_lookups(&mut self, nodes: usize, groups: usize) {
+        self.metric_reporter
+            .histogram("node-identifier-lookup-nodes", nodes as f64)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e));
+        self.metric_reporter
+            .histogram("node-identifier-lookup-groups", groups as f64)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+    }
+
+    /// Nodes that failed identification, and were either sent to be retried or, having used
+    /// up their attempts, dropped
+    pub fn report_failed_nodes(&mut self, retried: usize, dropped: usize) {
+        self.metric_reporter
+            .counter("node-identifier-retried-nodes", retried as f64, None)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e));
+        self.metric_reporter
+            .counter("node-identifier-dropped-nodes", dropped as f64, None)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+    }
+
+    /// Cache hits and misses while identifying a single subgraph
+    pub fn report_cache_lookups(
+        &mut self,
+        session_hits: u64,
+        session_misses: u64,
+        asset_id_hits: u64,
+        asset_id_misses: u64,
+    ) {
+        let counts = [
+            ("node-identifier-session-cache-hits", session_hits),
+            ("node-identifier-session-cache-misses", session_misses),
+            ("node-identifier-asset-id-cache-hits", asset_id_hits),
+            ("node-identifier-asset-id-cache-misses", asset_id_misses),
+        ];
+        for (metric_name, count) in counts.iter() {
+            self.metric_reporter
+                .counter(metric_name, *count as f64, None)
+                .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+        }
+    }
+}
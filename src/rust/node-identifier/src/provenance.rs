@@ -0,0 +1,91 @@
+//! Records how each node key was chosen. When a node lands on the wrong entity, its provenance
+//! shows whether it matched a canonical session, stretched a guessed one, was defaulted by the
+//! retry handler, or came from a static mapping.
+//!
+//! With `NODE_IDENTIFIER_RECORD_PROVENANCE=true`, one JSON record per identified node is logged
+//! under the `node_identifier::provenance` target, so that it can be queried alongside the
+//! rest of the node identifier's logs.
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::sessions::{SessionMatch, SessionMatchKind};
+
+/// The log target provenance records are written to
+pub const PROVENANCE_TARGET: &str = "node_identifier::provenance";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Resolved against a session timeline
+    Session,
+    /// Resolved through the static mapping of a process GUID
+    ProcessGuid,
+    /// Resolved through a static mapping from its primary key properties
+    StaticMapping,
+    /// Derived from the node itself, ie: an asset id or an ip address
+    Intrinsic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub strategy: Strategy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pseudo_key: Option<String>,
+    /// The session matched, for nodes identified by session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<SessionMatch>,
+    /// Whether the node key was guessed because nothing matched
+    pub defaulted: bool,
+}
+
+impl Provenance {
+    pub fn session(pseudo_key: String, session: SessionMatch) -> Self {
+        Self {
+            strategy: Strategy::Session,
+            pseudo_key: Some(pseudo_key),
+            defaulted: session.kind == SessionMatchKind::Defaulted,
+            session: Some(session),
+        }
+    }
+
+    pub fn static_mapping(strategy: Strategy, pseudo_key: String) -> Self {
+        Self {
+            strategy,
+            pseudo_key: Some(pseudo_key),
+            session: None,
+            defaulted: false,
+        }
+    }
+
+    pub fn intrinsic() -> Self {
+        Self {
+            strategy: Strategy::Intrinsic,
+            pseudo_key: None,
+            session: None,
+            defaulted: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ProvenanceRecord<'a> {
+    unid_node_key: &'a str,
+    node_key: &'a str,
+    #[serde(flatten)]
+    provenance: &'a Provenance,
+}
+
+/// Logs that `unid_node_key` was identified as `node_key`, and how
+pub(crate) fn record(unid_node_key: &str, node_key: &str, provenance: &Provenance) {
+    let record = ProvenanceRecord {
+        unid_node_key,
+        node_key,
+        provenance,
+    };
+
+    match serde_json::to_string(&record) {
+        Ok(record) => info!(target: PROVENANCE_TARGET, "{}", record),
+        Err(e) => warn!("Failed to serialize provenance of {}: {}", node_key, e),
+    }
+}
@@ -0,0 +1,306 @@
+//! Nodes that fail identification are retried on their own, rather than with the subgraph
+//! they arrived in. The nodes that failed, the edges touching them and the reasons they
+//! failed are written to the retry bucket as a `RetryPayload`, which the retry handler picks
+//! up. The rest of the subgraph is emitted as usual, so it's never identified twice.
+//!
+//! Each payload counts the attempts made at its nodes, and once `RetryPolicy::max_attempts`
+//! is reached they're logged and dropped.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use failure::Error;
+use grapl_graph_descriptions::graph_description::*;
+use log::*;
+use prost::Message;
+use rusoto_s3::{PutObjectRequest, S3};
+use rusoto_sqs::{SendMessageRequest, Sqs, SqsClient};
+use serde::{Deserialize, Serialize};
+use sqs_lambda::event_decoder::PayloadDecoder;
+
+use crate::{env_or, s3_event, time_based_key_fn};
+
+/// How many times a node is attempted before it's given up on
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 3 }
+    }
+}
+
+impl RetryPolicy {
+    /// Reads `NODE_IDENTIFIER_MAX_ATTEMPTS`, falling back to the default
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_attempts: env_or("NODE_IDENTIFIER_MAX_ATTEMPTS", defaults.max_attempts).max(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeFailure {
+    pub node_key: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPayload {
+    /// The number of times these nodes have failed identification
+    pub attempts: u32,
+    /// The unidentified nodes that failed, along with every edge touching them
+    pub subgraph: Graph,
+    /// The node keys that the other ends of those edges were identified as, keyed by their
+    /// unidentified node keys
+    pub identified_node_keys: HashMap<String, String>,
+    pub failures: Vec<NodeFailure>,
+}
+
+impl RetryPayload {
+    /// Collects the nodes in `failures` out of `unid_graph`, along with the edges between them
+    /// and the nodes in `identified_node_keys`
+    pub fn new(
+        attempts: u32,
+        unid_graph: &Graph,
+        failures: HashMap<String, String>,
+        identified_node_keys: &HashMap<String, String>,
+    ) -> Self {
+        let mut subgraph = Graph::new(unid_graph.timestamp);
+        for node_key in failures.keys() {
+            if let Some(node) = unid_graph.nodes.get(node_key) {
+                subgraph.add_node(node.clone());
+            }
+        }
+
+        let mut neighbours = HashMap::new();
+        for edge in unid_graph
+            .edges
+            .values()
+            .flat_map(|edges| edges.edges.iter())
+        {
+            if !failures.contains_key(&edge.from) && !failures.contains_key(&edge.to) {
+                continue;
+            }
+
+            let (from, to) = (&edge.from, &edge.to);
+            let mut known = true;
+            for node_key in [from, to].iter() {
+                if failures.contains_key(*node_key) {
+                    continue;
+                }
+                match identified_node_keys.get(*node_key) {
+                    Some(identified) => {
+                        neighbours.insert((*node_key).clone(), identified.clone());
+                    }
+                    None => known = false,
+                }
+            }
+
+            // Edges to nodes that were neither identified nor failed can't be remapped
+            if known {
+                subgraph.add_edge(edge.edge_name.clone(), from.clone(), to.clone());
+            }
+        }
+
+        let failures = failures
+            .into_iter()
+            .map(|(node_key, reason)| NodeFailure { node_key, reason })
+            .collect();
+
+        Self {
+            attempts,
+            subgraph,
+            identified_node_keys: neighbours,
+            failures,
+        }
+    }
+
+    /// Compressed the same way as the subgraphs, so that both can be decoded by
+    /// `IdentifierInputDecoder`
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let payload = serde_json::to_vec(self)?;
+        let mut compressed = Vec::with_capacity(payload.len());
+        zstd::stream::copy_encode(&mut Cursor::new(&payload), &mut compressed, 4)?;
+        Ok(compressed)
+    }
+}
+
+/// Either subgraphs straight from a generator, or nodes being retried after failing
+#[derive(Debug, Clone)]
+pub enum IdentifierInput {
+    Subgraphs(GeneratedSubgraphs),
+    Retry(RetryPayload),
+}
+
+/// Decodes both zstd compressed `GeneratedSubgraphs` protos and retry payloads, as the retry
+/// handler receives both
+#[derive(Debug, Clone, Default)]
+pub struct IdentifierInputDecoder;
+
+impl PayloadDecoder<IdentifierInput> for IdentifierInputDecoder {
+    fn decode(&mut self, body: Vec<u8>) -> Result<IdentifierInput, Box<dyn std::error::Error>> {
+        let mut decompressed = Vec::new();
+        zstd::stream::copy_decode(&mut Cursor::new(&body), &mut decompressed)?;
+
+        // Retry payloads are JSON objects, while a GeneratedSubgraphs proto can never start
+        // with a '{', as that would be a group tag
+        if decompressed.first() == Some(&b'{') {
+            Ok(IdentifierInput::Retry(serde_json::from_slice(
+                &decompressed,
+            )?))
+        } else {
+            let subgraphs = GeneratedSubgraphs::decode(Bytes::from(decompressed))?;
+            Ok(IdentifierInput::Subgraphs(subgraphs))
+        }
+    }
+}
+
+#[async_trait]
+pub trait RetryEmitter: Clone + Send + Sync + 'static {
+    async fn emit_retry(&self, payload: &RetryPayload) -> Result<(), Error>;
+}
+
+/// Writes retry payloads to a bucket, whose notifications feed the retry handler's queue
+#[derive(Clone)]
+pub struct S3RetryEmitter<S3T>
+where
+    S3T: S3 + Clone + Send + Sync + 'static,
+{
+    s3: S3T,
+    bucket: String,
+    notify: Option<(SqsClient, String)>,
+}
+
+impl<S3T> S3RetryEmitter<S3T>
+where
+    S3T: S3 + Clone + Send + Sync + 'static,
+{
+    pub fn new(s3: S3T, bucket: impl Into<String>) -> Self {
+        Self {
+            s3,
+            bucket: bucket.into(),
+            notify: None,
+        }
+    }
+
+    /// Sends the S3 event for each payload to `queue_url` directly, for when bucket
+    /// notifications aren't set up, ie: when running locally
+    pub fn with_notification(mut self, sqs: SqsClient, queue_url: impl Into<String>) -> Self {
+        self.notify = Some((sqs, queue_url.into()));
+        self
+    }
+}
+
+#[async_trait]
+impl<S3T> RetryEmitter for S3RetryEmitter<S3T>
+where
+    S3T: S3 + Clone + Send + Sync + 'static,
+{
+    async fn emit_retry(&self, payload: &RetryPayload) -> Result<(), Error> {
+        let body = payload.encode()?;
+        let key = time_based_key_fn(&body);
+
+        self.s3
+            .put_object(PutObjectRequest {
+                bucket: self.bucket.clone(),
+                key: key.clone(),
+                body: Some(body.into()),
+                ..Default::default()
+            })
+            .await?;
+        info!(
+            "Emitted {} failed nodes for attempt {} to {}",
+            payload.failures.len(),
+            payload.attempts + 1,
+            key
+        );
+
+        if let Some((sqs, queue_url)) = &self.notify {
+            sqs.send_message(SendMessageRequest {
+                message_body: serde_json::to_string(&s3_event(self.bucket.clone(), key))?,
+                queue_url: queue_url.clone(),
+                ..Default::default()
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use grapl_graph_descriptions::node::NodeT;
+    use grapl_graph_descriptions::process::ProcessState;
+
+    fn process(pid: u64) -> Node {
+        ProcessBuilder::default()
+            .asset_id("asset".to_owned())
+            .state(ProcessState::Existing)
+            .process_id(pid)
+            .last_seen_timestamp(1_544_301_484_600u64)
+            .build()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn payload_carries_failed_nodes_and_their_edges() {
+        let (parent, child, unrelated) = (process(1), process(2), process(3));
+        let mut unid_graph = Graph::new(0)
+            .with_node(parent.clone())
+            .with_node(child.clone())
+            .with_node(unrelated.clone());
+        unid_graph.add_edge("children", parent.clone_node_key(), child.clone_node_key());
+        unid_graph.add_edge(
+            "children",
+            parent.clone_node_key(),
+            unrelated.clone_node_key(),
+        );
+
+        let failures = vec![(child.clone_node_key(), "reason".to_owned())]
+            .into_iter()
+            .collect();
+        let identified = vec![
+            (parent.clone_node_key(), "parent".to_owned()),
+            (unrelated.clone_node_key(), "unrelated".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+
+        let payload = RetryPayload::new(1, &unid_graph, failures, &identified);
+
+        assert_eq!(payload.subgraph.nodes.len(), 1);
+        assert!(payload.subgraph.nodes.contains_key(child.get_node_key()));
+        let edges: Vec<_> = payload
+            .subgraph
+            .edges
+            .values()
+            .flat_map(|edges| edges.edges.iter())
+            .collect();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].to, child.get_node_key());
+        assert_eq!(payload.identified_node_keys.len(), 1);
+        assert_eq!(
+            payload.identified_node_keys[parent.get_node_key()],
+            "parent"
+        );
+
+        // Both kinds of input share a decoder
+        match IdentifierInputDecoder
+            .decode(payload.encode().unwrap())
+            .unwrap()
+        {
+            IdentifierInput::Retry(decoded) => assert_eq!(decoded, payload),
+            IdentifierInput::Subgraphs(_) => panic!("Decoded retry payload as subgraphs"),
+        }
+    }
+}
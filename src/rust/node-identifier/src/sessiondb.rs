@@ -1,35 +1,120 @@
-use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use failure::{bail, Error};
-use hmap::hmap;
 use log::{info, warn};
-use rusoto_core::RusotoError;
-use rusoto_dynamodb::{
-    AttributeValue, AttributeValueUpdate, Delete, DeleteItemInput, DynamoDb, Put, PutItemInput,
-    QueryInput, TransactWriteItem, TransactWriteItemsInput, UpdateItemInput,
-};
 
 use uuid::Uuid;
 
+use crate::env_or;
 use crate::sessions::*;
+use crate::storage::{SessionStorage, VersionConflict};
+
+/// How far apart two timestamps for the same event may be and still be matched
+#[derive(Debug, Clone, Copy)]
+pub struct SkewTolerance {
+    /// Timestamps are truncated to a multiple of this many milliseconds before matching
+    pub precision_ms: u64,
+    /// Timestamps at most this many milliseconds apart are considered the same, unless
+    /// more skew has been learned for the asset
+    pub tolerance_ms: u64,
+    /// The largest skew that will be learned for an asset. A termination stamped further
+    /// before a creation is more likely to end a process whose creation was never seen.
+    pub max_learned_ms: u64,
+    /// How long, in event time, a learned skew is trusted without being observed again
+    pub learned_ttl_ms: u64,
+}
+
+impl Default for SkewTolerance {
+    fn default() -> Self {
+        Self {
+            precision_ms: 100,
+            tolerance_ms: 10,
+            max_learned_ms: 2_000,
+            learned_ttl_ms: 60 * 60 * 1000,
+        }
+    }
+}
+
+impl SkewTolerance {
+    /// Reads `NODE_IDENTIFIER_TIMESTAMP_PRECISION_MS`, `NODE_IDENTIFIER_SKEW_TOLERANCE_MS`,
+    /// `NODE_IDENTIFIER_MAX_LEARNED_SKEW_MS` and `NODE_IDENTIFIER_LEARNED_SKEW_TTL_MS`,
+    /// falling back to the defaults
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            precision_ms: env_or(
+                "NODE_IDENTIFIER_TIMESTAMP_PRECISION_MS",
+                defaults.precision_ms,
+            )
+            .max(1),
+            tolerance_ms: env_or("NODE_IDENTIFIER_SKEW_TOLERANCE_MS", defaults.tolerance_ms),
+            max_learned_ms: env_or(
+                "NODE_IDENTIFIER_MAX_LEARNED_SKEW_MS",
+                defaults.max_learned_ms,
+            ),
+            learned_ttl_ms: env_or(
+                "NODE_IDENTIFIER_LEARNED_SKEW_TTL_MS",
+                defaults.learned_ttl_ms,
+            ),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct SessionDb<D>
+pub struct SessionDb<S>
 where
-    D: DynamoDb,
+    S: SessionStorage,
 {
-    dynamo: D,
+    storage: S,
     table_name: String,
+    skew: SkewTolerance,
+    asset_skew_ms: u64,
+    observed_skew_ms: Arc<AtomicU64>,
 }
 
-impl<D> SessionDb<D>
+impl<S> SessionDb<S>
 where
-    D: DynamoDb,
+    S: SessionStorage,
 {
-    pub fn new(dynamo: D, table_name: impl Into<String>) -> Self {
+    pub fn new(storage: S, table_name: impl Into<String>) -> Self {
         Self {
-            dynamo,
+            storage,
             table_name: table_name.into(),
+            skew: SkewTolerance::default(),
+            asset_skew_ms: 0,
+            observed_skew_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Matches timestamps within `skew`, or within the skew learned for the asset that
+    /// these sessions belong to, whichever is wider
+    pub fn with_skew(mut self, skew: SkewTolerance, asset_skew_ms: u64) -> Self {
+        self.skew = skew;
+        self.asset_skew_ms = asset_skew_ms;
+        self
+    }
+
+    /// The widest gap seen between a termination and the later stamped creation of the
+    /// session it ended, which should be learned as the asset's skew
+    pub fn observed_skew(&self) -> Option<u64> {
+        match self.observed_skew_ms.load(Ordering::Relaxed) {
+            0 => None,
+            skew_ms => Some(skew_ms),
+        }
+    }
+
+    fn within_skew(&self, ts_1: u64, ts_2: u64) -> bool {
+        skewed_cmp(
+            ts_1,
+            ts_2,
+            std::cmp::max(self.skew.tolerance_ms, self.asset_skew_ms),
+        )
+    }
+
+    fn observe_skew(&self, skew_ms: u64) {
+        if skew_ms <= self.skew.max_learned_ms {
+            self.observed_skew_ms.fetch_max(skew_ms, Ordering::Relaxed);
         }
     }
 
@@ -37,85 +122,20 @@ where
         &self,
         unid: &UnidSession,
     ) -> Result<Option<Session>, Error> {
-        info!("Finding first session after : {}", &self.table_name);
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            table_name: self.table_name.clone(),
-            key_condition_expression: Some(
-                "pseudo_key = :pseudo_key AND create_time >= :create_time".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pseudo_key".to_owned() => AttributeValue {
-                    s: unid.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                ":create_time".to_owned() => AttributeValue {
-                    n: unid.timestamp.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        let res = wait_on!(self.dynamo.query(query));
-        if let Err(RusotoError::Unknown(ref e)) = res {
-            bail!("Query failed with error: {:?}", e);
-        };
-
-        if let Some(items) = res?.items {
-            match &items[..] {
-                [] => Ok(None),
-                [item] => Session::try_from(item.clone()).map(Option::from),
-                _ => bail!("Unexpected number of items returned"),
-            }
-        } else {
-            Ok(None)
-        }
+        self.storage
+            .find_first_session_after(&self.table_name, unid)
+            .await
     }
 
     pub async fn find_last_session_before(
         &self,
         unid: &UnidSession,
     ) -> Result<Option<Session>, Error> {
-        info!("Finding last session before");
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            scan_index_forward: Some(false),
-            table_name: self.table_name.clone(),
-            key_condition_expression: Some(
-                "pseudo_key = :pseudo_key AND create_time <= :create_time".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pseudo_key".to_owned() => AttributeValue {
-                    s: unid.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                ":create_time".to_owned() => AttributeValue {
-                    n: unid.timestamp.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        let res = wait_on!(self.dynamo.query(query))?;
-
-        if let Some(items) = res.items {
-            match &items[..] {
-                [] => Ok(None),
-                [item] => Session::try_from(item.clone()).map(Option::from),
-                _ => bail!("Unexpected number of items returned"),
-            }
-        } else {
-            Ok(None)
-        }
+        self.storage
+            .find_last_session_before(&self.table_name, unid)
+            .await
     }
 
-    // `create_time` is the sort key in the table, so updating it is not possible.
-    // Instead, in one transaction, the row must be deleted and recreated with the
-    // new create_time
     // This method assumes that the `session` passed in has already been modified
     pub async fn update_session_create_time(
         &self,
@@ -123,217 +143,66 @@ where
         new_time: u64,
         is_canon: bool,
     ) -> Result<(), Error> {
-        info!("Updating session create time");
-        let mut new_session = session.to_owned();
-        new_session.create_time = new_time;
-        new_session.is_create_canon = is_canon;
-        new_session.version += 1;
-        // Create new session with new create_time, increment version
-
-        let put_req = Put {
-            item: serde_dynamodb::to_hashmap(&new_session).unwrap(),
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        let del_req = Delete {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.transact_write_items(TransactWriteItemsInput {
-            transact_items: vec![
-                TransactWriteItem {
-                    delete: del_req.into(),
-                    ..Default::default()
-                },
-                TransactWriteItem {
-                    put: put_req.into(),
-                    ..Default::default()
-                },
-            ],
-            ..Default::default()
-        }))?;
-
-        Ok(())
+        self.storage
+            .update_session_create_time(&self.table_name, session, new_time, is_canon)
+            .await
     }
 
     pub async fn make_create_time_canonical(&self, session: &Session) -> Result<(), Error> {
-        info!("Updating session end time");
-        // Use version as a constraint
-        let upd_req = UpdateItemInput {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            attribute_updates: Some(hmap! {
-                "is_create_canon".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                            bool: true.into(),
-                            ..Default::default()
-                        }),
-                    ..Default::default()
-                },
-                "version".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                        n: (session.version + 1).to_string().into(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-                }
-            }),
-            table_name: self.table_name.clone(),
-            condition_expression: Some("version = :version".into()),
-            expression_attribute_values: Some(hmap! {
-                ":version".to_owned() => AttributeValue {
-                    n: session.version.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.update_item(upd_req))?;
-
-        Ok(())
+        self.storage
+            .make_create_time_canonical(&self.table_name, session)
+            .await
     }
 
-    // Update version, and use it as a constraint
     pub async fn update_session_end_time(
         &self,
         session: &Session,
         new_time: u64,
         is_canon: bool,
     ) -> Result<(), Error> {
-        info!("Updating session end time");
-        // Use version as a constraint
-        let upd_req = UpdateItemInput {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            attribute_updates: Some(hmap! {
-                "end_time".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                        n: new_time.to_string().into(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-                },
-                "is_end_canon".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                            bool: is_canon.into(),
-                            ..Default::default()
-                        }),
-                    ..Default::default()
-                },
-                "version".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                        n: (session.version + 1).to_string().into(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-
-                }
-            }),
-            table_name: self.table_name.clone(),
-            condition_expression: Some("version = :version".into()),
-            expression_attribute_values: Some(hmap! {
-                ":version".to_owned() => AttributeValue {
-                    n: session.version.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.update_item(upd_req))?;
-
-        Ok(())
+        self.storage
+            .update_session_end_time(&self.table_name, session, new_time, is_canon)
+            .await
     }
 
     pub async fn create_session(&self, session: &Session) -> Result<(), Error> {
-        info!("create session");
-        let put_req = PutItemInput {
-            item: serde_dynamodb::to_hashmap(session).unwrap(),
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.put_item(put_req))?;
-
-        Ok(())
+        self.storage.create_session(&self.table_name, session).await
     }
 
     pub async fn delete_session(&self, session: &Session) -> Result<(), Error> {
-        info!("delete session");
-        let del_req = DeleteItemInput {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.delete_item(del_req))?;
-        Ok(())
+        self.storage.delete_session(&self.table_name, session).await
     }
 
-    pub async fn handle_creation_event(&self, unid: UnidSession) -> Result<String, Error> {
+    pub async fn handle_creation_event(&self, unid: UnidSession) -> Result<SessionMatch, Error> {
         info!(
             "Handling unid session creation, pseudo_key: {:?} seen at: {}.",
             unid.pseudo_key, unid.timestamp
         );
 
         // Look for first session where session.create_time >= unid.create_time
-        let session = self.find_first_session_after(&unid).await?;
+        let next_session = self.find_first_session_after(&unid).await?;
 
-        if let Some(session) = session {
+        if let Some(ref session) = next_session {
             // If session.is_create_canon is false,
             // This means that there is a 'Guessed' session in the future,
             // and we should consider this the canonical ID for that session
             if !session.is_create_canon && session.create_time != unid.timestamp {
                 info!("Extending session create_time");
-                self.update_session_create_time(&session, unid.timestamp, true)
+                self.truncate_overlapping_session(&unid).await?;
+                self.update_session_create_time(session, unid.timestamp, true)
                     .await?;
-                return Ok(session.session_id);
+                return Ok(SessionMatch::updated(
+                    session,
+                    SessionMatchKind::MadeCanonical,
+                ));
             }
 
             // If the timestamps are the same, we've found the session_id
             // No need to update the database here - it's already canonical,
             // with an accurate timestamp
-            if skewed_cmp(unid.timestamp, session.create_time) {
+            if self.within_skew(unid.timestamp, session.create_time) {
                 info!("Found existing session with exact create time");
-                return Ok(session.session_id);
+                return Ok(SessionMatch::found(session, SessionMatchKind::Canonical));
             }
 
             // We should never be looking at a case where the query returned
@@ -347,30 +216,17 @@ where
             }
         }
 
-        // Look for last session where session.create_time <= unid.create_time
-        let session = self.find_last_session_before(&unid).await?;
-
-        if let Some(session) = session {
-            // If session.end_time >= unid.create_time (indicates overlapping sessions, error)
-            // This will correct that session so that it does not overlap anymore.
-            if session.end_time >= unid.timestamp {
-                warn!(
-                    "Found session created before new session. Fixing overlapping end_time.
-                    {:?}
-                    {:?}
-                ",
-                    session, unid
-                );
-                // if session.end_time is NOT canonical, we can update it
-                //                self.update_session_end_time(&session, unid.timestamp - 100, session.is_end_canon)?;
-            }
+        if let Some(session_match) = self.find_skewed_creation(&unid).await? {
+            return Ok(session_match);
         }
 
+        self.truncate_overlapping_session(&unid).await?;
+
         // Create new session, return new session id
         let session = Session {
             session_id: Uuid::new_v4().to_string(),
             create_time: unid.timestamp,
-            end_time: unid.timestamp + 101,
+            end_time: default_end_time(unid.timestamp, next_session.as_ref()),
             is_create_canon: true,
             is_end_canon: false,
             version: 0,
@@ -379,14 +235,167 @@ where
 
         info!("Creating session");
         self.create_session(&session).await?;
-        Ok(session.session_id)
+        Ok(SessionMatch::found(&session, SessionMatchKind::Created))
+    }
+
+    /// Looks for the session that a creation at `unid.timestamp` belongs to, when its
+    /// timestamp is skewed after one already recorded for it: either a duplicate creation, or
+    /// a termination that was stamped before the creation.
+    async fn find_skewed_creation(
+        &self,
+        unid: &UnidSession,
+    ) -> Result<Option<SessionMatch>, Error> {
+        let session = match self.find_last_session_before(unid).await? {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+
+        if session.is_create_canon {
+            if self.within_skew(unid.timestamp, session.create_time) {
+                info!("Found existing session with skewed create time");
+                return Ok(Some(SessionMatch::found(
+                    &session,
+                    SessionMatchKind::Canonical,
+                )));
+            }
+            return Ok(None);
+        }
+
+        // A session that was only ever terminated, followed by this creation. This is as likely
+        // to be a reused pid as a skewed clock, so only the configured tolerance applies.
+        if session.is_end_canon
+            && unid.timestamp >= session.end_time
+            && skewed_cmp(unid.timestamp, session.end_time, self.skew.tolerance_ms)
+        {
+            info!("Creation follows its own termination, making create_time canonical");
+            self.make_create_time_canonical(&session).await?;
+            return Ok(Some(SessionMatch::updated(
+                &session,
+                SessionMatchKind::MadeCanonical,
+            )));
+        }
+
+        Ok(None)
+    }
+
+    /// A session created at `unid.timestamp` must not overlap the last session created before
+    /// it. A guessed end_time is pulled back to just before the new session, while a canonical
+    /// end_time is known to be correct and is left as is.
+    async fn truncate_overlapping_session(&self, unid: &UnidSession) -> Result<(), Error> {
+        if unid.timestamp == 0 {
+            return Ok(());
+        }
+
+        // Look for last session where session.create_time < unid.create_time
+        let before = UnidSession {
+            timestamp: unid.timestamp - 1,
+            ..unid.clone()
+        };
+
+        let session = match self.find_last_session_before(&before).await? {
+            Some(session) if session.end_time >= unid.timestamp => session,
+            _ => return Ok(()),
+        };
+
+        if session.is_end_canon {
+            warn!(
+                "Session overlaps a canonical end_time, not truncating. {:?} {:?}",
+                session, unid
+            );
+            return Ok(());
+        }
+
+        info!("Truncating overlapping session end_time");
+        self.update_session_end_time(&session, unid.timestamp - 1, false)
+            .await
+    }
+
+    pub async fn handle_termination_event(
+        &self,
+        unid: UnidSession,
+        should_default: bool,
+    ) -> Result<SessionMatch, Error> {
+        info!(
+            "Handling unid session termination, pseudo_key: {:?} seen at: {}.",
+            unid.pseudo_key, unid.timestamp
+        );
+
+        // Look for last session where session.create_time <= unid.create_time
+        let session = self.find_last_session_before(&unid).await?;
+
+        if let Some(session) = session {
+            if !session.is_end_canon {
+                // The version check fails if the session was modified since we read it,
+                // in which case the event is retried against the updated session
+                info!("Setting canonical session end_time");
+                self.update_session_end_time(&session, unid.timestamp, true)
+                    .await?;
+                return Ok(SessionMatch::updated(
+                    &session,
+                    SessionMatchKind::Terminated,
+                ));
+            }
+
+            // The session has already been terminated, either by this same event or by
+            // one that disagrees with it by less than the session it ended
+            if unid.timestamp <= session.end_time
+                || self.within_skew(unid.timestamp, session.end_time)
+            {
+                info!("Found session with canonical end_time");
+                return Ok(SessionMatch::found(&session, SessionMatchKind::Within));
+            }
+        }
+
+        // The termination may have been stamped before a creation that was already recorded.
+        // Nothing before it was left to end, so that ordering can only be the clock's skew.
+        let next_session = self.find_first_session_after(&unid).await?;
+        if let Some(session) = next_session {
+            if session.is_create_canon && !session.is_end_canon {
+                self.observe_skew(session.create_time - unid.timestamp);
+
+                if self.within_skew(unid.timestamp, session.create_time) {
+                    // A session can't end before it was created
+                    info!("Termination precedes its creation, setting canonical end_time");
+                    self.update_session_end_time(&session, session.create_time, true)
+                        .await?;
+                    return Ok(SessionMatch::updated(
+                        &session,
+                        SessionMatchKind::Terminated,
+                    ));
+                }
+            }
+        }
+
+        // Either there is no session, or the last one ended before this termination, so
+        // this terminates a session that we never saw the creation of
+        if should_default {
+            info!("Defaulting and creating new terminated session.");
+            let session = Session {
+                session_id: Uuid::new_v4().to_string(),
+                create_time: unid.timestamp,
+                end_time: unid.timestamp,
+                is_create_canon: false,
+                is_end_canon: true,
+                version: 0,
+                pseudo_key: unid.pseudo_key,
+            };
+            self.create_session(&session).await?;
+
+            Ok(SessionMatch::found(&session, SessionMatchKind::Defaulted))
+        } else {
+            warn!("Could not attribute terminated session. Not defaulting.");
+            bail!(
+                "Could not attribute terminated session. should_default {}. Not defaulting.",
+                should_default
+            )
+        }
     }
 
     pub async fn handle_last_seen(
         &self,
         unid: UnidSession,
         should_default: bool,
-    ) -> Result<String, Error> {
+    ) -> Result<SessionMatch, Error> {
         info!(
             "Handling unid session, pseudo_key: {:?} seen at: {}.",
             unid.pseudo_key, unid.timestamp
@@ -395,39 +404,47 @@ where
         // Look for session where session.create_time <= unid.create_time <= session.end_time
         // Look for last session where session.create_time <= unid.create_time
         let session = self.find_last_session_before(&unid).await?;
-        if let Some(mut session) = session {
-            if unid.timestamp < session.end_time || skewed_cmp(unid.timestamp, session.end_time) {
+        if let Some(session) = session {
+            if unid.timestamp < session.end_time
+                || self.within_skew(unid.timestamp, session.end_time)
+            {
                 info!("Identified session because it fell within a timeline.");
-                return Ok(session.session_id);
+                return Ok(SessionMatch::found(&session, SessionMatchKind::Within));
             }
 
+            // Only a session that hasn't been terminated can be extended. Once its end_time
+            // is canonical, anything seen after it belongs to a later session.
             if !session.is_end_canon {
-                session.end_time = unid.timestamp;
                 info!("Updating session end_time.");
-                //                self.update_session_end_time(&session, unid.timestamp, false)?;
+                self.update_session_end_time(&session, unid.timestamp, false)
+                    .await?;
 
-                return Ok(session.session_id);
+                return Ok(SessionMatch::updated(&session, SessionMatchKind::Extended));
             }
         }
 
-        let session = self.find_first_session_after(&unid).await?;
-        if let Some(session) = session {
+        let next_session = self.find_first_session_after(&unid).await?;
+        if let Some(ref session) = next_session {
             if !session.is_create_canon {
                 info!("Found a later, non canonical session. Extending create_time..");
 
-                self.update_session_create_time(&session, unid.timestamp, false)
+                self.update_session_create_time(session, unid.timestamp, false)
                     .await?;
-                return Ok(session.session_id);
+                return Ok(SessionMatch::updated(session, SessionMatchKind::Extended));
+            }
+
+            if self.within_skew(unid.timestamp, session.create_time) {
+                info!("Found a later session, created within skew.");
+                return Ok(SessionMatch::found(session, SessionMatchKind::Within));
             }
         }
 
         if should_default {
             info!("Defaulting and creating new session.");
-            let session_id = Uuid::new_v4().to_string();
             let session = Session {
-                session_id: session_id.clone(),
+                session_id: Uuid::new_v4().to_string(),
                 create_time: unid.timestamp,
-                end_time: unid.timestamp + 101,
+                end_time: default_end_time(unid.timestamp, next_session.as_ref()),
                 is_create_canon: false,
                 is_end_canon: false,
                 version: 0,
@@ -435,7 +452,7 @@ where
             };
             self.create_session(&session).await?;
 
-            Ok(session_id)
+            Ok(SessionMatch::found(&session, SessionMatchKind::Defaulted))
         } else {
             warn!("Could not attribute session. Not defaulting.");
             bail!(
@@ -447,18 +464,65 @@ where
 
     pub async fn handle_unid_session(
         &self,
-        mut unid: UnidSession,
+        unid: UnidSession,
         should_default: bool,
     ) -> Result<String, Error> {
-        unid.timestamp = shave_int(unid.timestamp, 1);
-        if unid.is_creation {
-            self.handle_creation_event(unid).await
-        } else {
-            self.handle_last_seen(unid, should_default).await
+        let session_match = self.match_unid_session(unid, should_default).await?;
+        Ok(session_match.session_id)
+    }
+
+    /// Resolves `unid` to its session like `handle_unid_session`, also describing how the
+    /// session was matched
+    pub async fn match_unid_session(
+        &self,
+        mut unid: UnidSession,
+        should_default: bool,
+    ) -> Result<SessionMatch, Error> {
+        unid.timestamp -= unid.timestamp % self.skew.precision_ms;
+
+        // Every handler reads the timeline before updating it, so when a session turns out
+        // to have been modified in between, the event is simply handled again
+        let mut conflicts = 0;
+        loop {
+            let result = if unid.is_creation {
+                self.handle_creation_event(unid.clone()).await
+            } else if unid.is_termination {
+                self.handle_termination_event(unid.clone(), should_default)
+                    .await
+            } else {
+                self.handle_last_seen(unid.clone(), should_default).await
+            };
+
+            match result {
+                Err(e) if is_version_conflict(&e) && conflicts < MAX_VERSION_CONFLICTS => {
+                    conflicts += 1;
+                    warn!("Retrying unid session after conflict: {}", e);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// How many times an event is retried after a concurrent session update before giving up
+const MAX_VERSION_CONFLICTS: usize = 3;
+
+fn is_version_conflict(e: &Error) -> bool {
+    e.downcast_ref::<VersionConflict>().is_some()
+}
+
+/// The end_time of a session whose real end we have yet to see: a little after its creation,
+/// but never overlapping the session that follows it
+fn default_end_time(create_time: u64, next_session: Option<&Session>) -> u64 {
+    match next_session {
+        Some(next) if next.create_time > create_time => {
+            std::cmp::min(create_time + 101, next.create_time - 1)
         }
+        _ => create_time + 101,
     }
 }
 
-pub fn skewed_cmp(ts_1: u64, ts_2: u64) -> bool {
-    ts_1 - 10 < ts_2 && ts_1 + 10 > ts_2
+/// Whether two timestamps are at most `tolerance_ms` apart
+pub fn skewed_cmp(ts_1: u64, ts_2: u64, tolerance_ms: u64) -> bool {
+    std::cmp::max(ts_1, ts_2) - std::cmp::min(ts_1, ts_2) <= tolerance_ms
 }
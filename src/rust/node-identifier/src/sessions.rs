@@ -6,11 +6,12 @@ use rusoto_dynamodb::AttributeValue;
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnidSession {
     pub pseudo_key: String,
     pub timestamp: u64,
-    pub is_creation: bool, // Is this a creation event
+    pub is_creation: bool,    // Is this a creation event
+    pub is_termination: bool, // Is this a termination event
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +25,54 @@ pub struct Session {
     pub version: u64, // This is an atomic version used for transactions
 }
 
+/// How an event was matched to its session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionMatchKind {
+    /// A session created at the event's (canonical) create time
+    Canonical,
+    /// A guessed session, whose create time this creation confirmed
+    MadeCanonical,
+    /// The event fell within a session's known lifetime
+    Within,
+    /// A non canonical session, stretched to cover the event
+    Extended,
+    /// A new session, started by this creation
+    Created,
+    /// A session that this termination ended
+    Terminated,
+    /// Nothing matched, so a new session was guessed
+    Defaulted,
+}
+
+/// The session an event resolved to, and how
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionMatch {
+    pub session_id: String,
+    /// The session's version once the event was handled
+    pub version: u64,
+    pub kind: SessionMatchKind,
+}
+
+impl SessionMatch {
+    /// `session` was matched as it was read
+    pub fn found(session: &Session, kind: SessionMatchKind) -> Self {
+        Self {
+            session_id: session.session_id.clone(),
+            version: session.version,
+            kind,
+        }
+    }
+
+    /// `session` was matched, and then updated once
+    pub fn updated(session: &Session, kind: SessionMatchKind) -> Self {
+        Self {
+            version: session.version + 1,
+            ..Self::found(session, kind)
+        }
+    }
+}
+
 impl TryFrom<HashMap<String, AttributeValue>> for Session {
     type Error = Error;
     fn try_from(map: HashMap<String, AttributeValue>) -> Result<Self, Error> {
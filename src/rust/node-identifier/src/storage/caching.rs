@@ -0,0 +1,518 @@
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use failure::Error;
+use lru::LruCache;
+
+use crate::assetdb::{AssetMapping, AssetSkew};
+use crate::env_or;
+use crate::sessions::{Session, UnidSession};
+use crate::storage::{AssetIdStorage, MappingStorage, ScanCursor, ScanPage, SessionStorage};
+
+/// How many timelines the cache holds, and for how long
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    /// The number of session timelines, and separately of hostnames, to keep
+    pub capacity: usize,
+    /// Bounds how stale an entry can be when another node identifier has written to the
+    /// same timeline
+    pub ttl: Duration,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+impl CacheOptions {
+    /// Reads `NODE_IDENTIFIER_CACHE_CAPACITY` and `NODE_IDENTIFIER_CACHE_TTL_SECS`, falling
+    /// back to the defaults
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let capacity = env_or("NODE_IDENTIFIER_CACHE_CAPACITY", defaults.capacity);
+        let ttl = env_or("NODE_IDENTIFIER_CACHE_TTL_SECS", defaults.ttl.as_secs());
+        Self {
+            capacity: capacity.max(1),
+            ttl: Duration::from_secs(ttl),
+        }
+    }
+}
+
+/// What a `find_last_*_before` query established: `value`, created at `created`, was the
+/// latest entry in its timeline for every timestamp up to `known_until`
+#[derive(Debug, Clone)]
+struct Known<T> {
+    value: T,
+    created: u64,
+    known_until: u64,
+}
+
+#[derive(Debug)]
+struct Timeline<T> {
+    known: Vec<Known<T>>,
+    expires_at: Instant,
+}
+
+impl<T: Clone> Timeline<T> {
+    fn lookup(&self, ts: u64) -> Option<T> {
+        self.known
+            .iter()
+            .find(|known| known.created <= ts && ts <= known.known_until)
+            .map(|known| known.value.clone())
+    }
+
+    fn insert(&mut self, value: T, created: u64, known_until: u64) {
+        match self.known.iter_mut().find(|known| known.created == created) {
+            Some(known) => {
+                known.value = value;
+                known.known_until = known.known_until.max(known_until);
+            }
+            None => self.known.push(Known {
+                value,
+                created,
+                known_until,
+            }),
+        }
+    }
+}
+
+struct Timelines<K: Hash + Eq, T> {
+    timelines: LruCache<K, Timeline<T>>,
+    ttl: Duration,
+}
+
+impl<K: Hash + Eq + Clone, T: Clone> Timelines<K, T> {
+    fn new(options: &CacheOptions) -> Self {
+        Self {
+            timelines: LruCache::new(options.capacity),
+            ttl: options.ttl,
+        }
+    }
+
+    fn lookup(&mut self, key: &K, ts: u64) -> Option<T> {
+        let expired = match self.timelines.get(key) {
+            Some(timeline) if timeline.expires_at > Instant::now() => return timeline.lookup(ts),
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            self.timelines.pop(key);
+        }
+        None
+    }
+
+    /// The live timeline for `key`, starting a new one if it's missing or expired
+    fn timeline(&mut self, key: K) -> &mut Timeline<T> {
+        let live = match self.timelines.peek(&key) {
+            Some(timeline) => timeline.expires_at > Instant::now(),
+            None => false,
+        };
+        if !live {
+            let timeline = Timeline {
+                known: vec![],
+                expires_at: Instant::now() + self.ttl,
+            };
+            self.timelines.put(key.clone(), timeline);
+        }
+        self.timelines.get_mut(&key).unwrap()
+    }
+}
+
+struct Tables {
+    /// Keyed by (table name, pseudo key)
+    sessions: Timelines<(String, String), Session>,
+    /// Keyed by host pseudo key
+    asset_ids: Timelines<String, AssetMapping>,
+    /// Keyed by asset id, along with when each estimate expires
+    asset_skews: LruCache<String, (Option<AssetSkew>, Instant)>,
+    ttl: Duration,
+    /// Bumped on every write, so that reads which raced with a write are never cached
+    generation: u64,
+}
+
+/// Resolved sessions, asset ids and asset skews, shared between every `CachingStorage` built from it
+#[derive(Clone)]
+pub struct LookupCache {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl LookupCache {
+    pub fn new(options: CacheOptions) -> Self {
+        Self {
+            tables: Arc::new(Mutex::new(Tables {
+                sessions: Timelines::new(&options),
+                asset_ids: Timelines::new(&options),
+                asset_skews: LruCache::new(options.capacity),
+                ttl: options.ttl,
+                generation: 0,
+            })),
+        }
+    }
+
+    fn tables(&self) -> std::sync::MutexGuard<'_, Tables> {
+        self.tables.lock().unwrap()
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheStats {
+    session_hits: AtomicU64,
+    session_misses: AtomicU64,
+    asset_id_hits: AtomicU64,
+    asset_id_misses: AtomicU64,
+}
+
+/// Answers `find_last_session_before`, `find_last_asset_mapping_before` and `get_asset_skew`
+/// from a `LookupCache` where it can, passing everything else through to the wrapped backend.
+///
+/// Any write to a session timeline drops that timeline from the cache, both before and after
+/// the write, so a retry after a `VersionConflict` always reads through. Sessions are assumed
+/// not to overlap (see `SessionDb`), so a cached session answers for any timestamp up to its
+/// end time.
+///
+/// Clones share the same hit and miss counts, which are independent of the shared cache.
+#[derive(Clone)]
+pub struct CachingStorage<S> {
+    inner: S,
+    cache: LookupCache,
+    stats: Arc<CacheStats>,
+}
+
+impl<S> CachingStorage<S> {
+    pub fn new(inner: S, cache: LookupCache) -> Self {
+        Self {
+            inner,
+            cache,
+            stats: Arc::new(CacheStats::default()),
+        }
+    }
+
+    pub fn session_hits(&self) -> u64 {
+        self.stats.session_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn session_misses(&self) -> u64 {
+        self.stats.session_misses.load(Ordering::Relaxed)
+    }
+
+    pub fn asset_id_hits(&self) -> u64 {
+        self.stats.asset_id_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn asset_id_misses(&self) -> u64 {
+        self.stats.asset_id_misses.load(Ordering::Relaxed)
+    }
+
+    fn invalidate_sessions(&self, table_name: &str, pseudo_key: &str) {
+        let mut tables = self.cache.tables();
+        tables.generation += 1;
+        tables
+            .sessions
+            .timelines
+            .pop(&(table_name.to_owned(), pseudo_key.to_owned()));
+    }
+}
+
+#[async_trait]
+impl<S> SessionStorage for CachingStorage<S>
+where
+    S: SessionStorage,
+{
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        self.inner.find_first_session_after(table_name, unid).await
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        let key = (table_name.to_owned(), unid.pseudo_key.clone());
+        let generation = {
+            let mut tables = self.cache.tables();
+            if let Some(session) = tables.sessions.lookup(&key, unid.timestamp) {
+                self.stats.session_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(session));
+            }
+            tables.generation
+        };
+        self.stats.session_misses.fetch_add(1, Ordering::Relaxed);
+
+        let session = self
+            .inner
+            .find_last_session_before(table_name, unid)
+            .await?;
+
+        if let Some(session) = &session {
+            let mut tables = self.cache.tables();
+            if tables.generation == generation {
+                let known_until = unid.timestamp.max(session.end_time);
+                tables.sessions.timeline(key).insert(
+                    session.clone(),
+                    session.create_time,
+                    known_until,
+                );
+            }
+        }
+
+        Ok(session)
+    }
+
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        let result = self
+            .inner
+            .update_session_create_time(table_name, session, new_time, is_canon)
+            .await;
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        result
+    }
+
+    async fn make_create_time_canonical(
+        &self,
+        table_name: &str,
+        session: &Session,
+    ) -> Result<(), Error> {
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        let result = self
+            .inner
+            .make_create_time_canonical(table_name, session)
+            .await;
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        result
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        let result = self
+            .inner
+            .update_session_end_time(table_name, session, new_time, is_canon)
+            .await;
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        result
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        let result = self.inner.create_session(table_name, session).await;
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        result
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        let result = self.inner.delete_session(table_name, session).await;
+        self.invalidate_sessions(table_name, &session.pseudo_key);
+        result
+    }
+
+    async fn scan_sessions(
+        &self,
+        table_name: &str,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<Session>, Error> {
+        self.inner.scan_sessions(table_name, start, limit).await
+    }
+}
+
+#[async_trait]
+impl<S> AssetIdStorage for CachingStorage<S>
+where
+    S: AssetIdStorage,
+{
+    async fn find_first_asset_mapping_after(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        self.inner
+            .find_first_asset_mapping_after(pseudo_key, ts)
+            .await
+    }
+
+    async fn find_last_asset_mapping_before(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let key = pseudo_key.to_owned();
+        let generation = {
+            let mut tables = self.cache.tables();
+            if let Some(mapping) = tables.asset_ids.lookup(&key, ts) {
+                self.stats.asset_id_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(Some(mapping));
+            }
+            tables.generation
+        };
+        self.stats.asset_id_misses.fetch_add(1, Ordering::Relaxed);
+
+        let mapping = self
+            .inner
+            .find_last_asset_mapping_before(pseudo_key, ts)
+            .await?;
+
+        // The mapping was the latest one for every timestamp from its creation up to `ts`
+        if let Some(mapping) = &mapping {
+            let mut tables = self.cache.tables();
+            if tables.generation == generation {
+                tables
+                    .asset_ids
+                    .timeline(key)
+                    .insert(mapping.clone(), mapping.c_timestamp, ts);
+            }
+        }
+
+        Ok(mapping)
+    }
+
+    async fn create_asset_mapping(
+        &self,
+        pseudo_key: String,
+        asset_id: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .create_asset_mapping(pseudo_key.clone(), asset_id.clone(), c_timestamp)
+            .await;
+
+        // Only drop the hostname when the new mapping changes a cached answer
+        let mut tables = self.cache.tables();
+        tables.generation += 1;
+        let changed = match tables.asset_ids.timelines.peek(&pseudo_key) {
+            Some(timeline) => timeline
+                .known
+                .iter()
+                .any(|known| known.created <= c_timestamp && c_timestamp <= known.known_until),
+            None => false,
+        };
+        if changed {
+            tables.asset_ids.timelines.pop(&pseudo_key);
+        }
+
+        result
+    }
+
+    async fn end_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .end_asset_mapping(pseudo_key.clone(), c_timestamp, end_timestamp)
+            .await;
+
+        let mut tables = self.cache.tables();
+        tables.generation += 1;
+        tables.asset_ids.timelines.pop(&pseudo_key);
+
+        result
+    }
+
+    async fn get_asset_skew(&self, asset_id: &str) -> Result<Option<AssetSkew>, Error> {
+        let generation = {
+            let mut tables = self.cache.tables();
+            match tables.asset_skews.get(&asset_id.to_owned()) {
+                Some((skew_ms, expires_at)) if *expires_at > Instant::now() => {
+                    return Ok(*skew_ms);
+                }
+                _ => tables.generation,
+            }
+        };
+
+        let skew_ms = self.inner.get_asset_skew(asset_id).await?;
+
+        let mut tables = self.cache.tables();
+        if tables.generation == generation {
+            let expires_at = Instant::now() + tables.ttl;
+            tables
+                .asset_skews
+                .put(asset_id.to_owned(), (skew_ms, expires_at));
+        }
+
+        Ok(skew_ms)
+    }
+
+    async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .record_asset_skew(asset_id.clone(), skew, ttl_ms)
+            .await;
+
+        let mut tables = self.cache.tables();
+        tables.generation += 1;
+        tables.asset_skews.pop(&asset_id);
+
+        result
+    }
+
+    async fn delete_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        let result = self
+            .inner
+            .delete_asset_mapping(pseudo_key.clone(), c_timestamp)
+            .await;
+
+        let mut tables = self.cache.tables();
+        tables.generation += 1;
+        tables.asset_ids.timelines.pop(&pseudo_key);
+
+        result
+    }
+
+    async fn scan_asset_mappings(
+        &self,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<(String, AssetMapping)>, Error> {
+        self.inner.scan_asset_mappings(start, limit).await
+    }
+}
+
+#[async_trait]
+impl<S> MappingStorage for CachingStorage<S>
+where
+    S: MappingStorage,
+{
+    async fn get_mapping(&self, pseudo_key: &str) -> Result<Option<String>, Error> {
+        self.inner.get_mapping(pseudo_key).await
+    }
+
+    async fn put_mapping(&self, pseudo_key: String, maps_to: String) -> Result<bool, Error> {
+        self.inner.put_mapping(pseudo_key, maps_to).await
+    }
+}
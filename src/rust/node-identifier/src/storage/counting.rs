@@ -0,0 +1,216 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use failure::Error;
+
+use crate::assetdb::{AssetMapping, AssetSkew};
+use crate::sessions::{Session, UnidSession};
+use crate::storage::{AssetIdStorage, MappingStorage, ScanCursor, ScanPage, SessionStorage};
+
+#[derive(Debug, Default)]
+struct Counts {
+    reads: AtomicU64,
+    writes: AtomicU64,
+}
+
+/// Wraps another backend, counting the reads and writes issued against it. Clones share the
+/// same counts, so wrapping a backend once per subgraph gives that subgraph's call counts.
+#[derive(Debug, Clone)]
+pub struct CountingStorage<S> {
+    inner: S,
+    counts: Arc<Counts>,
+}
+
+impl<S> CountingStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            counts: Arc::new(Counts::default()),
+        }
+    }
+
+    pub fn reads(&self) -> u64 {
+        self.counts.reads.load(Ordering::Relaxed)
+    }
+
+    pub fn writes(&self) -> u64 {
+        self.counts.writes.load(Ordering::Relaxed)
+    }
+
+    fn read(&self) -> &S {
+        self.counts.reads.fetch_add(1, Ordering::Relaxed);
+        &self.inner
+    }
+
+    fn write(&self) -> &S {
+        self.counts.writes.fetch_add(1, Ordering::Relaxed);
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<S> SessionStorage for CountingStorage<S>
+where
+    S: SessionStorage,
+{
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        self.read().find_first_session_after(table_name, unid).await
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        self.read().find_last_session_before(table_name, unid).await
+    }
+
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        self.write()
+            .update_session_create_time(table_name, session, new_time, is_canon)
+            .await
+    }
+
+    async fn make_create_time_canonical(
+        &self,
+        table_name: &str,
+        session: &Session,
+    ) -> Result<(), Error> {
+        self.write()
+            .make_create_time_canonical(table_name, session)
+            .await
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        self.write()
+            .update_session_end_time(table_name, session, new_time, is_canon)
+            .await
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        self.write().create_session(table_name, session).await
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        self.write().delete_session(table_name, session).await
+    }
+
+    async fn scan_sessions(
+        &self,
+        table_name: &str,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<Session>, Error> {
+        self.read().scan_sessions(table_name, start, limit).await
+    }
+}
+
+#[async_trait]
+impl<S> AssetIdStorage for CountingStorage<S>
+where
+    S: AssetIdStorage,
+{
+    async fn find_first_asset_mapping_after(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        self.read()
+            .find_first_asset_mapping_after(pseudo_key, ts)
+            .await
+    }
+
+    async fn find_last_asset_mapping_before(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        self.read()
+            .find_last_asset_mapping_before(pseudo_key, ts)
+            .await
+    }
+
+    async fn create_asset_mapping(
+        &self,
+        pseudo_key: String,
+        asset_id: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        self.write()
+            .create_asset_mapping(pseudo_key, asset_id, c_timestamp)
+            .await
+    }
+
+    async fn end_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(), Error> {
+        self.write()
+            .end_asset_mapping(pseudo_key, c_timestamp, end_timestamp)
+            .await
+    }
+
+    async fn get_asset_skew(&self, asset_id: &str) -> Result<Option<AssetSkew>, Error> {
+        self.read().get_asset_skew(asset_id).await
+    }
+
+    async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error> {
+        self.write().record_asset_skew(asset_id, skew, ttl_ms).await
+    }
+
+    async fn delete_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        self.write()
+            .delete_asset_mapping(pseudo_key, c_timestamp)
+            .await
+    }
+
+    async fn scan_asset_mappings(
+        &self,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<(String, AssetMapping)>, Error> {
+        self.read().scan_asset_mappings(start, limit).await
+    }
+}
+
+#[async_trait]
+impl<S> MappingStorage for CountingStorage<S>
+where
+    S: MappingStorage,
+{
+    async fn get_mapping(&self, pseudo_key: &str) -> Result<Option<String>, Error> {
+        self.read().get_mapping(pseudo_key).await
+    }
+
+    async fn put_mapping(&self, pseudo_key: String, maps_to: String) -> Result<bool, Error> {
+        self.write().put_mapping(pseudo_key, maps_to).await
+    }
+}
@@ -0,0 +1,694 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use async_trait::async_trait;
+use failure::{bail, Error};
+use hmap::hmap;
+use log::info;
+use rusoto_core::RusotoError;
+use rusoto_dynamodb::{
+    AttributeValue, AttributeValueUpdate, Delete, DeleteItemInput, DynamoDb, GetItemInput, Put,
+    PutItemError, PutItemInput, QueryInput, ScanInput, TransactWriteItem, TransactWriteItemsError,
+    TransactWriteItemsInput, UpdateItemError, UpdateItemInput,
+};
+
+use crate::assetdb::{AssetIdMapping, AssetMapping, AssetSkew};
+use crate::dynamic_sessiondb::{DirectMapping, ResolvedMapping};
+use crate::sessions::{Session, UnidSession};
+use crate::storage::{
+    AssetIdStorage, MappingStorage, ScanCursor, ScanPage, SessionStorage, VersionConflict,
+};
+
+fn session_key(session: &Session) -> HashMap<String, AttributeValue> {
+    hmap! {
+        "pseudo_key".to_owned() => AttributeValue {
+            s: session.pseudo_key.clone().into(),
+            ..Default::default()
+        },
+        "create_time".to_owned() => AttributeValue {
+            n: session.create_time.to_string().into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Skew estimates live in the asset id mappings table, under a pseudo key that no hostname
+/// mapping uses
+fn asset_skew_key(asset_id: &str) -> HashMap<String, AttributeValue> {
+    hmap! {
+        "pseudo_key".to_owned() => AttributeValue {
+            s: format!("asset_skew{}", asset_id).into(),
+            ..Default::default()
+        },
+        "c_timestamp".to_owned() => AttributeValue {
+            n: "0".to_owned().into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// The key of the row a scan resumes after, where `sort_key` names the table's range key
+fn cursor_key(cursor: &ScanCursor, sort_key: &str) -> HashMap<String, AttributeValue> {
+    hmap! {
+        "pseudo_key".to_owned() => AttributeValue {
+            s: cursor.pseudo_key.clone().into(),
+            ..Default::default()
+        },
+        sort_key.to_owned() => AttributeValue {
+            n: cursor.sort_key.to_string().into(),
+            ..Default::default()
+        }
+    }
+}
+
+fn key_cursor(key: &HashMap<String, AttributeValue>, sort_key: &str) -> Result<ScanCursor, Error> {
+    let pseudo_key = key.get("pseudo_key").and_then(|value| value.s.clone());
+    let sort_value = key.get(sort_key).and_then(|value| value.n.as_ref());
+
+    match (pseudo_key, sort_value) {
+        (Some(pseudo_key), Some(sort_value)) => Ok(ScanCursor {
+            pseudo_key,
+            sort_key: sort_value.parse()?,
+        }),
+        _ => bail!("Scan stopped at an unexpected key: {:?}", key),
+    }
+}
+
+#[async_trait]
+impl<D> SessionStorage for D
+where
+    D: DynamoDb + Clone + Send + Sync,
+{
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        info!("Finding first session after : {}", table_name);
+        let query = QueryInput {
+            consistent_read: Some(true),
+            limit: Some(1),
+            table_name: table_name.to_owned(),
+            key_condition_expression: Some(
+                "pseudo_key = :pseudo_key AND create_time >= :create_time".into(),
+            ),
+            expression_attribute_values: Some(hmap! {
+                ":pseudo_key".to_owned() => AttributeValue {
+                    s: unid.pseudo_key.clone().into(),
+                    ..Default::default()
+                },
+                ":create_time".to_owned() => AttributeValue {
+                    n: unid.timestamp.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.query(query));
+        if let Err(RusotoError::Unknown(ref e)) = res {
+            bail!("Query failed with error: {:?}", e);
+        };
+
+        if let Some(items) = res?.items {
+            match &items[..] {
+                [] => Ok(None),
+                [item] => Session::try_from(item.clone()).map(Option::from),
+                _ => bail!("Unexpected number of items returned"),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        info!("Finding last session before");
+        let query = QueryInput {
+            consistent_read: Some(true),
+            limit: Some(1),
+            scan_index_forward: Some(false),
+            table_name: table_name.to_owned(),
+            key_condition_expression: Some(
+                "pseudo_key = :pseudo_key AND create_time <= :create_time".into(),
+            ),
+            expression_attribute_values: Some(hmap! {
+                ":pseudo_key".to_owned() => AttributeValue {
+                    s: unid.pseudo_key.clone().into(),
+                    ..Default::default()
+                },
+                ":create_time".to_owned() => AttributeValue {
+                    n: unid.timestamp.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.query(query))?;
+
+        if let Some(items) = res.items {
+            match &items[..] {
+                [] => Ok(None),
+                [item] => Session::try_from(item.clone()).map(Option::from),
+                _ => bail!("Unexpected number of items returned"),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    // `create_time` is the sort key in the table, so updating it is not possible.
+    // Instead, in one transaction, the row must be deleted and recreated with the
+    // new create_time
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        info!("Updating session create time");
+        let mut new_session = session.to_owned();
+        new_session.create_time = new_time;
+        new_session.is_create_canon = is_canon;
+        new_session.version += 1;
+        // Create new session with new create_time, increment version
+
+        let put_req = Put {
+            item: serde_dynamodb::to_hashmap(&new_session).unwrap(),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        // Use version as a constraint
+        let del_req = Delete {
+            key: session_key(session),
+            table_name: table_name.to_owned(),
+            condition_expression: Some("version = :version".into()),
+            expression_attribute_values: Some(hmap! {
+                ":version".to_owned() => AttributeValue {
+                    n: session.version.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        let written = wait_on!(self.transact_write_items(TransactWriteItemsInput {
+            transact_items: vec![
+                TransactWriteItem {
+                    delete: del_req.into(),
+                    ..Default::default()
+                },
+                TransactWriteItem {
+                    put: put_req.into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }));
+
+        match written {
+            Ok(_) => Ok(()),
+            // The reasons are listed per item, ie: "[ConditionalCheckFailed, None]"
+            Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(reasons)))
+                if reasons.contains("ConditionalCheckFailed") =>
+            {
+                Err(VersionConflict::new(session).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn make_create_time_canonical(
+        &self,
+        table_name: &str,
+        session: &Session,
+    ) -> Result<(), Error> {
+        info!("Updating session end time");
+        // Use version as a constraint
+        let upd_req = UpdateItemInput {
+            key: session_key(session),
+            attribute_updates: Some(hmap! {
+                "is_create_canon".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                            bool: true.into(),
+                            ..Default::default()
+                        }),
+                    ..Default::default()
+                },
+                "version".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                        n: (session.version + 1).to_string().into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }
+            }),
+            table_name: table_name.to_owned(),
+            condition_expression: Some("version = :version".into()),
+            expression_attribute_values: Some(hmap! {
+                ":version".to_owned() => AttributeValue {
+                    n: session.version.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        match wait_on!(self.update_item(upd_req)) {
+            Ok(_) => Ok(()),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => {
+                Err(VersionConflict::new(session).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Update version, and use it as a constraint
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        info!("Updating session end time");
+        // Use version as a constraint
+        let upd_req = UpdateItemInput {
+            key: session_key(session),
+            attribute_updates: Some(hmap! {
+                "end_time".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                        n: new_time.to_string().into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                "is_end_canon".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                            bool: is_canon.into(),
+                            ..Default::default()
+                        }),
+                    ..Default::default()
+                },
+                "version".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                        n: (session.version + 1).to_string().into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+
+                }
+            }),
+            table_name: table_name.to_owned(),
+            condition_expression: Some("version = :version".into()),
+            expression_attribute_values: Some(hmap! {
+                ":version".to_owned() => AttributeValue {
+                    n: session.version.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        match wait_on!(self.update_item(upd_req)) {
+            Ok(_) => Ok(()),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => {
+                Err(VersionConflict::new(session).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        info!("create session");
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(session).unwrap(),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        wait_on!(self.put_item(put_req))?;
+
+        Ok(())
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        info!("delete session");
+        let del_req = DeleteItemInput {
+            key: session_key(session),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        wait_on!(self.delete_item(del_req))?;
+        Ok(())
+    }
+
+    async fn scan_sessions(
+        &self,
+        table_name: &str,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<Session>, Error> {
+        let scan = ScanInput {
+            consistent_read: Some(true),
+            limit: Some(limit as i64),
+            exclusive_start_key: start.map(|start| cursor_key(start, "create_time")),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.scan(scan))?;
+
+        let items = res
+            .items
+            .unwrap_or_default()
+            .into_iter()
+            .map(Session::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        let next = match res.last_evaluated_key {
+            Some(key) => Some(key_cursor(&key, "create_time")?),
+            None => None,
+        };
+
+        Ok(ScanPage { items, next })
+    }
+}
+
+#[async_trait]
+impl<D> AssetIdStorage for D
+where
+    D: DynamoDb + Clone + Send + Sync,
+{
+    async fn find_first_asset_mapping_after(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let query = QueryInput {
+            consistent_read: Some(true),
+            limit: Some(1),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            key_condition_expression: Some(
+                "pseudo_key = :pkey_val AND c_timestamp >= :c_timestamp".into(),
+            ),
+            expression_attribute_values: Some(hmap! {
+                ":pkey_val".to_owned() => AttributeValue {
+                    s: pseudo_key.to_owned().into(),
+                    ..Default::default()
+                },
+                ":c_timestamp".to_owned() => AttributeValue {
+                    n: ts.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.query(query))?;
+
+        match res.items {
+            Some(mut items) if items.len() == 1 => {
+                let item = items.remove(0);
+                Ok(Some(serde_dynamodb::from_hashmap(item)?))
+            }
+            Some(items) if items.is_empty() => Ok(None),
+            Some(items) => bail!("Unexpected number of items returned: {}", items.len()),
+            None => Ok(None),
+        }
+    }
+
+    async fn find_last_asset_mapping_before(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let query = QueryInput {
+            consistent_read: Some(true),
+            limit: Some(1),
+            scan_index_forward: Some(false),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            key_condition_expression: Some(
+                "pseudo_key = :pseudo_key AND c_timestamp <= :c_timestamp".into(),
+            ),
+            expression_attribute_values: Some(hmap! {
+                ":pseudo_key".to_owned() => AttributeValue {
+                    s: pseudo_key.to_owned().into(),
+                    ..Default::default()
+                },
+                ":c_timestamp".to_owned() => AttributeValue {
+                    n: ts.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.query(query))?;
+
+        if let Some(items) = res.items {
+            match &items[..] {
+                [] => Ok(None),
+                [item] => Ok(Some(serde_dynamodb::from_hashmap(item.clone())?)),
+                _ => bail!("Unexpected number of items returned"),
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn create_asset_mapping(
+        &self,
+        pseudo_key: String,
+        asset_id: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        let mapping = AssetIdMapping {
+            pseudo_key,
+            asset_id: &asset_id,
+            c_timestamp,
+        };
+
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            ..Default::default()
+        };
+
+        let put_item_response = wait_on!(self.put_item(put_req))?;
+
+        info!(
+            "PutItemResponse for {:?} {}: {:?}",
+            mapping.pseudo_key, asset_id, put_item_response
+        );
+
+        Ok(())
+    }
+
+    async fn end_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(), Error> {
+        let upd_req = UpdateItemInput {
+            key: hmap! {
+                "pseudo_key".to_owned() => AttributeValue {
+                    s: pseudo_key.into(),
+                    ..Default::default()
+                },
+                "c_timestamp".to_owned() => AttributeValue {
+                    n: c_timestamp.to_string().into(),
+                    ..Default::default()
+                }
+            },
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            update_expression: Some("SET end_timestamp = :end_timestamp".into()),
+            condition_expression: Some("attribute_exists(asset_id)".into()),
+            expression_attribute_values: Some(hmap! {
+                ":end_timestamp".to_owned() => AttributeValue {
+                    n: end_timestamp.to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        match wait_on!(self.update_item(upd_req)) {
+            Ok(_) => Ok(()),
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => {
+                bail!("No asset mapping created at {}", c_timestamp)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_asset_skew(&self, asset_id: &str) -> Result<Option<AssetSkew>, Error> {
+        let query = GetItemInput {
+            consistent_read: Some(true),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            key: asset_skew_key(asset_id),
+            ..Default::default()
+        };
+
+        match wait_on!(self.get_item(query))?.item {
+            Some(item) => Ok(Some(serde_dynamodb::from_hashmap(item)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error> {
+        let upd_req = UpdateItemInput {
+            key: asset_skew_key(&asset_id),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            update_expression: Some("SET skew_ms = :skew_ms, observed_at = :observed_at".into()),
+            condition_expression: Some(
+                "attribute_not_exists(skew_ms) OR attribute_not_exists(observed_at) \
+                 OR skew_ms <= :skew_ms OR observed_at < :expired_before"
+                    .into(),
+            ),
+            expression_attribute_values: Some(hmap! {
+                ":skew_ms".to_owned() => AttributeValue {
+                    n: skew.skew_ms.to_string().into(),
+                    ..Default::default()
+                },
+                ":observed_at".to_owned() => AttributeValue {
+                    n: skew.observed_at.to_string().into(),
+                    ..Default::default()
+                },
+                ":expired_before".to_owned() => AttributeValue {
+                    n: skew.observed_at.saturating_sub(ttl_ms).to_string().into(),
+                    ..Default::default()
+                }
+            }),
+            ..Default::default()
+        };
+
+        match wait_on!(self.update_item(upd_req)) {
+            Ok(_) => Ok(()),
+            // A wider estimate that hasn't expired has already been stored
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        let del_req = DeleteItemInput {
+            key: cursor_key(
+                &ScanCursor {
+                    pseudo_key,
+                    sort_key: c_timestamp,
+                },
+                "c_timestamp",
+            ),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            ..Default::default()
+        };
+
+        wait_on!(self.delete_item(del_req))?;
+        Ok(())
+    }
+
+    async fn scan_asset_mappings(
+        &self,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<(String, AssetMapping)>, Error> {
+        let scan = ScanInput {
+            consistent_read: Some(true),
+            limit: Some(limit as i64),
+            exclusive_start_key: start.map(|start| cursor_key(start, "c_timestamp")),
+            table_name: grapl_config::asset_id_mappings_table_name(),
+            // Skips the skew estimates that share the table
+            filter_expression: Some("attribute_exists(asset_id)".into()),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.scan(scan))?;
+
+        let mut items = vec![];
+        for item in res.items.unwrap_or_default() {
+            let pseudo_key = match item.get("pseudo_key").and_then(|value| value.s.clone()) {
+                Some(pseudo_key) => pseudo_key,
+                None => bail!("Asset id mapping is missing its pseudo_key"),
+            };
+            items.push((pseudo_key, serde_dynamodb::from_hashmap(item)?));
+        }
+        let next = match res.last_evaluated_key {
+            Some(key) => Some(key_cursor(&key, "c_timestamp")?),
+            None => None,
+        };
+
+        Ok(ScanPage { items, next })
+    }
+}
+
+#[async_trait]
+impl<D> MappingStorage for D
+where
+    D: DynamoDb + Clone + Send + Sync,
+{
+    async fn get_mapping(&self, pseudo_key: &str) -> Result<Option<String>, Error> {
+        let mut key: HashMap<String, AttributeValue> = HashMap::new();
+
+        key.insert(
+            "pseudo_key".to_owned(),
+            AttributeValue {
+                s: Some(pseudo_key.to_owned()),
+                ..Default::default()
+            },
+        );
+
+        let query = GetItemInput {
+            consistent_read: Some(true),
+            table_name: grapl_config::static_mapping_table_name(),
+            key,
+            ..Default::default()
+        };
+
+        let item = wait_on!(self.get_item(query))?.item;
+
+        match item {
+            Some(item) => {
+                let mapping: ResolvedMapping = serde_dynamodb::from_hashmap(item)?;
+                Ok(Some(mapping.mapping))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn put_mapping(&self, pseudo_key: String, maps_to: String) -> Result<bool, Error> {
+        let mapping = DirectMapping {
+            pseudo_key,
+            mapping: maps_to,
+        };
+
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
+            table_name: grapl_config::static_mapping_table_name(),
+            condition_expression: Some("attribute_not_exists(pseudo_key)".into()),
+            ..Default::default()
+        };
+
+        match wait_on!(self.put_item(put_req)) {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
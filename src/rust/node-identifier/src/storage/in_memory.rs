@@ -0,0 +1,354 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use failure::{bail, Error};
+
+use crate::assetdb::{AssetMapping, AssetSkew};
+use crate::sessions::{Session, UnidSession};
+use crate::storage::{
+    AssetIdStorage, MappingStorage, ScanCursor, ScanPage, SessionStorage, VersionConflict,
+};
+
+/// Sessions ordered by (pseudo_key, create_time), mirroring the DynamoDB hash and range keys
+type Timeline = BTreeMap<(String, u64), Session>;
+
+#[derive(Debug, Default)]
+struct Tables {
+    sessions: HashMap<String, Timeline>,
+    asset_mappings: BTreeMap<(String, u64), AssetMapping>,
+    asset_skews: HashMap<String, AssetSkew>,
+    mappings: HashMap<String, String>,
+}
+
+/// A process local storage backend, primarily for tests. Clones share the same tables.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStorage {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl InMemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A page of `rows`, which are keyed the same way as DynamoDB's hash and range keys
+fn scan<T, U>(
+    rows: &BTreeMap<(String, u64), T>,
+    start: Option<&ScanCursor>,
+    limit: usize,
+    item: impl Fn(&str, &T) -> U,
+) -> ScanPage<U> {
+    let start = match start {
+        Some(start) => Bound::Excluded((start.pseudo_key.clone(), start.sort_key)),
+        None => Bound::Unbounded,
+    };
+
+    let page: Vec<_> = rows.range((start, Bound::Unbounded)).take(limit).collect();
+    let next = match page.last() {
+        Some(((pseudo_key, sort_key), _)) if page.len() == limit => Some(ScanCursor {
+            pseudo_key: pseudo_key.clone(),
+            sort_key: *sort_key,
+        }),
+        _ => None,
+    };
+
+    ScanPage {
+        items: page
+            .into_iter()
+            .map(|((pseudo_key, _), row)| item(pseudo_key, row))
+            .collect(),
+        next,
+    }
+}
+
+/// Looks up the stored copy of `session`, failing if its version has since changed
+fn versioned_session<'a>(
+    tables: &'a mut Tables,
+    table_name: &str,
+    session: &Session,
+) -> Result<&'a mut Session, Error> {
+    let stored = tables
+        .sessions
+        .get_mut(table_name)
+        .and_then(|timeline| timeline.get_mut(&(session.pseudo_key.clone(), session.create_time)));
+
+    match stored {
+        Some(stored) if stored.version == session.version => Ok(stored),
+        Some(_) => Err(VersionConflict::new(session).into()),
+        None => bail!("Session does not exist: {}", session.session_id),
+    }
+}
+
+#[async_trait]
+impl SessionStorage for InMemoryStorage {
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        let session = tables.sessions.get(table_name).and_then(|timeline| {
+            timeline
+                .range(
+                    (unid.pseudo_key.clone(), unid.timestamp)..=(unid.pseudo_key.clone(), u64::MAX),
+                )
+                .next()
+                .map(|(_, session)| session.clone())
+        });
+
+        Ok(session)
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        let session = tables.sessions.get(table_name).and_then(|timeline| {
+            timeline
+                .range((unid.pseudo_key.clone(), 0)..=(unid.pseudo_key.clone(), unid.timestamp))
+                .next_back()
+                .map(|(_, session)| session.clone())
+        });
+
+        Ok(session)
+    }
+
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+
+        versioned_session(&mut tables, table_name, session)?;
+
+        let mut new_session = session.to_owned();
+        new_session.create_time = new_time;
+        new_session.is_create_canon = is_canon;
+        new_session.version += 1;
+
+        let timeline = tables.sessions.entry(table_name.to_owned()).or_default();
+        timeline.remove(&(session.pseudo_key.clone(), session.create_time));
+        timeline.insert((new_session.pseudo_key.clone(), new_time), new_session);
+
+        Ok(())
+    }
+
+    async fn make_create_time_canonical(
+        &self,
+        table_name: &str,
+        session: &Session,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+        let stored = versioned_session(&mut tables, table_name, session)?;
+
+        stored.is_create_canon = true;
+        stored.version += 1;
+
+        Ok(())
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+        let stored = versioned_session(&mut tables, table_name, session)?;
+
+        stored.end_time = new_time;
+        stored.is_end_canon = is_canon;
+        stored.version += 1;
+
+        Ok(())
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+
+        tables
+            .sessions
+            .entry(table_name.to_owned())
+            .or_default()
+            .insert(
+                (session.pseudo_key.clone(), session.create_time),
+                session.clone(),
+            );
+
+        Ok(())
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+
+        if let Some(timeline) = tables.sessions.get_mut(table_name) {
+            timeline.remove(&(session.pseudo_key.clone(), session.create_time));
+        }
+
+        Ok(())
+    }
+
+    async fn scan_sessions(
+        &self,
+        table_name: &str,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<Session>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        Ok(match tables.sessions.get(table_name) {
+            Some(timeline) => scan(timeline, start, limit, |_, session| session.clone()),
+            None => ScanPage {
+                items: vec![],
+                next: None,
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl AssetIdStorage for InMemoryStorage {
+    async fn find_first_asset_mapping_after(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        Ok(tables
+            .asset_mappings
+            .range((pseudo_key.to_owned(), ts)..=(pseudo_key.to_owned(), u64::MAX))
+            .next()
+            .map(|(_, mapping)| mapping.clone()))
+    }
+
+    async fn find_last_asset_mapping_before(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        Ok(tables
+            .asset_mappings
+            .range((pseudo_key.to_owned(), 0)..=(pseudo_key.to_owned(), ts))
+            .next_back()
+            .map(|(_, mapping)| mapping.clone()))
+    }
+
+    async fn create_asset_mapping(
+        &self,
+        pseudo_key: String,
+        asset_id: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+        let mapping = AssetMapping {
+            asset_id,
+            c_timestamp,
+            end_timestamp: None,
+        };
+        tables
+            .asset_mappings
+            .insert((pseudo_key, c_timestamp), mapping);
+
+        Ok(())
+    }
+
+    async fn end_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+        match tables.asset_mappings.get_mut(&(pseudo_key, c_timestamp)) {
+            Some(mapping) => mapping.end_timestamp = Some(end_timestamp),
+            None => bail!("No asset mapping created at {}", c_timestamp),
+        }
+
+        Ok(())
+    }
+
+    async fn get_asset_skew(&self, asset_id: &str) -> Result<Option<AssetSkew>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        Ok(tables.asset_skews.get(asset_id).copied())
+    }
+
+    async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+        match tables.asset_skews.get(&asset_id) {
+            Some(stored) if !skew.supersedes(stored, ttl_ms) => (),
+            _ => {
+                tables.asset_skews.insert(asset_id, skew);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.asset_mappings.remove(&(pseudo_key, c_timestamp));
+
+        Ok(())
+    }
+
+    async fn scan_asset_mappings(
+        &self,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<(String, AssetMapping)>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        Ok(scan(
+            &tables.asset_mappings,
+            start,
+            limit,
+            |pseudo_key, mapping| (pseudo_key.to_owned(), mapping.clone()),
+        ))
+    }
+}
+
+#[async_trait]
+impl MappingStorage for InMemoryStorage {
+    async fn get_mapping(&self, pseudo_key: &str) -> Result<Option<String>, Error> {
+        let tables = self.tables.lock().unwrap();
+
+        Ok(tables.mappings.get(pseudo_key).cloned())
+    }
+
+    async fn put_mapping(&self, pseudo_key: String, maps_to: String) -> Result<bool, Error> {
+        let mut tables = self.tables.lock().unwrap();
+
+        match tables.mappings.entry(pseudo_key) {
+            Entry::Vacant(entry) => {
+                entry.insert(maps_to);
+                Ok(true)
+            }
+            Entry::Occupied(_) => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Storage for the state node identification depends on: process/file/connection session
+//! timelines, hostname to asset id mappings, and static (direct) mappings.
+//!
+//! `SessionDb`, `AssetIdDb` and `DynamicMappingDb` implement the identification logic on top
+//! of these traits. Any `DynamoDb` client is a storage backend, which is what runs in AWS;
+//! `InMemoryStorage` and `SqliteStorage` (behind the `sqlite` feature) allow the same logic
+//! to be unit tested, or run without DynamoDB at all. `CountingStorage` wraps any of them to count the calls made, and
+//! `CachingStorage` to answer repeated lookups from memory.
+
+use std::fmt;
+
+use async_trait::async_trait;
+use failure::Error;
+
+use crate::assetdb::{AssetMapping, AssetSkew};
+use crate::sessions::{Session, UnidSession};
+
+mod caching;
+mod counting;
+mod dynamodb;
+mod in_memory;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use caching::{CacheOptions, CachingStorage, LookupCache};
+pub use counting::CountingStorage;
+pub use in_memory::InMemoryStorage;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+/// Returned by the conditional session updates when the stored session's version no longer
+/// matches the one that was read, ie: it was modified concurrently
+#[derive(Debug)]
+pub struct VersionConflict {
+    pub session_id: String,
+    pub version: u64,
+}
+
+impl VersionConflict {
+    pub fn new(session: &Session) -> Self {
+        Self {
+            session_id: session.session_id.clone(),
+            version: session.version,
+        }
+    }
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Session {} was modified since version {}",
+            self.session_id, self.version
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// Where a table scan resumes: the hash and range keys of the last row it read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanCursor {
+    pub pseudo_key: String,
+    pub sort_key: u64,
+}
+
+/// One page of a table scan, along with where the next page starts, unless the scan is done
+#[derive(Debug, Clone)]
+pub struct ScanPage<T> {
+    pub items: Vec<T>,
+    pub next: Option<ScanCursor>,
+}
+
+/// Session timelines, keyed by `table_name`, then `pseudo_key`, then `create_time`
+#[async_trait]
+pub trait SessionStorage: Clone + Send + Sync {
+    /// The session for `unid.pseudo_key` with the smallest `create_time >= unid.timestamp`
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error>;
+
+    /// The session for `unid.pseudo_key` with the largest `create_time <= unid.timestamp`
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error>;
+
+    /// Atomically replaces `session` with a copy that has the new create time, and an
+    /// incremented version. Fails with `VersionConflict` if the stored session's version no
+    /// longer matches.
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error>;
+
+    /// Fails with `VersionConflict` if the stored session's version no longer matches
+    /// `session.version`
+    async fn make_create_time_canonical(
+        &self,
+        table_name: &str,
+        session: &Session,
+    ) -> Result<(), Error>;
+
+    /// Fails with `VersionConflict` if the stored session's version no longer matches
+    /// `session.version`
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error>;
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error>;
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error>;
+
+    /// Up to `limit` sessions, following `start`. A pseudo key's sessions are scanned
+    /// together, in `create_time` order.
+    async fn scan_sessions(
+        &self,
+        table_name: &str,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<Session>, Error>;
+}
+
+/// Time ranged mappings from a host pseudo key (ie: "hostname" + hostname) to an asset id
+#[async_trait]
+pub trait AssetIdStorage: Clone + Send + Sync {
+    /// The mapping with the smallest `c_timestamp >= ts`
+    async fn find_first_asset_mapping_after(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error>;
+
+    /// The mapping with the largest `c_timestamp <= ts`
+    async fn find_last_asset_mapping_before(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error>;
+
+    /// Creates an open ended mapping, replacing any mapping with the same `c_timestamp`
+    async fn create_asset_mapping(
+        &self,
+        pseudo_key: String,
+        asset_id: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error>;
+
+    /// Sets the end time of the mapping created at `c_timestamp`, failing if there is none
+    async fn end_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(), Error>;
+
+    /// The clock skew most recently learned for `asset_id`, whether or not it has expired
+    async fn get_asset_skew(&self, asset_id: &str) -> Result<Option<AssetSkew>, Error>;
+
+    /// Stores `skew` for `asset_id`, unless a wider skew was observed within `ttl_ms` before
+    /// it. See `AssetSkew::supersedes`.
+    async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error>;
+
+    async fn delete_asset_mapping(&self, pseudo_key: String, c_timestamp: u64)
+        -> Result<(), Error>;
+
+    /// Up to `limit` mappings along with their pseudo keys, following `start`. A pseudo key's
+    /// mappings are scanned together, in `c_timestamp` order.
+    async fn scan_asset_mappings(
+        &self,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<(String, AssetMapping)>, Error>;
+}
+
+/// Direct mappings from a pseudo key to a node key
+#[async_trait]
+pub trait MappingStorage: Clone + Send + Sync {
+    async fn get_mapping(&self, pseudo_key: &str) -> Result<Option<String>, Error>;
+
+    /// Maps `pseudo_key` to `maps_to`, unless it's already mapped, in which case the existing
+    /// mapping is kept. Returns whether the mapping was written.
+    async fn put_mapping(&self, pseudo_key: String, maps_to: String) -> Result<bool, Error>;
+}
+
+/// Everything the node identifier needs from a single backend
+pub trait Storage: SessionStorage + AssetIdStorage + MappingStorage + 'static {}
+
+impl<T> Storage for T where T: SessionStorage + AssetIdStorage + MappingStorage + 'static {}
@@ -0,0 +1,565 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use failure::{bail, Error};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::assetdb::{AssetMapping, AssetSkew};
+use crate::sessions::{Session, UnidSession};
+use crate::storage::{
+    AssetIdStorage, MappingStorage, ScanCursor, ScanPage, SessionStorage, VersionConflict,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        table_name TEXT NOT NULL,
+        pseudo_key TEXT NOT NULL,
+        create_time INTEGER NOT NULL,
+        session_id TEXT NOT NULL,
+        end_time INTEGER NOT NULL,
+        is_create_canon INTEGER NOT NULL,
+        is_end_canon INTEGER NOT NULL,
+        version INTEGER NOT NULL,
+        PRIMARY KEY (table_name, pseudo_key, create_time)
+    );
+
+    CREATE TABLE IF NOT EXISTS asset_id_mappings (
+        pseudo_key TEXT NOT NULL,
+        c_timestamp INTEGER NOT NULL,
+        asset_id TEXT NOT NULL,
+        end_timestamp INTEGER,
+        PRIMARY KEY (pseudo_key, c_timestamp)
+    );
+
+    CREATE TABLE IF NOT EXISTS asset_skews (
+        asset_id TEXT NOT NULL PRIMARY KEY,
+        skew_ms INTEGER NOT NULL,
+        observed_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS static_mappings (
+        pseudo_key TEXT NOT NULL PRIMARY KEY,
+        mapping TEXT NOT NULL
+    );
+";
+
+const SESSION_COLUMNS: &str =
+    "session_id, pseudo_key, create_time, end_time, is_create_canon, is_end_canon, version";
+
+const ASSET_MAPPING_COLUMNS: &str = "asset_id, c_timestamp, end_timestamp";
+
+/// A single file storage backend, for self hosted deployments that don't run DynamoDB.
+///
+/// SQLite serializes writers anyways, so every clone shares one connection. Queries block, so
+/// they run on the blocking thread pool rather than on the calling task.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, Error> {
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Runs `f` with the connection on the blocking thread pool
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Connection) -> Result<T, Error> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap())).await?
+    }
+}
+
+fn session_from_row(row: &Row<'_>) -> rusqlite::Result<Session> {
+    Ok(Session {
+        session_id: row.get(0)?,
+        pseudo_key: row.get(1)?,
+        create_time: row.get::<_, i64>(2)? as u64,
+        end_time: row.get::<_, i64>(3)? as u64,
+        is_create_canon: row.get(4)?,
+        is_end_canon: row.get(5)?,
+        version: row.get::<_, i64>(6)? as u64,
+    })
+}
+
+fn asset_mapping_from_row(row: &Row<'_>) -> rusqlite::Result<AssetMapping> {
+    Ok(AssetMapping {
+        asset_id: row.get(0)?,
+        c_timestamp: row.get::<_, i64>(1)? as u64,
+        end_timestamp: row.get::<_, Option<i64>>(2)?.map(|end| end as u64),
+    })
+}
+
+/// The keys a scan starts after, ordered before every row when the scan is just starting
+fn scan_start(start: Option<&ScanCursor>) -> (String, i64) {
+    match start {
+        Some(start) => (start.pseudo_key.clone(), start.sort_key as i64),
+        None => (String::new(), -1),
+    }
+}
+
+/// Where the scan continues after a full page, keyed by `last`
+fn scan_next<T>(items: &[T], limit: usize, last: impl Fn(&T) -> ScanCursor) -> Option<ScanCursor> {
+    match items.last() {
+        Some(item) if items.len() == limit => Some(last(item)),
+        _ => None,
+    }
+}
+
+fn insert_session(conn: &Connection, table_name: &str, session: &Session) -> Result<(), Error> {
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO sessions (table_name, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            SESSION_COLUMNS
+        ),
+        params![
+            table_name,
+            session.session_id,
+            session.pseudo_key,
+            session.create_time as i64,
+            session.end_time as i64,
+            session.is_create_canon,
+            session.is_end_canon,
+            session.version as i64,
+        ],
+    )?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl SessionStorage for SqliteStorage {
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        let table_name = table_name.to_owned();
+        let unid = unid.clone();
+        self.with_conn(move |conn| {
+            let session = conn
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM sessions
+                         WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time >= ?3
+                         ORDER BY create_time ASC LIMIT 1",
+                        SESSION_COLUMNS
+                    ),
+                    params![table_name, unid.pseudo_key, unid.timestamp as i64],
+                    session_from_row,
+                )
+                .optional()?;
+
+            Ok(session)
+        })
+        .await
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        unid: &UnidSession,
+    ) -> Result<Option<Session>, Error> {
+        let table_name = table_name.to_owned();
+        let unid = unid.clone();
+        self.with_conn(move |conn| {
+            let session = conn
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM sessions
+                         WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time <= ?3
+                         ORDER BY create_time DESC LIMIT 1",
+                        SESSION_COLUMNS
+                    ),
+                    params![table_name, unid.pseudo_key, unid.timestamp as i64],
+                    session_from_row,
+                )
+                .optional()?;
+
+            Ok(session)
+        })
+        .await
+    }
+
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let table_name = table_name.to_owned();
+        let session = session.clone();
+        let mut new_session = session.clone();
+        new_session.create_time = new_time;
+        new_session.is_create_canon = is_canon;
+        new_session.version += 1;
+
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            let deleted = tx.execute(
+                "DELETE FROM sessions
+                 WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3 AND version = ?4",
+                params![
+                    table_name,
+                    session.pseudo_key,
+                    session.create_time as i64,
+                    session.version as i64
+                ],
+            )?;
+            if deleted == 0 {
+                return Err(VersionConflict::new(&session).into());
+            }
+            insert_session(&tx, &table_name, &new_session)?;
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn make_create_time_canonical(
+        &self,
+        table_name: &str,
+        session: &Session,
+    ) -> Result<(), Error> {
+        let table_name = table_name.to_owned();
+        let session = session.clone();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE sessions SET is_create_canon = 1, version = version + 1
+                 WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3 AND version = ?4",
+                params![
+                    table_name,
+                    session.pseudo_key,
+                    session.create_time as i64,
+                    session.version as i64
+                ],
+            )?;
+
+            if updated == 0 {
+                return Err(VersionConflict::new(&session).into());
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let table_name = table_name.to_owned();
+        let session = session.clone();
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE sessions SET end_time = ?1, is_end_canon = ?2, version = version + 1
+                 WHERE table_name = ?3 AND pseudo_key = ?4 AND create_time = ?5 AND version = ?6",
+                params![
+                    new_time as i64,
+                    is_canon,
+                    table_name,
+                    session.pseudo_key,
+                    session.create_time as i64,
+                    session.version as i64
+                ],
+            )?;
+
+            if updated == 0 {
+                return Err(VersionConflict::new(&session).into());
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let table_name = table_name.to_owned();
+        let session = session.clone();
+        self.with_conn(move |conn| insert_session(conn, &table_name, &session))
+            .await
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let table_name = table_name.to_owned();
+        let session = session.clone();
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM sessions WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3",
+                params![table_name, session.pseudo_key, session.create_time as i64],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn scan_sessions(
+        &self,
+        table_name: &str,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<Session>, Error> {
+        let table_name = table_name.to_owned();
+        let start = start.cloned();
+        self.with_conn(move |conn| {
+            let (pseudo_key, create_time) = scan_start(start.as_ref());
+
+            let mut statement = conn.prepare(&format!(
+                "SELECT {} FROM sessions
+                 WHERE table_name = ?1
+                   AND (pseudo_key > ?2 OR (pseudo_key = ?2 AND create_time > ?3))
+                 ORDER BY pseudo_key ASC, create_time ASC LIMIT ?4",
+                SESSION_COLUMNS
+            ))?;
+            let items = statement
+                .query_map(
+                    params![table_name, pseudo_key, create_time, limit as i64],
+                    session_from_row,
+                )?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let next = scan_next(&items, limit, |session| ScanCursor {
+                pseudo_key: session.pseudo_key.clone(),
+                sort_key: session.create_time,
+            });
+            Ok(ScanPage { items, next })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl AssetIdStorage for SqliteStorage {
+    async fn find_first_asset_mapping_after(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let pseudo_key = pseudo_key.to_owned();
+        self.with_conn(move |conn| {
+            let mapping = conn
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM asset_id_mappings
+                         WHERE pseudo_key = ?1 AND c_timestamp >= ?2
+                         ORDER BY c_timestamp ASC LIMIT 1",
+                        ASSET_MAPPING_COLUMNS
+                    ),
+                    params![pseudo_key, ts as i64],
+                    asset_mapping_from_row,
+                )
+                .optional()?;
+
+            Ok(mapping)
+        })
+        .await
+    }
+
+    async fn find_last_asset_mapping_before(
+        &self,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<AssetMapping>, Error> {
+        let pseudo_key = pseudo_key.to_owned();
+        self.with_conn(move |conn| {
+            let mapping = conn
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM asset_id_mappings
+                         WHERE pseudo_key = ?1 AND c_timestamp <= ?2
+                         ORDER BY c_timestamp DESC LIMIT 1",
+                        ASSET_MAPPING_COLUMNS
+                    ),
+                    params![pseudo_key, ts as i64],
+                    asset_mapping_from_row,
+                )
+                .optional()?;
+
+            Ok(mapping)
+        })
+        .await
+    }
+
+    async fn create_asset_mapping(
+        &self,
+        pseudo_key: String,
+        asset_id: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO asset_id_mappings (pseudo_key, c_timestamp, asset_id)
+                 VALUES (?1, ?2, ?3)",
+                params![pseudo_key, c_timestamp as i64, asset_id],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn end_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+        end_timestamp: u64,
+    ) -> Result<(), Error> {
+        self.with_conn(move |conn| {
+            let updated = conn.execute(
+                "UPDATE asset_id_mappings SET end_timestamp = ?3
+                 WHERE pseudo_key = ?1 AND c_timestamp = ?2",
+                params![pseudo_key, c_timestamp as i64, end_timestamp as i64],
+            )?;
+
+            if updated == 0 {
+                bail!("No asset mapping created at {}", c_timestamp);
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_asset_skew(&self, asset_id: &str) -> Result<Option<AssetSkew>, Error> {
+        let asset_id = asset_id.to_owned();
+        self.with_conn(move |conn| {
+            let skew: Option<(i64, i64)> = conn
+                .query_row(
+                    "SELECT skew_ms, observed_at FROM asset_skews WHERE asset_id = ?1",
+                    params![asset_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+
+            Ok(skew.map(|(skew_ms, observed_at)| AssetSkew {
+                skew_ms: skew_ms as u64,
+                observed_at: observed_at as u64,
+            }))
+        })
+        .await
+    }
+
+    async fn record_asset_skew(
+        &self,
+        asset_id: String,
+        skew: AssetSkew,
+        ttl_ms: u64,
+    ) -> Result<(), Error> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT INTO asset_skews (asset_id, skew_ms, observed_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (asset_id) DO UPDATE
+                 SET skew_ms = excluded.skew_ms, observed_at = excluded.observed_at
+                 WHERE excluded.skew_ms >= skew_ms OR observed_at < ?4",
+                params![
+                    asset_id,
+                    skew.skew_ms as i64,
+                    skew.observed_at as i64,
+                    skew.observed_at.saturating_sub(ttl_ms) as i64
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_asset_mapping(
+        &self,
+        pseudo_key: String,
+        c_timestamp: u64,
+    ) -> Result<(), Error> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "DELETE FROM asset_id_mappings WHERE pseudo_key = ?1 AND c_timestamp = ?2",
+                params![pseudo_key, c_timestamp as i64],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn scan_asset_mappings(
+        &self,
+        start: Option<&ScanCursor>,
+        limit: usize,
+    ) -> Result<ScanPage<(String, AssetMapping)>, Error> {
+        let start = start.cloned();
+        self.with_conn(move |conn| {
+            let (pseudo_key, c_timestamp) = scan_start(start.as_ref());
+
+            let mut statement = conn.prepare(&format!(
+                "SELECT {}, pseudo_key FROM asset_id_mappings
+                 WHERE pseudo_key > ?1 OR (pseudo_key = ?1 AND c_timestamp > ?2)
+                 ORDER BY pseudo_key ASC, c_timestamp ASC LIMIT ?3",
+                ASSET_MAPPING_COLUMNS
+            ))?;
+            let items = statement
+                .query_map(params![pseudo_key, c_timestamp, limit as i64], |row| {
+                    Ok((row.get::<_, String>(3)?, asset_mapping_from_row(row)?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            let next = scan_next(&items, limit, |(pseudo_key, mapping)| ScanCursor {
+                pseudo_key: pseudo_key.clone(),
+                sort_key: mapping.c_timestamp,
+            });
+            Ok(ScanPage { items, next })
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl MappingStorage for SqliteStorage {
+    async fn get_mapping(&self, pseudo_key: &str) -> Result<Option<String>, Error> {
+        let pseudo_key = pseudo_key.to_owned();
+        self.with_conn(move |conn| {
+            let mapping = conn
+                .query_row(
+                    "SELECT mapping FROM static_mappings WHERE pseudo_key = ?1",
+                    params![pseudo_key],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            Ok(mapping)
+        })
+        .await
+    }
+
+    async fn put_mapping(&self, pseudo_key: String, maps_to: String) -> Result<bool, Error> {
+        self.with_conn(move |conn| {
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO static_mappings (pseudo_key, mapping) VALUES (?1, ?2)",
+                params![pseudo_key, maps_to],
+            )?;
+
+            Ok(inserted == 1)
+        })
+        .await
+    }
+}
@@ -0,0 +1,214 @@
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+use node_identifier::assetdb::{AssetIdDb, AssetIdentifier};
+use node_identifier::dynamic_sessiondb::{DynamicMappingDb, DynamicNodeIdentifier};
+use node_identifier::sessiondb::SessionDb;
+use node_identifier::storage::InMemoryStorage;
+use tokio::runtime::Runtime;
+
+fn dynamic_identifier(storage: InMemoryStorage) -> DynamicNodeIdentifier<InMemoryStorage> {
+    DynamicNodeIdentifier::new(
+        AssetIdentifier::new(AssetIdDb::new(storage.clone())),
+        SessionDb::new(storage.clone(), "dynamic_session_table"),
+        DynamicMappingDb::new(storage),
+        false,
+    )
+}
+
+fn process(pid: u64) -> Node {
+    ProcessBuilder::default()
+        .asset_id("asset".to_owned())
+        .state(ProcessState::Existing)
+        .process_id(pid)
+        .last_seen_timestamp(1_544_301_484_600u64)
+        .build()
+        .unwrap()
+        .into()
+}
+
+fn static_strategy(property: &str) -> IdStrategy {
+    Static {
+        primary_key_properties: vec![property.to_owned()],
+        primary_key_requires_asset_id: false,
+    }
+    .into()
+}
+
+fn created_strategy(property: &str, created_time: u64) -> IdStrategy {
+    Session {
+        primary_key_properties: vec![property.to_owned()],
+        primary_key_requires_asset_id: false,
+        created_time,
+        last_seen_time: 0,
+        terminated_time: 0,
+    }
+    .into()
+}
+
+fn dynamic_node(
+    node_key: &str,
+    id_strategy: Vec<IdStrategy>,
+    properties: &[(&str, &str)],
+) -> DynamicNode {
+    let mut node = DynamicNode {
+        node_key: node_key.to_owned(),
+        node_type: "Service".to_owned(),
+        seen_at: 1_544_301_484_600,
+        id_strategy,
+        ..Default::default()
+    };
+    for (name, value) in properties {
+        node.set_property(*name, (*value).to_owned());
+    }
+    node
+}
+
+/// A node identified by its `name`, so that any two nodes named alike are identified as one
+fn service(node_key: &str, name: Option<&str>) -> Node {
+    let properties: Vec<_> = name.into_iter().map(|name| ("name", name)).collect();
+    dynamic_node(node_key, vec![static_strategy("name")], &properties).into()
+}
+
+fn edges(graph: &Graph) -> Vec<(&str, &str, &str)> {
+    let mut edges: Vec<_> = graph
+        .edges
+        .values()
+        .flat_map(|edges| edges.edges.iter())
+        .map(|edge| {
+            (
+                edge.from.as_str(),
+                edge.edge_name.as_str(),
+                edge.to.as_str(),
+            )
+        })
+        .collect();
+    edges.sort();
+    edges
+}
+
+#[test]
+fn dynamic_nodes_are_merged_and_their_edges_remapped() {
+    let mut runtime = Runtime::new().unwrap();
+    let identifier = dynamic_identifier(InMemoryStorage::new());
+
+    let process = process(1);
+    let mut unid_graph = Graph::new(0)
+        .with_node(process.clone())
+        .with_node(service("unid-a", Some("sshd")))
+        .with_node(service("unid-b", Some("sshd")));
+    unid_graph.add_edge("runs", process.clone_node_key(), "unid-a");
+    unid_graph.add_edge("run_by", "unid-b", process.clone_node_key());
+
+    let attribution = runtime.block_on(identifier.attribute_dynamic_nodes(&unid_graph));
+
+    assert!(attribution.dead_nodes.is_empty());
+    assert_eq!(attribution.unid_id_map.len(), 2);
+    let node_key = attribution.unid_id_map["unid-a"].as_str();
+    assert_eq!(attribution.unid_id_map["unid-b"], node_key);
+
+    // The process keeps its node key, and both dynamic nodes collapse into one
+    let graph = &attribution.graph;
+    assert_eq!(graph.nodes.len(), 2);
+    assert!(graph.nodes.contains_key(process.get_node_key()));
+    assert_eq!(graph.nodes[node_key].get_node_key(), node_key);
+    let mut expected = vec![
+        (node_key, "run_by", process.get_node_key()),
+        (process.get_node_key(), "runs", node_key),
+    ];
+    expected.sort();
+    assert_eq!(edges(graph), expected);
+}
+
+#[test]
+fn unidentifiable_dynamic_nodes_are_removed_with_their_edges() {
+    let mut runtime = Runtime::new().unwrap();
+    let identifier = dynamic_identifier(InMemoryStorage::new());
+
+    let process = process(1);
+    let mut unid_graph = Graph::new(0)
+        .with_node(process.clone())
+        .with_node(service("unid-a", Some("sshd")))
+        .with_node(service("unid-nameless", None));
+    unid_graph.add_edge("runs", process.clone_node_key(), "unid-a");
+    unid_graph.add_edge("runs", process.clone_node_key(), "unid-nameless");
+    unid_graph.add_edge("connects", "unid-nameless", "unid-a");
+
+    let attribution = runtime.block_on(identifier.attribute_dynamic_nodes(&unid_graph));
+
+    assert_eq!(attribution.dead_nodes.len(), 1);
+    assert!(attribution.dead_nodes.contains_key("unid-nameless"));
+    assert!(!attribution.unid_id_map.contains_key("unid-nameless"));
+
+    let node_key = attribution.unid_id_map["unid-a"].as_str();
+    let graph = &attribution.graph;
+    assert_eq!(graph.nodes.len(), 2);
+    assert!(!graph.nodes.contains_key("unid-nameless"));
+    assert_eq!(
+        edges(graph),
+        vec![(process.get_node_key(), "runs", node_key)]
+    );
+}
+
+#[test]
+fn strategies_fall_back_only_when_properties_are_missing() {
+    let mut runtime = Runtime::new().unwrap();
+    let identifier = dynamic_identifier(InMemoryStorage::new());
+    let mut identify = |properties: &[(&str, &str)]| {
+        let node = dynamic_node(
+            "unid",
+            vec![static_strategy("path"), static_strategy("name")],
+            properties,
+        );
+        runtime
+            .block_on(identifier.attribute_dynamic_node(&node))
+            .expect("Failed to attribute node")
+            .node_key
+    };
+
+    let by_name = identify(&[("name", "sshd")]);
+    let by_path = identify(&[("name", "sshd"), ("path", "/usr/sbin/sshd")]);
+    assert_ne!(by_name, by_path);
+    assert_eq!(identify(&[("name", "sshd")]), by_name);
+    assert_eq!(identify(&[("path", "/usr/sbin/sshd")]), by_path);
+
+    let nameless = dynamic_node(
+        "unid",
+        vec![static_strategy("path"), static_strategy("name")],
+        &[],
+    );
+    assert!(runtime
+        .block_on(identifier.attribute_dynamic_node(&nameless))
+        .is_err());
+}
+
+#[test]
+fn combined_strategies_partition_sessions_by_static_key() {
+    let mut runtime = Runtime::new().unwrap();
+    let identifier = dynamic_identifier(InMemoryStorage::new());
+    let mut identify = |node_key: &str, name: &str| {
+        let mut node = dynamic_node(
+            node_key,
+            vec![static_strategy("name"), created_strategy("pid", 1_000)],
+            &[("name", name), ("pid", "100")],
+        );
+        node.combine_id_strategies = true;
+        runtime
+            .block_on(identifier.attribute_dynamic_node(&node))
+            .expect("Failed to attribute node")
+            .node_key
+    };
+
+    // Sessions are shared between nodes of the same type, whatever their unidentified keys
+    let sshd = identify("unid-a", "sshd");
+    assert_eq!(identify("unid-b", "sshd"), sshd);
+    // The same pid and creation time, but another service
+    assert_ne!(identify("unid-c", "cron"), sshd);
+
+    // Combining requires exactly one session strategy
+    let mut sessionless = dynamic_node("unid", vec![static_strategy("name")], &[("name", "sshd")]);
+    sessionless.combine_id_strategies = true;
+    assert!(runtime
+        .block_on(identifier.attribute_dynamic_node(&sessionless))
+        .is_err());
+}
@@ -80,6 +80,7 @@ fn canon_create_on_empty_timeline(asset_id: String, pid: u64) {
         pseudo_key: format!("{}{}", asset_id, pid),
         timestamp: 1544301484600,
         is_creation: true,
+        is_termination: false,
     };
 
     let session_id = runtime
@@ -127,6 +128,7 @@ fn canon_create_update_existing_non_canon_create(asset_id: String, pid: u64) {
         pseudo_key: format!("{}{}", asset_id, pid),
         timestamp: 1_544_301_484_500,
         is_creation: true,
+        is_termination: false,
     };
 
     let session_id = runtime
@@ -174,6 +176,7 @@ fn noncanon_create_update_existing_non_canon_create(asset_id: String, pid: u64)
         pseudo_key: format!("{}{}", asset_id, pid),
         timestamp: 1_544_301_484_500,
         is_creation: false,
+        is_termination: false,
     };
 
     let session_id = runtime
@@ -218,6 +221,7 @@ fn noncanon_create_on_empty_timeline_with_default(asset_id: String, pid: u64) {
         pseudo_key: format!("{}{}", asset_id, pid),
         timestamp: 1_544_301_484_500,
         is_creation: false,
+        is_termination: false,
     };
 
     let session_id = runtime
@@ -245,6 +249,7 @@ fn noncanon_create_on_empty_timeline_without_default() {
         pseudo_key: "asset_id_a1234".into(),
         timestamp: 1_544_301_484_500,
         is_creation: false,
+        is_termination: false,
     };
 
     let session_id = runtime.block_on(session_db.handle_unid_session(unid, false));
@@ -299,6 +304,7 @@ fn update_end_time(asset_id: String, pid: u64) {
         pseudo_key: format!("{}{}", asset_id, pid),
         timestamp: 1_544_301_484_800,
         is_creation: false,
+        is_termination: false,
     };
 
     let session_id = runtime
@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use node_identifier::sessiondb::SessionDb;
+use node_identifier::sessions::{Session, UnidSession};
+use node_identifier::storage::InMemoryStorage;
+use quickcheck_macros::quickcheck;
+use tokio::runtime::Runtime;
+
+const PSEUDO_KEY: &str = "asset_id_a1234";
+const BASE_TIME: u64 = 1_544_301_484_000;
+
+fn unid(timestamp: u64, is_creation: bool, is_termination: bool) -> UnidSession {
+    UnidSession {
+        pseudo_key: PSEUDO_KEY.into(),
+        timestamp,
+        is_creation,
+        is_termination,
+    }
+}
+
+// Builds the events of consecutive, non overlapping processes that all share a pseudo key
+// (ie: pid reuse), from (gap, duration, last seen count) triples
+fn process_events(lifetimes: &[(u8, u8, u8)]) -> Vec<UnidSession> {
+    let mut events = vec![];
+    let mut last_end = BASE_TIME;
+
+    for &(gap, duration, last_seen_count) in lifetimes.iter().take(5) {
+        let create_time = last_end + (u64::from(gap) + 1) * 1_000;
+        let end_time = create_time + (u64::from(duration) + 1) * 1_000;
+        let last_seen_count = u64::from(last_seen_count % 3);
+
+        events.push(unid(create_time, true, false));
+        for i in 1..=last_seen_count {
+            let seen_at = create_time + i * (end_time - create_time) / (last_seen_count + 1);
+            events.push(unid(seen_at - (seen_at % 100), false, false));
+        }
+        events.push(unid(end_time, false, true));
+
+        last_end = end_time;
+    }
+
+    events
+}
+
+// A Fisher-Yates shuffle, driven by quickcheck's values rather than an rng
+fn shuffle<T>(items: &mut [T], order: &[usize]) {
+    for i in (1..items.len()).rev() {
+        let j = order.get(i).copied().unwrap_or(i) % (i + 1);
+        items.swap(i, j);
+    }
+}
+
+async fn timeline(session_db: &SessionDb<InMemoryStorage>) -> Vec<Session> {
+    let mut sessions = vec![];
+    let mut after = 0;
+
+    while let Some(session) = session_db
+        .find_first_session_after(&unid(after, false, false))
+        .await
+        .expect("Failed to query sessions")
+    {
+        after = session.create_time + 1;
+        sessions.push(session);
+    }
+
+    sessions
+}
+
+// Given the events of several processes that reuse a pid
+// When the events arrive in any order
+// Then every event is attributed, each creation gets its own session, and sessions only
+//      overlap where both sides of the overlap are canonical
+#[quickcheck]
+fn sessions_never_overlap_non_canonically(lifetimes: Vec<(u8, u8, u8)>, order: Vec<usize>) {
+    let mut runtime = Runtime::new().unwrap();
+    let session_db = SessionDb::new(InMemoryStorage::new(), "process_history_table");
+
+    let mut events = process_events(&lifetimes);
+    shuffle(&mut events, &order);
+
+    let creation_times: HashSet<u64> = events
+        .iter()
+        .filter(|event| event.is_creation)
+        .map(|event| event.timestamp)
+        .collect();
+    let termination_times: HashSet<u64> = events
+        .iter()
+        .filter(|event| event.is_termination)
+        .map(|event| event.timestamp)
+        .collect();
+
+    let mut created_session_ids = HashSet::new();
+    for event in events {
+        let is_creation = event.is_creation;
+        let session_id = runtime
+            .block_on(session_db.handle_unid_session(event, true))
+            .expect("Failed to attribute event");
+
+        if is_creation {
+            assert!(created_session_ids.insert(session_id));
+        }
+    }
+
+    let sessions = runtime.block_on(timeline(&session_db));
+
+    for session in &sessions {
+        assert!(session.create_time <= session.end_time, "{:?}", session);
+
+        if session.is_create_canon {
+            assert!(creation_times.contains(&session.create_time));
+        }
+        if session.is_end_canon {
+            assert!(termination_times.contains(&session.end_time));
+        }
+    }
+
+    for pair in sessions.windows(2) {
+        let (earlier, later) = (&pair[0], &pair[1]);
+
+        if earlier.end_time >= later.create_time {
+            assert!(
+                earlier.is_end_canon && later.is_create_canon,
+                "Non canonical overlap {:?} {:?}",
+                earlier,
+                later
+            );
+        }
+    }
+}
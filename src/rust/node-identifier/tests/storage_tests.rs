@@ -0,0 +1,934 @@
+use grapl_graph_descriptions::file::FileState;
+use grapl_graph_descriptions::graph_description::host::*;
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+use node_identifier::assetdb::{AssetIdDb, AssetIdentifier, AssetSkew, MappingConflict};
+use node_identifier::compaction::{
+    compact_asset_mappings, compact_sessions, CompactionOptions, CompactionStats,
+};
+use node_identifier::dynamic_sessiondb::DynamicMappingDb;
+use node_identifier::sessiondb::{SessionDb, SkewTolerance};
+use node_identifier::sessions::{Session, SessionMatchKind, UnidSession};
+#[cfg(feature = "sqlite")]
+use node_identifier::storage::SqliteStorage;
+use node_identifier::storage::{
+    CacheOptions, CachingStorage, CountingStorage, InMemoryStorage, LookupCache, Storage,
+    VersionConflict,
+};
+use node_identifier::NodeKeyAttributor;
+use tokio::runtime::Runtime;
+
+// Each scenario runs against every backend that doesn't need an external service, and once
+// more through the cache, which must never change an outcome
+macro_rules! on_each_backend {
+    ($($scenario:ident),* $(,)?) => {
+        $(
+            #[test]
+            fn $scenario() {
+                let mut runtime = Runtime::new().unwrap();
+
+                runtime.block_on(super::$scenario(InMemoryStorage::new()));
+                #[cfg(feature = "sqlite")]
+                runtime.block_on(super::$scenario(
+                    SqliteStorage::open_in_memory().expect("Failed to open sqlite"),
+                ));
+                runtime.block_on(super::$scenario(CachingStorage::new(
+                    InMemoryStorage::new(),
+                    LookupCache::new(CacheOptions::default()),
+                )));
+            }
+        )*
+    };
+}
+
+mod backends {
+    use super::*;
+
+    on_each_backend!(
+        canon_create_is_idempotent,
+        canon_create_extends_non_canon_session,
+        noncanon_on_empty_timeline_requires_default,
+        end_time_update_rejects_stale_version,
+        create_time_update_rejects_stale_version,
+        termination_ends_session,
+        hostname_resolves_to_mapped_asset_id,
+        retired_hostname_is_not_attributed_to_its_successor,
+        reclaiming_handed_over_hostname_conflicts,
+        leased_ip_resolves_to_asset_holding_mac,
+        host_ip_attributes_builtin_nodes,
+        static_mapping_round_trips,
+        asset_skew_expires,
+        termination_before_its_creation_is_learned_as_skew,
+        pid_reused_after_termination_is_a_new_session,
+        session_matches_report_how_they_matched,
+        process_guid_keeps_its_node_across_pids,
+        reused_pid_with_new_guid_gets_a_new_node,
+        files_keep_sessions_stored_under_their_raw_path,
+        concurrent_first_sightings_agree_on_a_node_key,
+        compaction_merges_guessed_sessions_and_expires_old_ones,
+        compaction_folds_repeated_asset_mappings,
+    );
+}
+
+fn session_db<S: Storage>(storage: S) -> SessionDb<S> {
+    SessionDb::new(storage, "process_history_table")
+}
+
+fn non_canon_session() -> Session {
+    Session {
+        pseudo_key: "asset_id_a1234".into(),
+        create_time: 1_544_301_484_600,
+        is_create_canon: false,
+        session_id: "SessionId".into(),
+        is_end_canon: false,
+        end_time: 1_544_301_484_700,
+        version: 0,
+    }
+}
+
+async fn canon_create_is_idempotent(storage: impl Storage) {
+    let session_db = session_db(storage);
+
+    let unid = || UnidSession {
+        pseudo_key: "asset_id_a1234".into(),
+        timestamp: 1_544_301_484_600,
+        is_creation: true,
+        is_termination: false,
+    };
+
+    let first = session_db
+        .handle_unid_session(unid(), false)
+        .await
+        .expect("Failed to create session");
+    let second = session_db
+        .handle_unid_session(unid(), false)
+        .await
+        .expect("Failed to find session");
+
+    assert_eq!(first, second);
+}
+
+async fn canon_create_extends_non_canon_session(storage: impl Storage) {
+    let session_db = session_db(storage);
+    session_db
+        .create_session(&non_canon_session())
+        .await
+        .expect("Failed to create session");
+
+    let unid = UnidSession {
+        pseudo_key: "asset_id_a1234".into(),
+        timestamp: 1_544_301_484_500,
+        is_creation: true,
+        is_termination: false,
+    };
+
+    let session_id = session_db
+        .handle_unid_session(unid, false)
+        .await
+        .expect("Failed to handle unid");
+    assert_eq!(session_id, "SessionId");
+
+    let session = session_db
+        .find_first_session_after(&UnidSession {
+            pseudo_key: "asset_id_a1234".into(),
+            timestamp: 0,
+            is_creation: false,
+            is_termination: false,
+        })
+        .await
+        .expect("Failed to query sessions")
+        .expect("Missing session");
+
+    assert_eq!(session.create_time, 1_544_301_484_500);
+    assert!(session.is_create_canon);
+    assert_eq!(session.version, 1);
+}
+
+async fn noncanon_on_empty_timeline_requires_default(storage: impl Storage) {
+    let session_db = session_db(storage);
+
+    let unid = || UnidSession {
+        pseudo_key: "asset_id_a1234".into(),
+        timestamp: 1_544_301_484_500,
+        is_creation: false,
+        is_termination: false,
+    };
+
+    assert!(session_db.handle_unid_session(unid(), false).await.is_err());
+    assert!(session_db.handle_unid_session(unid(), true).await.is_ok());
+}
+
+async fn end_time_update_rejects_stale_version(storage: impl Storage) {
+    let session = non_canon_session();
+    storage
+        .create_session("process_history_table", &session)
+        .await
+        .expect("Failed to create session");
+
+    storage
+        .update_session_end_time("process_history_table", &session, 1_544_301_484_900, true)
+        .await
+        .expect("Failed to update end time");
+
+    // `session` still carries version 0, which has since been superseded
+    let stale = storage
+        .update_session_end_time("process_history_table", &session, 1_544_301_485_000, true)
+        .await;
+
+    let stale = stale.expect_err("Stale update succeeded");
+    assert!(stale.downcast_ref::<VersionConflict>().is_some());
+}
+
+async fn create_time_update_rejects_stale_version(storage: impl Storage) {
+    let session = non_canon_session();
+    storage
+        .create_session("process_history_table", &session)
+        .await
+        .expect("Failed to create session");
+
+    storage
+        .update_session_end_time("process_history_table", &session, 1_544_301_484_900, true)
+        .await
+        .expect("Failed to update end time");
+
+    // Moving the create time of the version 0 copy would drop the new end time
+    let stale = storage
+        .update_session_create_time("process_history_table", &session, 1_544_301_484_500, true)
+        .await;
+    let stale = stale.expect_err("Stale update succeeded");
+    assert!(stale.downcast_ref::<VersionConflict>().is_some());
+
+    let stored = storage
+        .find_last_session_before(
+            "process_history_table",
+            &UnidSession {
+                pseudo_key: session.pseudo_key.clone(),
+                timestamp: 1_544_301_484_900,
+                is_creation: false,
+                is_termination: false,
+            },
+        )
+        .await
+        .expect("Failed to find session")
+        .expect("Session was deleted");
+    assert_eq!(stored.create_time, session.create_time);
+    assert_eq!(stored.end_time, 1_544_301_484_900);
+    assert_eq!(stored.version, 1);
+}
+
+async fn termination_ends_session(storage: impl Storage) {
+    let session_db = session_db(storage);
+
+    let unid = |timestamp, is_creation, is_termination| UnidSession {
+        pseudo_key: "asset_id_a1234".into(),
+        timestamp,
+        is_creation,
+        is_termination,
+    };
+
+    let created = session_db
+        .handle_unid_session(unid(1_544_301_484_600, true, false), false)
+        .await
+        .expect("Failed to create session");
+
+    let terminated = session_db
+        .handle_unid_session(unid(1_544_301_485_600, false, true), false)
+        .await
+        .expect("Failed to terminate session");
+    assert_eq!(created, terminated);
+
+    let session = session_db
+        .find_last_session_before(&unid(1_544_301_485_600, false, false))
+        .await
+        .expect("Failed to query sessions")
+        .expect("Missing session");
+    assert_eq!(session.end_time, 1_544_301_485_600);
+    assert!(session.is_end_canon);
+
+    // Within the terminated session
+    let during = session_db
+        .handle_unid_session(unid(1_544_301_485_000, false, false), false)
+        .await
+        .expect("Failed to find session");
+    assert_eq!(during, created);
+
+    // After the session ended, the pseudo key must belong to a new session
+    let after = session_db
+        .handle_unid_session(unid(1_544_301_489_600, false, false), true)
+        .await
+        .expect("Failed to create session");
+    assert_ne!(after, created);
+
+    let recreated = session_db
+        .handle_unid_session(unid(1_544_301_490_600, true, false), false)
+        .await
+        .expect("Failed to create session");
+    assert_ne!(recreated, created);
+}
+
+async fn hostname_resolves_to_mapped_asset_id(storage: impl Storage) {
+    let asset_id_db = AssetIdDb::new(storage);
+    let hostname = HostId::Hostname("fakehostname".to_owned());
+
+    asset_id_db
+        .create_mapping(&hostname, "asset_id_a".into(), 1500)
+        .await
+        .expect("Mapping creation failed");
+    asset_id_db
+        .create_mapping(&hostname, "asset_id_b".into(), 2500)
+        .await
+        .expect("Mapping creation failed");
+
+    let resolve = |ts| asset_id_db.resolve_asset_id(&hostname, ts);
+
+    assert_eq!(resolve(1000).await.unwrap().as_deref(), Some("asset_id_a"));
+    assert_eq!(resolve(1510).await.unwrap().as_deref(), Some("asset_id_a"));
+    assert_eq!(resolve(2600).await.unwrap().as_deref(), Some("asset_id_b"));
+}
+
+async fn retired_hostname_is_not_attributed_to_its_successor(storage: impl Storage) {
+    let asset_id_db = AssetIdDb::new(storage);
+    let hostname = HostId::Hostname("ip-10-0-0-5".to_owned());
+
+    asset_id_db
+        .create_mapping(&hostname, "asset_id_a".into(), 1000)
+        .await
+        .expect("Mapping creation failed");
+    asset_id_db
+        .retire_mapping(&hostname, "asset_id_a", 2000)
+        .await
+        .expect("Failed to retire mapping");
+    asset_id_db
+        .create_mapping(&hostname, "asset_id_b".into(), 5000)
+        .await
+        .expect("Mapping creation failed");
+
+    let resolve = |ts| asset_id_db.resolve_asset_id(&hostname, ts);
+
+    assert_eq!(resolve(1500).await.unwrap().as_deref(), Some("asset_id_a"));
+    // Between the two machines, nothing held the hostname
+    assert_eq!(resolve(3000).await.unwrap(), None);
+    assert_eq!(resolve(5500).await.unwrap().as_deref(), Some("asset_id_b"));
+
+    // Only the asset holding the hostname can retire it
+    assert!(asset_id_db
+        .retire_mapping(&hostname, "asset_id_a", 6000)
+        .await
+        .is_err());
+}
+
+async fn reclaiming_handed_over_hostname_conflicts(storage: impl Storage) {
+    let asset_id_db = AssetIdDb::new(storage);
+    let hostname = HostId::Hostname("ip-10-0-0-5".to_owned());
+
+    for (asset_id, ts) in &[
+        ("asset_id_a", 1000),
+        ("asset_id_a", 1500),
+        ("asset_id_b", 2000),
+    ] {
+        asset_id_db
+            .create_mapping(&hostname, (*asset_id).into(), *ts)
+            .await
+            .expect("Mapping creation failed");
+    }
+
+    let conflict = asset_id_db
+        .create_mapping(&hostname, "asset_id_a".into(), 2500)
+        .await
+        .expect_err("Conflicting claim succeeded");
+    let conflict = conflict
+        .downcast_ref::<MappingConflict>()
+        .expect("Expected a MappingConflict");
+    assert_eq!(conflict.held_by, "asset_id_b");
+
+    // The conflicting claim was not recorded
+    let resolved = asset_id_db.resolve_asset_id(&hostname, 3000).await.unwrap();
+    assert_eq!(resolved.as_deref(), Some("asset_id_b"));
+
+    // Once the old machine's claim has been retired, it can be handed back
+    asset_id_db
+        .retire_mapping(&hostname, "asset_id_a", 1900)
+        .await
+        .expect("Failed to retire mapping");
+    asset_id_db
+        .create_mapping(&hostname, "asset_id_a".into(), 2500)
+        .await
+        .expect("Mapping creation failed");
+}
+
+async fn leased_ip_resolves_to_asset_holding_mac(storage: impl Storage) {
+    let asset_id_db = AssetIdDb::new(storage);
+    let mac_address = HostId::MacAddress("00:1b:44:11:3a:b7".to_owned());
+    let host_ip = HostId::HostIp("10.0.0.5".to_owned());
+
+    asset_id_db
+        .create_mapping(&mac_address, "asset_id_a".into(), 1000)
+        .await
+        .expect("Mapping creation failed");
+
+    let unknown = asset_id_db.record_lease("10.0.0.9", "00:1b:44:11:3a:b8", 2000);
+    assert_eq!(unknown.await.unwrap(), None);
+
+    let leased = asset_id_db.record_lease("10.0.0.5", "00:1b:44:11:3a:b7", 2000);
+    assert_eq!(leased.await.unwrap().as_deref(), Some("asset_id_a"));
+
+    // The address is handed to another asset later on
+    asset_id_db
+        .create_mapping(&host_ip, "asset_id_b".into(), 3000)
+        .await
+        .expect("Mapping creation failed");
+
+    let resolve = |ts| asset_id_db.resolve_asset_id(&host_ip, ts);
+
+    assert_eq!(resolve(2500).await.unwrap().as_deref(), Some("asset_id_a"));
+    assert_eq!(resolve(3500).await.unwrap().as_deref(), Some("asset_id_b"));
+}
+
+async fn host_ip_attributes_builtin_nodes(storage: impl Storage) {
+    let asset_id_db = AssetIdDb::new(storage.clone());
+    asset_id_db
+        .create_mapping(
+            &HostId::HostIp("10.0.0.5".to_owned()),
+            "asset_id_a".into(),
+            1000,
+        )
+        .await
+        .expect("Mapping creation failed");
+
+    let process: Node = ProcessBuilder::default()
+        .host_ip(Some("10.0.0.5".to_owned()))
+        .state(ProcessState::Created)
+        .process_id(1u64)
+        .created_timestamp(1500u64)
+        .build()
+        .unwrap()
+        .into();
+
+    let asset_identifier = AssetIdentifier::new(asset_id_db);
+    let asset_id = asset_identifier.attribute_asset_id(&process).await.unwrap();
+    assert_eq!(asset_id, "asset_id_a");
+}
+
+async fn static_mapping_round_trips(storage: impl Storage) {
+    let mapping_db = DynamicMappingDb::new(storage);
+
+    assert_eq!(mapping_db.direct_map("pseudo_key").await.unwrap(), None);
+
+    mapping_db
+        .create_mapping("pseudo_key".into(), "node_key".into())
+        .await
+        .expect("Failed to create mapping");
+
+    assert_eq!(
+        mapping_db
+            .direct_map("pseudo_key")
+            .await
+            .unwrap()
+            .as_deref(),
+        Some("node_key")
+    );
+}
+
+async fn asset_skew_expires(storage: impl Storage) {
+    let asset_id_db = AssetIdDb::new(storage);
+    let ttl_ms = 10_000;
+    let skew = |skew_ms, observed_at| AssetSkew {
+        skew_ms,
+        observed_at,
+    };
+    let record = |skew| asset_id_db.record_asset_skew("asset_id_a".into(), skew, ttl_ms);
+
+    assert_eq!(
+        asset_id_db
+            .asset_skew("asset_id_a", 1_000, ttl_ms)
+            .await
+            .unwrap(),
+        0
+    );
+
+    // A narrower estimate doesn't replace a wider one that is still fresh
+    record(skew(300, 1_000))
+        .await
+        .expect("Failed to record skew");
+    record(skew(100, 2_000))
+        .await
+        .expect("Failed to record skew");
+    assert_eq!(
+        asset_id_db
+            .asset_skew("asset_id_a", 2_000, ttl_ms)
+            .await
+            .unwrap(),
+        300
+    );
+    assert_eq!(
+        asset_id_db
+            .asset_skew("asset_id_b", 2_000, ttl_ms)
+            .await
+            .unwrap(),
+        0
+    );
+
+    // Once it hasn't been observed for longer than the ttl, the estimate no longer applies,
+    // and a narrower one takes its place
+    assert_eq!(
+        asset_id_db
+            .asset_skew("asset_id_a", 20_000, ttl_ms)
+            .await
+            .unwrap(),
+        0
+    );
+    record(skew(100, 20_000))
+        .await
+        .expect("Failed to record skew");
+    assert_eq!(
+        asset_id_db
+            .asset_skew("asset_id_a", 20_000, ttl_ms)
+            .await
+            .unwrap(),
+        100
+    );
+}
+
+fn unid(timestamp: u64, is_creation: bool, is_termination: bool) -> UnidSession {
+    UnidSession {
+        pseudo_key: "asset_id_a1234".into(),
+        timestamp,
+        is_creation,
+        is_termination,
+    }
+}
+
+async fn termination_before_its_creation_is_learned_as_skew(storage: impl Storage) {
+    // The asset's clock runs ahead when reporting creations, so the termination is stamped
+    // before the creation of the session it ends
+    let session_db = session_db(storage.clone()).with_skew(SkewTolerance::default(), 0);
+    let created = session_db
+        .handle_unid_session(unid(1_544_301_485_100, true, false), false)
+        .await
+        .expect("Failed to create session");
+    let terminated = session_db
+        .handle_unid_session(unid(1_544_301_484_600, false, true), true)
+        .await
+        .expect("Failed to terminate session");
+
+    // Without any known skew, the termination is defaulted, but the skew is learned
+    assert_ne!(created, terminated);
+    assert_eq!(session_db.observed_skew(), Some(500));
+
+    // Once the skew is known, the same pair of events resolves to one session
+    let session_db =
+        SessionDb::new(storage, "file_history_table").with_skew(SkewTolerance::default(), 500);
+    let created = session_db
+        .handle_unid_session(unid(1_544_301_485_100, true, false), false)
+        .await
+        .expect("Failed to create session");
+    let terminated = session_db
+        .handle_unid_session(unid(1_544_301_484_600, false, true), true)
+        .await
+        .expect("Failed to terminate session");
+    assert_eq!(created, terminated);
+}
+
+async fn pid_reused_after_termination_is_a_new_session(storage: impl Storage) {
+    // Even with skew learned for the asset, a creation after a termination may be a new
+    // process that was given the same pid
+    let session_db = session_db(storage).with_skew(SkewTolerance::default(), 1_000);
+    let terminated = session_db
+        .handle_unid_session(unid(1_544_301_484_600, false, true), true)
+        .await
+        .expect("Failed to terminate session");
+    let created = session_db
+        .handle_unid_session(unid(1_544_301_485_100, true, false), false)
+        .await
+        .expect("Failed to create session");
+
+    assert_ne!(created, terminated);
+    assert_eq!(session_db.observed_skew(), None);
+}
+
+async fn session_matches_report_how_they_matched(storage: impl Storage) {
+    let session_db = session_db(storage);
+
+    let defaulted = session_db
+        .match_unid_session(unid(1_544_301_484_600, false, false), true)
+        .await
+        .expect("Failed to default session");
+    assert_eq!(defaulted.kind, SessionMatchKind::Defaulted);
+    assert_eq!(defaulted.version, 0);
+
+    let created = session_db
+        .match_unid_session(unid(1_544_301_484_500, true, false), false)
+        .await
+        .expect("Failed to create session");
+    assert_eq!(created.kind, SessionMatchKind::MadeCanonical);
+    assert_eq!(created.session_id, defaulted.session_id);
+    assert_eq!(created.version, 1);
+
+    let seen = session_db
+        .match_unid_session(unid(1_544_301_484_550, false, false), false)
+        .await
+        .expect("Failed to find session");
+    assert_eq!(seen.kind, SessionMatchKind::Within);
+    assert_eq!(seen.version, 1);
+
+    let terminated = session_db
+        .match_unid_session(unid(1_544_301_494_600, false, true), false)
+        .await
+        .expect("Failed to terminate session");
+    assert_eq!(terminated.kind, SessionMatchKind::Terminated);
+    assert_eq!(terminated.session_id, defaulted.session_id);
+    assert_eq!(terminated.version, 2);
+}
+
+fn attributor<S: Storage>(storage: S) -> NodeKeyAttributor<S> {
+    // Table names are read from the environment, though the test backends accept any
+    for var in &[
+        "PROCESS_HISTORY_TABLE",
+        "FILE_HISTORY_TABLE",
+        "DYNAMIC_SESSION_TABLE",
+        "STATIC_MAPPING_TABLE",
+    ] {
+        std::env::set_var(var, var.to_lowercase());
+    }
+    NodeKeyAttributor::new(storage, true, SkewTolerance::default())
+}
+
+/// A process reported by its creation when `created`, or else as merely seen
+fn guid_process(pid: u64, process_guid: &str, timestamp: u64, created: bool) -> Node {
+    let mut builder = ProcessBuilder::default();
+    builder
+        .asset_id("asset_id_a".to_owned())
+        .process_id(pid)
+        .process_guid(process_guid.to_owned());
+    if created {
+        builder
+            .state(ProcessState::Created)
+            .created_timestamp(timestamp);
+    } else {
+        builder
+            .state(ProcessState::Existing)
+            .last_seen_timestamp(timestamp);
+    }
+    builder.build().unwrap().into()
+}
+
+async fn node_key<S: Storage>(attributor: &NodeKeyAttributor<S>, node: Node) -> String {
+    let (node, _) = attributor
+        .attribute_node_key(node)
+        .await
+        .expect("Failed to attribute process");
+    node.clone_node_key()
+}
+
+async fn process_guid_keeps_its_node_across_pids(storage: impl Storage) {
+    let attributor = attributor(storage);
+
+    let created = node_key(
+        &attributor,
+        guid_process(1234, "guid-a", 1_544_301_484_600, true),
+    )
+    .await;
+    let seen = node_key(
+        &attributor,
+        guid_process(5678, "guid-a", 1_544_301_485_600, false),
+    )
+    .await;
+    assert_eq!(seen, created);
+
+    // The pid's session is still shared with nodes that arrive without a GUID
+    let guidless = node_key(
+        &attributor,
+        guid_process(1234, "", 1_544_301_485_000, false),
+    )
+    .await;
+    assert_eq!(guidless, created);
+}
+
+async fn reused_pid_with_new_guid_gets_a_new_node(storage: impl Storage) {
+    let attributor = attributor(storage);
+
+    let first = node_key(
+        &attributor,
+        guid_process(1234, "guid-a", 1_544_301_484_600, true),
+    )
+    .await;
+
+    // First seen after the pid was reused, but before its creation arrived. This would match
+    // the first process's session.
+    let second = node_key(
+        &attributor,
+        guid_process(1234, "guid-b", 1_544_301_494_600, false),
+    )
+    .await;
+    assert_ne!(second, first);
+
+    let second_created = node_key(
+        &attributor,
+        guid_process(1234, "guid-b", 1_544_301_494_500, true),
+    )
+    .await;
+    assert_eq!(second_created, second);
+
+    let third = node_key(
+        &attributor,
+        guid_process(1234, "guid-c", 1_544_301_504_600, true),
+    )
+    .await;
+    assert_ne!(third, first);
+    assert_ne!(third, second);
+}
+
+/// A file on `asset_id_a`, seen at `timestamp`
+fn seen_file(file_path: &str, timestamp: u64) -> Node {
+    FileBuilder::default()
+        .asset_id("asset_id_a".to_owned())
+        .state(FileState::Existing)
+        .last_seen_timestamp(timestamp)
+        .file_path(file_path.to_owned())
+        .build()
+        .unwrap()
+        .into()
+}
+
+async fn files_keep_sessions_stored_under_their_raw_path(storage: impl Storage) {
+    // Sessions stored before file paths were normalised and hashed into their keys
+    let legacy_session = |file_path: &str, session_id: &str, is_end_canon| Session {
+        session_id: session_id.to_owned(),
+        create_time: 1_544_301_484_600,
+        end_time: 1_544_301_484_700,
+        is_create_canon: true,
+        is_end_canon,
+        version: 0,
+        pseudo_key: format!("asset_id_a{}", file_path),
+    };
+    for session in &[
+        legacy_session(r"C:\Users\alice\notes.txt", "live", false),
+        legacy_session(r"C:\Users\alice\deleted.txt", "deleted", true),
+    ] {
+        storage
+            .create_session("file_history_table", session)
+            .await
+            .expect("Failed to create session");
+    }
+
+    let attributor = attributor(storage);
+
+    let notes = seen_file(r"C:\Users\alice\notes.txt", 1_544_301_485_000);
+    assert_eq!(node_key(&attributor, notes).await, "live");
+
+    // The file was deleted before it was seen again, so it's a new file under the new key
+    let deleted = seen_file(r"C:\Users\alice\deleted.txt", 1_544_301_485_000);
+    assert_ne!(node_key(&attributor, deleted).await, "deleted");
+}
+
+async fn concurrent_first_sightings_agree_on_a_node_key(storage: impl Storage) {
+    let mapping_db = DynamicMappingDb::new(storage.clone());
+
+    let winner = mapping_db
+        .create_mapping("pseudo_key".into(), "node_key_a".into())
+        .await
+        .expect("Failed to create mapping");
+    let loser = mapping_db
+        .create_mapping("pseudo_key".into(), "node_key_b".into())
+        .await
+        .expect("Failed to create mapping");
+    assert_eq!(winner, "node_key_a");
+    assert_eq!(loser, "node_key_a");
+
+    let (a, b) = (attributor(storage.clone()), attributor(storage));
+    let (key_a, key_b) = futures::join!(
+        node_key(&a, guid_process(1234, "guid-a", 1_544_301_484_600, false)),
+        node_key(&b, guid_process(1234, "guid-a", 1_544_301_484_700, false)),
+    );
+    assert_eq!(key_a, key_b);
+}
+
+async fn compaction_merges_guessed_sessions_and_expires_old_ones(storage: impl Storage) {
+    let table_name = "process_history_table";
+    let session =
+        |pseudo_key: &str, create_time, end_time, is_create_canon, is_end_canon| Session {
+            pseudo_key: pseudo_key.into(),
+            create_time,
+            is_create_canon,
+            session_id: format!("{}-{}", pseudo_key, create_time),
+            is_end_canon,
+            end_time,
+            version: 0,
+        };
+
+    for session in &[
+        session("asset_id_a1234", 1000, 2000, true, false),
+        session("asset_id_a1234", 5000, 6000, false, false),
+        session("asset_id_a1234", 7000, 9000, false, true),
+        session("asset_id_a1234", 10_000, 11_000, false, false),
+        session("asset_id_b1234", 100, 500, true, true),
+    ] {
+        storage
+            .create_session(table_name, session)
+            .await
+            .expect("Failed to create session");
+    }
+
+    let mut options = CompactionOptions {
+        expire_before: Some(900),
+        dry_run: true,
+        // Small pages, so that merges span them
+        page_size: 2,
+    };
+    let expected = CompactionStats {
+        scanned: 5,
+        merged: 2,
+        expired: 1,
+        conflicts: 0,
+    };
+    let remaining = || async {
+        storage
+            .scan_sessions(table_name, None, 100)
+            .await
+            .expect("Failed to scan sessions")
+            .items
+    };
+
+    let stats = compact_sessions(&storage, table_name, &options)
+        .await
+        .expect("Dry run failed");
+    assert_eq!(stats, expected);
+    assert_eq!(remaining().await.len(), 5);
+
+    options.dry_run = false;
+    let stats = compact_sessions(&storage, table_name, &options)
+        .await
+        .expect("Compaction failed");
+    assert_eq!(stats, expected);
+
+    let remaining = remaining().await;
+    assert_eq!(remaining.len(), 2);
+    assert_eq!(remaining[0].session_id, "asset_id_a1234-1000");
+    assert_eq!(remaining[0].end_time, 9000);
+    assert!(remaining[0].is_end_canon);
+    assert_eq!(remaining[1].session_id, "asset_id_a1234-10000");
+}
+
+async fn compaction_folds_repeated_asset_mappings(storage: impl Storage) {
+    let hostname = "ip-10-0-0-5".to_owned();
+    for (asset_id, c_timestamp) in &[
+        ("asset_id_a", 1000),
+        ("asset_id_a", 3000),
+        ("asset_id_b", 5000),
+    ] {
+        storage
+            .create_asset_mapping(hostname.clone(), (*asset_id).to_owned(), *c_timestamp)
+            .await
+            .expect("Mapping creation failed");
+    }
+    storage
+        .end_asset_mapping(hostname.clone(), 3000, 4000)
+        .await
+        .expect("Failed to end mapping");
+
+    let options = CompactionOptions {
+        expire_before: Some(4500),
+        ..Default::default()
+    };
+    let stats = compact_asset_mappings(&storage, &options)
+        .await
+        .expect("Compaction failed");
+    assert_eq!(
+        stats,
+        CompactionStats {
+            scanned: 3,
+            merged: 1,
+            expired: 1,
+            conflicts: 0,
+        }
+    );
+
+    // The merged mapping was retired before the retention window
+    let remaining = storage
+        .scan_asset_mappings(None, 100)
+        .await
+        .expect("Failed to scan mappings")
+        .items;
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].0, hostname);
+    assert_eq!(remaining[0].1.asset_id, "asset_id_b");
+}
+
+#[test]
+fn cache_serves_repeat_lookups_until_session_changes() {
+    let mut runtime = Runtime::new().unwrap();
+    let counted = CountingStorage::new(InMemoryStorage::new());
+    let storage = CachingStorage::new(counted.clone(), LookupCache::new(CacheOptions::default()));
+    let session_db = session_db(storage.clone());
+
+    let unid = |timestamp, is_creation| UnidSession {
+        pseudo_key: "asset_id_a1234".into(),
+        timestamp,
+        is_creation,
+        is_termination: false,
+    };
+
+    runtime.block_on(async {
+        let identify = |unid| session_db.handle_unid_session(unid, false);
+
+        let created = identify(unid(1_544_301_484_600, true)).await.unwrap();
+        assert_eq!(
+            identify(unid(1_544_301_484_650, false)).await.unwrap(),
+            created
+        );
+
+        let reads = counted.reads();
+        assert_eq!(
+            identify(unid(1_544_301_484_660, false)).await.unwrap(),
+            created
+        );
+        assert_eq!(counted.reads(), reads);
+        assert_eq!(storage.session_hits(), 1);
+
+        // Extending the session's end_time drops it from the cache
+        assert_eq!(
+            identify(unid(1_544_301_485_600, false)).await.unwrap(),
+            created
+        );
+        let reads = counted.reads();
+        assert_eq!(
+            identify(unid(1_544_301_485_600, false)).await.unwrap(),
+            created
+        );
+        assert!(counted.reads() > reads);
+    });
+}
+
+#[test]
+fn cache_drops_hostname_when_remapped() {
+    let mut runtime = Runtime::new().unwrap();
+    let storage = CachingStorage::new(
+        InMemoryStorage::new(),
+        LookupCache::new(CacheOptions::default()),
+    );
+    let asset_id_db = AssetIdDb::new(storage.clone());
+    let hostname = HostId::Hostname("fakehostname".to_owned());
+
+    runtime.block_on(async {
+        let resolve = |ts| asset_id_db.resolve_asset_id(&hostname, ts);
+
+        asset_id_db
+            .create_mapping(&hostname, "asset_id_a".into(), 100)
+            .await
+            .expect("Mapping creation failed");
+
+        assert_eq!(resolve(200).await.unwrap().as_deref(), Some("asset_id_a"));
+        assert_eq!(resolve(300).await.unwrap().as_deref(), Some("asset_id_a"));
+        assert_eq!(resolve(250).await.unwrap().as_deref(), Some("asset_id_a"));
+        assert_eq!(storage.asset_id_hits(), 1);
+
+        asset_id_db
+            .create_mapping(&hostname, "asset_id_b".into(), 260)
+            .await
+            .expect("Mapping creation failed");
+
+        assert_eq!(resolve(300).await.unwrap().as_deref(), Some("asset_id_b"));
+        assert_eq!(resolve(250).await.unwrap().as_deref(), Some("asset_id_a"));
+    });
+}
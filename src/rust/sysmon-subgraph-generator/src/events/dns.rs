@@ -0,0 +1,73 @@
+use std::net::IpAddr;
+
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct DnsQueryEventData {
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="ProcessGuid">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub process_guid: String,
+    /// <Data Name="ProcessId">6228</Data>
+    pub process_id: u64,
+    /// <Data Name="QueryName">www.example.com</Data>
+    pub query_name: String,
+    /// <Data Name="QueryStatus">0</Data>
+    pub query_status: u64,
+    /// <Data Name="QueryResults">type:  5 example.edgekey.net;::ffff:93.184.216.34;</Data>
+    pub query_results: Option<String>,
+    /// <Data Name="Image">C:\Program Files\Mozilla Firefox\firefox.exe</Data>
+    pub image: String,
+}
+
+/// Sysmon Event ID 22, emitted when a process executes a DNS query.
+#[derive(Debug, Clone, Hash)]
+pub struct DnsQueryEvent {
+    pub system: System,
+    pub event_data: DnsQueryEventData,
+}
+
+impl DnsQueryEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = DnsQueryEventData {
+            utc_time: fields.required("UtcTime")?,
+            process_guid: normalize_guid(&fields.required("ProcessGuid")?),
+            process_id: fields.required("ProcessId")?.parse()?,
+            query_name: fields.required("QueryName")?,
+            query_status: fields.required("QueryStatus")?.parse()?,
+            query_results: fields.optional("QueryResults"),
+            image: fields.required("Image")?,
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+
+    /// The IP addresses the query resolved to.
+    ///
+    /// `QueryResults` is a `;` separated list that mixes addresses with non-address records,
+    /// such as `type:  5 example.edgekey.net` for a CNAME. Only the addresses are returned, with
+    /// IPv4-mapped IPv6 addresses (`::ffff:93.184.216.34`) converted to plain IPv4.
+    pub fn resolved_ips(&self) -> Vec<String> {
+        let query_results = match self.event_data.query_results {
+            Some(ref query_results) => query_results,
+            None => return vec![],
+        };
+
+        query_results
+            .split(';')
+            .map(str::trim)
+            .filter(|result| !result.is_empty() && !result.starts_with("type:"))
+            .map(|result| result.trim_start_matches("::ffff:"))
+            .filter_map(|result| result.parse::<IpAddr>().ok())
+            .map(|ip| ip.to_string())
+            .collect()
+    }
+}
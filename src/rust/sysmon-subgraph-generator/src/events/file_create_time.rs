@@ -0,0 +1,50 @@
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct FileCreateTimeEventData {
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="ProcessGuid">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub process_guid: String,
+    /// <Data Name="ProcessId">6228</Data>
+    pub process_id: u64,
+    /// <Data Name="Image">C:\Users\rsmith\AppData\Local\Temp\timestomp.exe</Data>
+    pub image: String,
+    /// <Data Name="TargetFilename">C:\Windows\System32\payload.dll</Data>
+    pub target_filename: String,
+    /// <Data Name="CreationUtcTime">2016-07-16 16:19:01.000</Data>
+    pub creation_utc_time: String,
+    /// <Data Name="PreviousCreationUtcTime">2017-04-28 22:08:21.310</Data>
+    pub previous_creation_utc_time: String,
+}
+
+/// Sysmon Event ID 2, emitted when a process changes a file's creation time.
+#[derive(Debug, Clone, Hash)]
+pub struct FileCreateTimeEvent {
+    pub system: System,
+    pub event_data: FileCreateTimeEventData,
+}
+
+impl FileCreateTimeEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = FileCreateTimeEventData {
+            utc_time: fields.required("UtcTime")?,
+            process_guid: normalize_guid(&fields.required("ProcessGuid")?),
+            process_id: fields.required("ProcessId")?.parse()?,
+            image: fields.required("Image")?,
+            target_filename: fields.required("TargetFilename")?,
+            creation_utc_time: fields.required("CreationUtcTime")?,
+            previous_creation_utc_time: fields.required("PreviousCreationUtcTime")?,
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+}
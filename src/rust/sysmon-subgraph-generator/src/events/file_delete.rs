@@ -0,0 +1,67 @@
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct FileDeleteEventData {
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="ProcessGuid">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub process_guid: String,
+    /// <Data Name="ProcessId">6228</Data>
+    pub process_id: u64,
+    /// <Data Name="User">DESKTOP-FVSHABR\grapltest</Data>
+    pub user: Option<String>,
+    /// <Data Name="Image">C:\Windows\System32\cmd.exe</Data>
+    pub image: String,
+    /// <Data Name="TargetFilename">C:\Users\rsmith\AppData\Local\Temp\payload.exe</Data>
+    pub target_filename: String,
+    /// <Data Name="Hashes">SHA256=6055A20CF7EC81843310AD37700FF67B2CF8CDE3DCE68D54BA42934177C10B57</Data>
+    pub hashes: Option<String>,
+    /// <Data Name="IsExecutable">true</Data>
+    pub is_executable: bool,
+    /// <Data Name="Archived">true</Data>
+    ///
+    /// Only present on Event ID 23 (FileDelete); Event ID 26 (FileDeleteDetected) never archives
+    pub archived: bool,
+}
+
+/// Sysmon Event IDs 23 and 26, emitted when a file is deleted.
+///
+/// Event 23 (FileDelete) is emitted when Sysmon archived the deleted file, and Event 26
+/// (FileDeleteDetected) when it only logged the deletion. Both describe the same activity.
+#[derive(Debug, Clone, Hash)]
+pub struct FileDeleteEvent {
+    pub system: System,
+    pub event_data: FileDeleteEventData,
+}
+
+impl FileDeleteEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = FileDeleteEventData {
+            utc_time: fields.required("UtcTime")?,
+            process_guid: normalize_guid(&fields.required("ProcessGuid")?),
+            process_id: fields.required("ProcessId")?.parse()?,
+            user: fields.optional("User"),
+            image: fields.required("Image")?,
+            target_filename: fields.required("TargetFilename")?,
+            hashes: fields.optional("Hashes"),
+            is_executable: fields
+                .optional("IsExecutable")
+                .map(|is_executable| is_executable.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            archived: fields
+                .optional("Archived")
+                .map(|archived| archived.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+}
@@ -0,0 +1,82 @@
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct ImageLoadEventData {
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="ProcessGuid">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub process_guid: String,
+    /// <Data Name="ProcessId">6228</Data>
+    pub process_id: u64,
+    /// <Data Name="Image">C:\Windows\System32\rundll32.exe</Data>
+    pub image: String,
+    /// <Data Name="ImageLoaded">C:\Users\rsmith\AppData\Local\Temp\version.dll</Data>
+    pub image_loaded: String,
+    /// <Data Name="FileVersion">10.0.17134.1 (WinBuild.160101.0800)</Data>
+    pub file_version: Option<String>,
+    /// <Data Name="Description">Version Checking and File Installation Libraries</Data>
+    pub description: Option<String>,
+    /// <Data Name="Product">Microsoft® Windows® Operating System</Data>
+    pub product: Option<String>,
+    /// <Data Name="Company">Microsoft Corporation</Data>
+    pub company: Option<String>,
+    /// <Data Name="Hashes">SHA256=6055A20CF7EC81843310AD37700FF67B2CF8CDE3DCE68D54BA42934177C10B57</Data>
+    pub hashes: Option<String>,
+    /// <Data Name="Signed">true</Data>
+    pub signed: bool,
+    /// <Data Name="Signature">Microsoft Windows</Data>
+    pub signature: Option<String>,
+    /// <Data Name="SignatureStatus">Valid</Data>
+    pub signature_status: Option<String>,
+}
+
+/// Sysmon Event ID 7, emitted when a module is loaded into a process.
+#[derive(Debug, Clone, Hash)]
+pub struct ImageLoadEvent {
+    pub system: System,
+    pub event_data: ImageLoadEventData,
+}
+
+impl ImageLoadEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = ImageLoadEventData {
+            utc_time: fields.required("UtcTime")?,
+            process_guid: normalize_guid(&fields.required("ProcessGuid")?),
+            process_id: fields.required("ProcessId")?.parse()?,
+            image: fields.required("Image")?,
+            image_loaded: fields.required("ImageLoaded")?,
+            file_version: fields.optional("FileVersion"),
+            description: fields.optional("Description"),
+            product: fields.optional("Product"),
+            company: fields.optional("Company"),
+            hashes: fields.optional("Hashes"),
+            signed: fields
+                .optional("Signed")
+                .map(|signed| signed.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+            signature: fields.optional("Signature"),
+            signature_status: fields.optional("SignatureStatus"),
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+
+    /// Whether the loaded module carries a valid code signature.
+    pub fn is_validly_signed(&self) -> bool {
+        self.event_data.signed
+            && self
+                .event_data
+                .signature_status
+                .as_ref()
+                .map(|status| status == "Valid")
+                .unwrap_or(false)
+    }
+}
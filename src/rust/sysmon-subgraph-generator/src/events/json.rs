@@ -0,0 +1,213 @@
+use failure::{bail, Error};
+use serde_json::{Map, Value};
+use sysmon::{
+    Channel, Computer, Data, EventId, EventRecordId, Execution, IntermediaryEventData, Keywords,
+    Level, Opcode, Provider, Security, System, Task, TimeCreated, Version,
+};
+
+use crate::events::RawEvent;
+
+/// Top level NXLog fields that describe the event itself rather than its `EventData`
+const NXLOG_HEADER_FIELDS: &[&str] = &[
+    "EventTime",
+    "EventReceivedTime",
+    "Hostname",
+    "Keywords",
+    "SeverityValue",
+    "Severity",
+    "EventID",
+    "SourceName",
+    "ProviderGuid",
+    "Version",
+    "Task",
+    "OpcodeValue",
+    "Opcode",
+    "RecordNumber",
+    "ProcessID",
+    "ThreadID",
+    "Channel",
+    "Domain",
+    "AccountName",
+    "UserID",
+    "AccountType",
+    "Message",
+    "Category",
+    "SourceModuleName",
+    "SourceModuleType",
+];
+
+/// Parses a Sysmon event shipped as JSON into a `RawEvent`.
+///
+/// Two layouts are supported:
+/// * Winlogbeat (ECS), where the header lives under `winlog` and the Sysmon fields under
+///   `winlog.event_data`
+/// * NXLog (`im_msvistalog` + `to_json()`), where the header and the Sysmon fields are all
+///   top level fields
+pub(crate) fn raw_event_from_json(s: &str) -> Result<RawEvent, Error> {
+    let event: Value = serde_json::from_str(s)?;
+
+    let event = match event {
+        Value::Object(event) => event,
+        _ => bail!("Expected a JSON object"),
+    };
+
+    match event.get("winlog") {
+        Some(Value::Object(winlog)) => from_winlogbeat(&event, winlog),
+        _ => from_nxlog(&event),
+    }
+}
+
+fn from_winlogbeat(
+    event: &Map<String, Value>,
+    winlog: &Map<String, Value>,
+) -> Result<RawEvent, Error> {
+    let computer = match string_field(winlog, "computer_name") {
+        Some(computer) => computer,
+        None => match event.get("host") {
+            Some(Value::Object(host)) => required_field(host, "name")?,
+            _ => bail!("No field: winlog.computer_name"),
+        },
+    };
+
+    let system = SystemHeader {
+        event_id: required_field(winlog, "event_id")?.parse()?,
+        computer,
+        record_id: string_field(winlog, "record_id")
+            .map(|record_id| record_id.parse())
+            .transpose()?
+            .unwrap_or_default(),
+        time_created: string_field(event, "@timestamp").unwrap_or_default(),
+        channel: string_field(winlog, "channel").unwrap_or_default(),
+        provider_name: string_field(winlog, "provider_name").unwrap_or_default(),
+        provider_guid: string_field(winlog, "provider_guid").unwrap_or_default(),
+    }
+    .into_system();
+
+    let event_data = match winlog.get("event_data") {
+        Some(Value::Object(event_data)) => event_data
+            .iter()
+            .map(|(name, value)| into_data(name, value))
+            .collect(),
+        _ => bail!("No field: winlog.event_data"),
+    };
+
+    Ok(RawEvent {
+        system,
+        event_data: IntermediaryEventData { data: event_data },
+    })
+}
+
+fn from_nxlog(event: &Map<String, Value>) -> Result<RawEvent, Error> {
+    let system = SystemHeader {
+        event_id: required_field(event, "EventID")?.parse()?,
+        computer: required_field(event, "Hostname")?,
+        record_id: string_field(event, "RecordNumber")
+            .map(|record_id| record_id.parse())
+            .transpose()?
+            .unwrap_or_default(),
+        time_created: string_field(event, "EventTime").unwrap_or_default(),
+        channel: string_field(event, "Channel").unwrap_or_default(),
+        provider_name: string_field(event, "SourceName").unwrap_or_default(),
+        provider_guid: string_field(event, "ProviderGuid").unwrap_or_default(),
+    }
+    .into_system();
+
+    let event_data = event
+        .iter()
+        .filter(|(name, _)| !NXLOG_HEADER_FIELDS.contains(&name.as_str()))
+        .map(|(name, value)| into_data(name, value))
+        .collect();
+
+    Ok(RawEvent {
+        system,
+        event_data: IntermediaryEventData { data: event_data },
+    })
+}
+
+/// The parts of the `System` header that JSON shippers carry and that Grapl makes use of
+struct SystemHeader {
+    event_id: u8,
+    computer: String,
+    record_id: u32,
+    time_created: String,
+    channel: String,
+    provider_name: String,
+    provider_guid: String,
+}
+
+impl SystemHeader {
+    fn into_system(self) -> System {
+        System {
+            provider: Provider {
+                provider_name: self.provider_name,
+                provider_guid: self.provider_guid,
+            },
+            event_id: EventId {
+                event_id: self.event_id,
+            },
+            version: Version {
+                version: String::new(),
+            },
+            level: Level {
+                level: String::new(),
+            },
+            task: Task {
+                task: String::new(),
+            },
+            opcode: Opcode {
+                opcode: String::new(),
+            },
+            keywords: Keywords {
+                keywords: String::new(),
+            },
+            time_created: TimeCreated {
+                system_time: self.time_created,
+            },
+            event_record_id: EventRecordId {
+                event_record_id: self.record_id,
+            },
+            execution: Execution {
+                process_id: String::new(),
+                thread_id: String::new(),
+            },
+            channel: Channel {
+                value: self.channel,
+            },
+            computer: Computer {
+                computer: self.computer,
+            },
+            security: Security {
+                security: String::new(),
+            },
+        }
+    }
+}
+
+fn into_data(name: &str, value: &Value) -> Data {
+    Data {
+        name: name.to_owned(),
+        value: value_to_string(value).filter(|value| !value.is_empty()),
+    }
+}
+
+/// Shippers are inconsistent about types, e.g. Winlogbeat 7 emits `event_id` as a number while
+/// Winlogbeat 8 emits it as a string, so every scalar is read as a string.
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn string_field(object: &Map<String, Value>, name: &str) -> Option<String> {
+    object.get(name).and_then(value_to_string)
+}
+
+fn required_field(object: &Map<String, Value>, name: &str) -> Result<String, Error> {
+    match string_field(object, name) {
+        Some(value) => Ok(value),
+        None => bail!("No field: {}", name),
+    }
+}
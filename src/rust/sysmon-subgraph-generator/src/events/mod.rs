@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use failure::{bail, Error};
+use serde_derive::Deserialize;
+use sysmon::{
+    Event, FileCreateEvent, FileCreateEventData, IntermediaryEventData, NetworkEvent,
+    NetworkEventData, System,
+};
+
+mod dns;
+mod file_create_time;
+mod file_delete;
+mod image_load;
+mod json;
+mod process_access;
+mod process_create;
+mod registry;
+mod remote_thread;
+
+pub use dns::{DnsQueryEvent, DnsQueryEventData};
+pub use file_create_time::{FileCreateTimeEvent, FileCreateTimeEventData};
+pub use file_delete::{FileDeleteEvent, FileDeleteEventData};
+pub use image_load::{ImageLoadEvent, ImageLoadEventData};
+pub use process_access::{ProcessAccessEvent, ProcessAccessEventData};
+pub use process_create::ProcessCreateEvent;
+pub use registry::{RegistryEvent, RegistryEventData};
+pub use remote_thread::{CreateRemoteThreadEvent, CreateRemoteThreadEventData};
+
+/// A Sysmon event that this generator knows how to turn into a subgraph.
+///
+/// The `sysmon` crate only understands ProcessCreate, FileCreate and NetworkConnect events.
+/// Every other event type is parsed here, from the same `RawEvent` representation, into
+/// crate-local types that mirror the ones provided by `sysmon`.
+#[derive(Debug, Clone, Hash)]
+pub enum SysmonEvent {
+    Sysmon(Event),
+    ProcessCreate(ProcessCreateEvent),
+    FileCreateTime(FileCreateTimeEvent),
+    ImageLoad(ImageLoadEvent),
+    CreateRemoteThread(CreateRemoteThreadEvent),
+    ProcessAccess(ProcessAccessEvent),
+    Registry(RegistryEvent),
+    DnsQuery(DnsQueryEvent),
+    FileDelete(FileDeleteEvent),
+}
+
+/// The Sysmon event ids that can be turned into a `SysmonEvent`.
+pub const SUPPORTED_EVENT_IDS: &[u8] = &[1, 2, 3, 7, 8, 10, 11, 12, 13, 14, 22, 23, 26];
+
+impl SysmonEvent {
+    #[cfg(test)]
+    pub fn from_str(s: impl AsRef<str>) -> Result<Self, Error> {
+        Self::from_raw(RawEvent::from_str(s.as_ref())?)
+    }
+
+    /// Converts an already parsed `RawEvent` into a `SysmonEvent`
+    pub(crate) fn from_raw(raw: RawEvent) -> Result<Self, Error> {
+        match raw.event_id() {
+            1 => Ok(SysmonEvent::ProcessCreate(
+                ProcessCreateEvent::try_from_raw(raw)?,
+            )),
+            2 => Ok(SysmonEvent::FileCreateTime(
+                FileCreateTimeEvent::try_from_raw(raw)?,
+            )),
+            3 => {
+                let event = NetworkEvent {
+                    event_data: NetworkEventData::try_from(raw.event_data)
+                        .map_err(|e| failure::err_msg(e.to_string()))?,
+                    system: raw.system,
+                };
+
+                if event.event_data.initiated {
+                    Ok(SysmonEvent::Sysmon(Event::OutboundNetwork(event)))
+                } else {
+                    Ok(SysmonEvent::Sysmon(Event::InboundNetwork(event)))
+                }
+            }
+            7 => Ok(SysmonEvent::ImageLoad(ImageLoadEvent::try_from_raw(raw)?)),
+            8 => Ok(SysmonEvent::CreateRemoteThread(
+                CreateRemoteThreadEvent::try_from_raw(raw)?,
+            )),
+            10 => Ok(SysmonEvent::ProcessAccess(
+                ProcessAccessEvent::try_from_raw(raw)?,
+            )),
+            11 => Ok(SysmonEvent::Sysmon(Event::FileCreate(FileCreateEvent {
+                event_data: FileCreateEventData::try_from(raw.event_data)
+                    .map_err(|e| failure::err_msg(e.to_string()))?,
+                system: raw.system,
+            }))),
+            12 | 13 | 14 => Ok(SysmonEvent::Registry(RegistryEvent::try_from_raw(raw)?)),
+            22 => Ok(SysmonEvent::DnsQuery(DnsQueryEvent::try_from_raw(raw)?)),
+            23 | 26 => Ok(SysmonEvent::FileDelete(FileDeleteEvent::try_from_raw(raw)?)),
+            event_id => bail!("Unsupported event id: {}", event_id),
+        }
+    }
+}
+
+/// The generic shape of every Sysmon event: a `System` header and a list of named `Data` fields.
+///
+/// Events are read either from the XML rendering Windows produces or from the JSON emitted by
+/// log shippers such as Winlogbeat and NXLog, which is normalized into the same shape.
+#[derive(Debug, Deserialize)]
+pub(crate) struct RawEvent {
+    #[serde(rename = "System")]
+    pub system: System,
+    #[serde(rename = "EventData")]
+    pub event_data: IntermediaryEventData,
+}
+
+impl RawEvent {
+    /// Parses a single event, selecting the format from its first character
+    pub fn from_str(s: &str) -> Result<Self, Error> {
+        match s.trim_start().chars().next() {
+            Some('<') => Self::from_xml(s),
+            Some('{') => json::raw_event_from_json(s),
+            _ => bail!("Unrecognized event format"),
+        }
+    }
+
+    pub fn from_xml(s: &str) -> Result<Self, Error> {
+        serde_xml_rs::from_str(s)
+            .map_err(|e| failure::err_msg(format!("Failed to parse event xml: {:?}", e)))
+    }
+
+    pub fn event_id(&self) -> u8 {
+        self.system.event_id.event_id
+    }
+}
+
+/// The `<Data Name="...">` fields of an event, keyed by name.
+///
+/// Fields with empty values are treated as absent.
+#[derive(Debug, Clone)]
+pub(crate) struct EventDataFields(HashMap<String, String>);
+
+impl EventDataFields {
+    pub fn required(&mut self, name: &str) -> Result<String, Error> {
+        match self.0.remove(name) {
+            Some(value) => Ok(value),
+            None => bail!("No field: {}", name),
+        }
+    }
+
+    pub fn optional(&mut self, name: &str) -> Option<String> {
+        self.0.remove(name)
+    }
+}
+
+impl From<IntermediaryEventData> for EventDataFields {
+    fn from(inter: IntermediaryEventData) -> Self {
+        let mut fields = HashMap::with_capacity(inter.data.len());
+
+        for data in inter.data {
+            if let Some(value) = data.value {
+                fields.insert(data.name, value);
+            }
+        }
+
+        EventDataFields(fields)
+    }
+}
+
+/// Normalizes a Sysmon GUID, such as `{A23EAE89-BD56-5903-0000-0010E9D95E00}`, into the
+/// lowercase, unbraced form produced by `uuid::Uuid::to_string`.
+pub(crate) fn normalize_guid(guid: &str) -> String {
+    guid.trim_start_matches('{')
+        .trim_end_matches('}')
+        .to_lowercase()
+}
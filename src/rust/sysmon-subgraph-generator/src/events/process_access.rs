@@ -0,0 +1,59 @@
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct ProcessAccessEventData {
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="SourceProcessGUID">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub source_process_guid: String,
+    /// <Data Name="SourceProcessId">6228</Data>
+    pub source_process_id: u64,
+    /// <Data Name="SourceThreadId">4712</Data>
+    pub source_thread_id: u64,
+    /// <Data Name="SourceImage">C:\Users\rsmith\AppData\Local\Temp\dumper.exe</Data>
+    pub source_image: String,
+    /// <Data Name="TargetProcessGUID">{A23EAE89-BD28-5903-0000-00102F345D00}</Data>
+    pub target_process_guid: String,
+    /// <Data Name="TargetProcessId">620</Data>
+    pub target_process_id: u64,
+    /// <Data Name="TargetImage">C:\Windows\system32\lsass.exe</Data>
+    pub target_image: String,
+    /// <Data Name="GrantedAccess">0x1010</Data>
+    pub granted_access: String,
+    /// <Data Name="CallTrace">C:\Windows\SYSTEM32\ntdll.dll+a5594|C:\Windows\System32\KERNELBASE.dll+1e865|UNKNOWN(00000000001A2B3C)</Data>
+    pub call_trace: Option<String>,
+}
+
+/// Sysmon Event ID 10, emitted when a process opens a handle to another process.
+#[derive(Debug, Clone, Hash)]
+pub struct ProcessAccessEvent {
+    pub system: System,
+    pub event_data: ProcessAccessEventData,
+}
+
+impl ProcessAccessEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = ProcessAccessEventData {
+            utc_time: fields.required("UtcTime")?,
+            source_process_guid: normalize_guid(&fields.required("SourceProcessGUID")?),
+            source_process_id: fields.required("SourceProcessId")?.parse()?,
+            source_thread_id: fields.required("SourceThreadId")?.parse()?,
+            source_image: fields.required("SourceImage")?,
+            target_process_guid: normalize_guid(&fields.required("TargetProcessGUID")?),
+            target_process_id: fields.required("TargetProcessId")?.parse()?,
+            target_image: fields.required("TargetImage")?,
+            granted_access: fields.required("GrantedAccess")?.to_lowercase(),
+            call_trace: fields.optional("CallTrace"),
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+}
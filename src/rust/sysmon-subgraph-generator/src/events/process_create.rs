@@ -0,0 +1,43 @@
+use std::convert::TryFrom;
+
+use failure::Error;
+
+use crate::events::{EventDataFields, RawEvent};
+
+/// Sysmon Event ID 1, emitted when a process is created.
+///
+/// Wraps `sysmon::ProcessCreateEvent` with the fields that the `sysmon` crate does not parse.
+#[derive(Debug, Clone, Hash)]
+pub struct ProcessCreateEvent {
+    pub event: sysmon::ProcessCreateEvent,
+    /// <Data Name="ParentUser">LAB\rsmith</Data>
+    ///
+    /// Only emitted by Sysmon 13.30 and later
+    pub parent_user: Option<String>,
+}
+
+impl ProcessCreateEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data.clone());
+
+        let event_data = sysmon::ProcessCreateEventData::try_from(raw.event_data)
+            .map_err(|e| failure::err_msg(e.to_string()))?;
+
+        Ok(Self {
+            event: sysmon::ProcessCreateEvent {
+                system: raw.system,
+                event_data,
+            },
+            parent_user: fields.optional("ParentUser"),
+        })
+    }
+}
+
+impl From<sysmon::ProcessCreateEvent> for ProcessCreateEvent {
+    fn from(event: sysmon::ProcessCreateEvent) -> Self {
+        Self {
+            event,
+            parent_user: None,
+        }
+    }
+}
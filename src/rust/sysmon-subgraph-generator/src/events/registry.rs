@@ -0,0 +1,68 @@
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct RegistryEventData {
+    /// <Data Name="EventType">SetValue</Data>
+    pub event_type: String,
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="ProcessGuid">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub process_guid: String,
+    /// <Data Name="ProcessId">6228</Data>
+    pub process_id: u64,
+    /// <Data Name="Image">C:\Windows\regedit.exe</Data>
+    pub image: String,
+    /// <Data Name="TargetObject">HKU\S-1-5-21-...\Software\Microsoft\Windows\CurrentVersion\Run\Updater</Data>
+    pub target_object: String,
+    /// <Data Name="Details">C:\Users\rsmith\AppData\Local\Temp\updater.exe</Data>
+    ///
+    /// Only present on Event ID 13 (RegistryEvent Value Set)
+    pub details: Option<String>,
+    /// <Data Name="NewName">HKU\S-1-5-21-...\Software\Microsoft\Windows\CurrentVersion\Run\Updater2</Data>
+    ///
+    /// Only present on Event ID 14 (RegistryEvent Key and Value Rename)
+    pub new_name: Option<String>,
+}
+
+/// Sysmon Event IDs 12, 13 and 14, emitted when a registry key or value is created, deleted,
+/// set or renamed.
+#[derive(Debug, Clone, Hash)]
+pub struct RegistryEvent {
+    pub system: System,
+    pub event_data: RegistryEventData,
+}
+
+impl RegistryEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = RegistryEventData {
+            event_type: fields.required("EventType")?,
+            utc_time: fields.required("UtcTime")?,
+            process_guid: normalize_guid(&fields.required("ProcessGuid")?),
+            process_id: fields.required("ProcessId")?.parse()?,
+            image: fields.required("Image")?,
+            target_object: fields.required("TargetObject")?,
+            details: fields.optional("Details"),
+            new_name: fields.optional("NewName"),
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+
+    /// Whether the event describes the deletion of a key or value
+    pub fn is_delete(&self) -> bool {
+        self.event_data.event_type.starts_with("Delete")
+    }
+
+    /// Whether the event describes the creation of a key or value
+    pub fn is_create(&self) -> bool {
+        self.event_data.event_type.starts_with("Create")
+    }
+}
@@ -0,0 +1,62 @@
+use failure::Error;
+use sysmon::System;
+
+use crate::events::{normalize_guid, EventDataFields, RawEvent};
+
+#[derive(Debug, Clone, Hash)]
+pub struct CreateRemoteThreadEventData {
+    /// <Data Name="UtcTime">2017-04-28 22:08:22.025</Data>
+    pub utc_time: String,
+    /// <Data Name="SourceProcessGuid">{A23EAE89-BD56-5903-0000-0010E9D95E00}</Data>
+    pub source_process_guid: String,
+    /// <Data Name="SourceProcessId">6228</Data>
+    pub source_process_id: u64,
+    /// <Data Name="SourceImage">C:\Users\rsmith\AppData\Local\Temp\injector.exe</Data>
+    pub source_image: String,
+    /// <Data Name="TargetProcessGuid">{A23EAE89-BD28-5903-0000-00102F345D00}</Data>
+    pub target_process_guid: String,
+    /// <Data Name="TargetProcessId">2316</Data>
+    pub target_process_id: u64,
+    /// <Data Name="TargetImage">C:\Windows\explorer.exe</Data>
+    pub target_image: String,
+    /// <Data Name="NewThreadId">6568</Data>
+    pub new_thread_id: u64,
+    /// <Data Name="StartAddress">0x00007FFB1C2A1234</Data>
+    pub start_address: String,
+    /// <Data Name="StartModule">C:\Windows\System32\kernel32.dll</Data>
+    pub start_module: Option<String>,
+    /// <Data Name="StartFunction">LoadLibraryW</Data>
+    pub start_function: Option<String>,
+}
+
+/// Sysmon Event ID 8, emitted when a process creates a thread in another process.
+#[derive(Debug, Clone, Hash)]
+pub struct CreateRemoteThreadEvent {
+    pub system: System,
+    pub event_data: CreateRemoteThreadEventData,
+}
+
+impl CreateRemoteThreadEvent {
+    pub(crate) fn try_from_raw(raw: RawEvent) -> Result<Self, Error> {
+        let mut fields = EventDataFields::from(raw.event_data);
+
+        let event_data = CreateRemoteThreadEventData {
+            utc_time: fields.required("UtcTime")?,
+            source_process_guid: normalize_guid(&fields.required("SourceProcessGuid")?),
+            source_process_id: fields.required("SourceProcessId")?.parse()?,
+            source_image: fields.required("SourceImage")?,
+            target_process_guid: normalize_guid(&fields.required("TargetProcessGuid")?),
+            target_process_id: fields.required("TargetProcessId")?.parse()?,
+            target_image: fields.required("TargetImage")?,
+            new_thread_id: fields.required("NewThreadId")?.parse()?,
+            start_address: fields.required("StartAddress")?,
+            start_module: fields.optional("StartModule"),
+            start_function: fields.optional("StartFunction"),
+        };
+
+        Ok(Self {
+            system: raw.system,
+            event_data,
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashSet;
+
+use log::warn;
+
+use crate::events::{ImageLoadEvent, SUPPORTED_EVENT_IDS};
+
+/// The set of Sysmon event ids that are turned into subgraphs.
+///
+/// Defaults to every supported event id. Configured through grapl-config's
+/// `SYSMON_ENABLED_EVENT_IDS` (or `SYSMON_ENABLED_EVENT_IDS_FILE`); unsupported ids in the
+/// configuration are ignored.
+#[derive(Debug, Clone)]
+pub struct EventIdFilter {
+    enabled: HashSet<u8>,
+}
+
+impl EventIdFilter {
+    pub fn new(event_ids: impl IntoIterator<Item = u8>) -> Self {
+        let enabled = event_ids
+            .into_iter()
+            .filter(|event_id| {
+                let supported = SUPPORTED_EVENT_IDS.contains(event_id);
+                if !supported {
+                    warn!("Ignoring unsupported Sysmon event id: {}", event_id);
+                }
+                supported
+            })
+            .collect();
+
+        Self { enabled }
+    }
+
+    pub fn from_config() -> Self {
+        match grapl_config::sysmon_enabled_event_ids() {
+            Some(event_ids) => Self::new(event_ids),
+            None => Self::default(),
+        }
+    }
+
+    /// Returns true if events with this id should be turned into subgraphs
+    pub fn is_enabled(&self, event_id: u8) -> bool {
+        self.enabled.contains(&event_id)
+    }
+}
+
+impl Default for EventIdFilter {
+    fn default() -> Self {
+        Self::new(SUPPORTED_EVENT_IDS.iter().copied())
+    }
+}
+
+/// Volume controls for Sysmon ImageLoad (Event ID 7) events.
+///
+/// ImageLoad is emitted for every module loaded by every process, so the vast majority of
+/// these events describe the same handful of system DLLs. This filter drops the loads that
+/// are least interesting for detection before they are turned into subgraphs.
+///
+/// Configured through the environment:
+/// * `SYSMON_IMAGE_LOAD_SKIP_SIGNED` - drop modules with a valid code signature (default `false`)
+/// * `SYSMON_IMAGE_LOAD_IGNORED_PATHS` - comma separated, case insensitive path prefixes to drop
+#[derive(Debug, Clone, Default)]
+pub struct ImageLoadFilter {
+    skip_signed: bool,
+    ignored_prefixes: Vec<String>,
+}
+
+impl ImageLoadFilter {
+    pub fn new(skip_signed: bool, ignored_prefixes: Vec<String>) -> Self {
+        Self {
+            skip_signed,
+            ignored_prefixes: ignored_prefixes
+                .into_iter()
+                .map(|prefix| prefix.to_lowercase())
+                .collect(),
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let skip_signed = std::env::var("SYSMON_IMAGE_LOAD_SKIP_SIGNED")
+            .map(|skip| skip.to_lowercase().parse().unwrap_or(false))
+            .unwrap_or(false);
+
+        let ignored_prefixes = std::env::var("SYSMON_IMAGE_LOAD_IGNORED_PATHS")
+            .map(|paths| {
+                paths
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|path| !path.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self::new(skip_signed, ignored_prefixes)
+    }
+
+    /// Returns true if the ImageLoad event should be turned into a subgraph
+    pub fn should_keep(&self, event: &ImageLoadEvent) -> bool {
+        if self.skip_signed && event.is_validly_signed() {
+            return false;
+        }
+
+        let image_loaded = event.event_data.image_loaded.to_lowercase();
+
+        !self
+            .ignored_prefixes
+            .iter()
+            .any(|prefix| image_loaded.starts_with(prefix))
+    }
+}
@@ -1,14 +1,29 @@
+use crate::events::{RawEvent, SysmonEvent};
+use crate::filters::{EventIdFilter, ImageLoadFilter};
 use crate::metrics::SysmonSubgraphGeneratorMetrics;
 use crate::models::SysmonTryFrom;
 use async_trait::async_trait;
-use failure::bail;
+use futures::stream::{self, StreamExt};
 use grapl_graph_descriptions::graph_description::*;
 use grapl_observe::log_time;
 use log::*;
+use rayon::prelude::*;
 use sqs_lambda::cache::{Cache, CacheResponse};
 use sqs_lambda::event_handler::{Completion, EventHandler, OutputEvent};
 use std::borrow::Cow;
-use sysmon::Event;
+use std::collections::HashSet;
+
+/// How many cache lookups a batch may have in flight when `SYSMON_CACHE_CONCURRENCY` is unset
+const DEFAULT_CACHE_CONCURRENCY: usize = 32;
+
+/// Reads the cache lookup concurrency limit from `SYSMON_CACHE_CONCURRENCY`
+pub(crate) fn cache_concurrency_from_env() -> usize {
+    std::env::var("SYSMON_CACHE_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse().ok())
+        .filter(|concurrency| *concurrency > 0)
+        .unwrap_or(DEFAULT_CACHE_CONCURRENCY)
+}
 
 #[derive(Clone)]
 pub(crate) struct SysmonSubgraphGenerator<C>
@@ -17,64 +32,232 @@ where
 {
     cache: C,
     metrics: SysmonSubgraphGeneratorMetrics,
+    event_id_filter: EventIdFilter,
+    image_load_filter: ImageLoadFilter,
+    cache_concurrency: usize,
 }
 
 impl<C> SysmonSubgraphGenerator<C>
 where
     C: Cache + Clone + Send + Sync + 'static,
 {
-    pub fn new(cache: C, metrics: SysmonSubgraphGeneratorMetrics) -> Self {
-        Self { cache, metrics }
+    pub fn new(
+        cache: C,
+        metrics: SysmonSubgraphGeneratorMetrics,
+        event_id_filter: EventIdFilter,
+        image_load_filter: ImageLoadFilter,
+        cache_concurrency: usize,
+    ) -> Self {
+        Self {
+            cache,
+            metrics,
+            event_id_filter,
+            image_load_filter,
+            cache_concurrency,
+        }
     }
 
     /// Takes a vec of event Strings, parses them, and converts them into subgraphs
+    ///
+    /// Parsing and subgraph conversion are CPU bound and run in parallel on rayon's pool. The
+    /// steps in between, deduplicating image loads and checking the cache, stay on the async
+    /// task since they depend on batch order and on IO respectively.
     async fn process_events(
         &mut self,
         events: Vec<Cow<'_, str>>,
-    ) -> (Graph, Vec<Event>, Option<failure::Error>) {
+    ) -> (Graph, Vec<SysmonEvent>, Option<failure::Error>) {
         let mut last_failure: Option<failure::Error> = None;
-        let mut identities = Vec::with_capacity(events.len());
-        let mut final_subgraph = Graph::new(0);
+        let mut parse_failures = 0;
+        let mut skipped_by_policy = 0;
 
-        for event in events {
-            let event = match Event::from_str(&event) {
-                Ok(event) => event,
-                Err(e) => {
+        // The same module is typically loaded by a process many times over, so only the
+        // first load of an image by a given process in a batch is kept
+        let mut seen_image_loads = HashSet::new();
+        let mut image_loads_filtered = 0;
+
+        let parsed = log_time!("event parse", parse_events(&self.event_id_filter, &events));
+
+        let mut candidates = Vec::with_capacity(parsed.len());
+
+        for (parsed, event) in parsed.into_iter().zip(events.iter()) {
+            let event = match parsed {
+                ParsedEvent::Event(event) => *event,
+                ParsedEvent::SkippedByPolicy(event_id) => {
+                    debug!("Skipping event with id {}", event_id);
+                    skipped_by_policy += 1;
+                    continue;
+                }
+                ParsedEvent::Failed(e) => {
                     warn!("Failed to deserialize event: {}, {}", e, event);
 
+                    parse_failures += 1;
                     last_failure = Some(failure::err_msg(format!("Failed: {}", e)));
 
                     continue;
                 }
             };
 
-            match self.cache.get(event.clone()).await {
-                Ok(CacheResponse::Hit) => {
-                    info!("Got cached response");
+            if let SysmonEvent::ImageLoad(ref image_load) = event {
+                let load_key = (
+                    image_load.system.computer.computer.clone(),
+                    image_load.event_data.process_guid.clone(),
+                    image_load.event_data.image_loaded.to_lowercase(),
+                );
+
+                if !self.image_load_filter.should_keep(image_load)
+                    || !seen_image_loads.insert(load_key)
+                {
+                    image_loads_filtered += 1;
                     continue;
                 }
-                Err(e) => warn!("Cache failed with: {:?}", e),
-                _ => (),
-            };
+            }
+
+            candidates.push(event);
+        }
 
-            let graph = match Graph::try_from(event.clone()) {
-                Ok(subgraph) => subgraph,
+        // Bounded so that a large batch doesn't open a connection per event at once, and
+        // ordered so that each response lines up with its candidate
+        let lookups: Vec<_> = candidates
+            .iter()
+            .map(|event| {
+                let mut cache = self.cache.clone();
+                let event = event.clone();
+
+                async move { cache.get(event).await }
+            })
+            .collect();
+        let cache_responses: Vec<_> = stream::iter(lookups)
+            .buffered(self.cache_concurrency)
+            .collect()
+            .await;
+
+        let uncached: Vec<_> = candidates
+            .into_iter()
+            .zip(cache_responses)
+            .filter_map(|(event, cache_response)| match cache_response {
+                Ok(CacheResponse::Hit) => {
+                    info!("Got cached response");
+                    None
+                }
                 Err(e) => {
-                    // TODO: we should probably be recording each separate failure, but this is only going to save the last failure
-                    last_failure = Some(e);
-                    continue;
+                    warn!("Cache failed with: {:?}", e);
+                    Some(event)
                 }
+                _ => Some(event),
+            })
+            .collect();
+
+        let converted = log_time!("subgraph conversion", events_to_subgraph(uncached));
+
+        if converted.last_failure.is_some() {
+            // TODO: we should probably be recording each separate failure, but this is only going to save the last failure
+            last_failure = converted.last_failure;
+        }
+
+        if skipped_by_policy > 0 {
+            info!("Skipped {} events by policy", skipped_by_policy);
+            self.metrics
+                .report_events_skipped_by_policy(skipped_by_policy);
+        }
+
+        if parse_failures > 0 {
+            self.metrics.report_parse_failures(parse_failures);
+        }
+
+        if image_loads_filtered > 0 {
+            info!("Filtered {} image load events", image_loads_filtered);
+            self.metrics
+                .report_image_loads_filtered(image_loads_filtered);
+        }
+
+        (converted.graph, converted.identities, last_failure)
+    }
+}
+
+/// The outcome of parsing a single line of a batch
+pub(crate) enum ParsedEvent {
+    Event(Box<SysmonEvent>),
+    SkippedByPolicy(u8),
+    Failed(failure::Error),
+}
+
+/// Parses every event of a batch in parallel, preserving batch order
+pub(crate) fn parse_events(
+    event_id_filter: &EventIdFilter,
+    events: &[Cow<'_, str>],
+) -> Vec<ParsedEvent> {
+    events
+        .par_iter()
+        .map(|event| {
+            let raw = match RawEvent::from_str(event) {
+                Ok(raw) => raw,
+                Err(e) => return ParsedEvent::Failed(e),
             };
 
-            identities.push(event);
+            let event_id = raw.event_id();
 
-            final_subgraph.merge(&graph);
+            if !event_id_filter.is_enabled(event_id) {
+                return ParsedEvent::SkippedByPolicy(event_id);
+            }
+
+            match SysmonEvent::from_raw(raw) {
+                Ok(event) => ParsedEvent::Event(Box::new(event)),
+                Err(e) => ParsedEvent::Failed(e),
+            }
+        })
+        .collect()
+}
+
+/// The merged result of converting a set of events into subgraphs
+pub(crate) struct ConvertedEvents {
+    pub graph: Graph,
+    pub identities: Vec<SysmonEvent>,
+    pub last_failure: Option<failure::Error>,
+}
+
+impl ConvertedEvents {
+    fn empty() -> Self {
+        Self {
+            graph: Graph::new(0),
+            identities: vec![],
+            last_failure: None,
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        self.graph.merge(&other.graph);
+        self.identities.extend(other.identities);
+
+        if other.last_failure.is_some() {
+            self.last_failure = other.last_failure;
         }
 
-        (final_subgraph, identities, last_failure)
+        self
     }
 }
 
+/// Converts events into subgraphs in parallel.
+///
+/// Subgraphs are merged pairwise as rayon joins its work back together, so no single thread
+/// has to fold every subgraph of the batch into one accumulator. The reduction keeps batch
+/// order, so `identities` and the last failure match what a sequential pass would produce.
+pub(crate) fn events_to_subgraph(events: Vec<SysmonEvent>) -> ConvertedEvents {
+    events
+        .into_par_iter()
+        .map(|event| match Graph::try_from(event.clone()) {
+            Ok(graph) => ConvertedEvents {
+                graph,
+                identities: vec![event],
+                last_failure: None,
+            },
+            Err(e) => ConvertedEvents {
+                last_failure: Some(e),
+                ..ConvertedEvents::empty()
+            },
+        })
+        .reduce(ConvertedEvents::empty, ConvertedEvents::merge)
+}
+
 #[async_trait]
 impl<C> EventHandler for SysmonSubgraphGenerator<C>
 where
@@ -92,13 +275,14 @@ where
 
         /*
            This iterator is taking a set of bytes of the logs, splitting the logs on newlines,
-           converting the byte sequences to utf-8 strings, and then filtering on the following criteria:
-               1. The line isn't empty
-               2. The line is not `\n` (to prevent issues with multiple newline sequences)
-               3. The line contains event with ID 1, 3, or 11
+           converting the byte sequences to utf-8 strings, and then filtering out empty lines
+           (including lone `\n`s, to prevent issues with multiple newline sequences).
+
+           Each line is either a single XML event or a single JSON event (Winlogbeat/NXLog),
+           so a batch may mix both formats.
 
-           The event ids 1, 3, and 11 correspond to Process Creation, Network Connection, and File Creation
-           in that order.
+           Which event types are turned into subgraphs is decided once each event's EventID
+           has been parsed, in `process_events`.
 
            https://docs.microsoft.com/en-us/sysinternals/downloads/sysmon#events
         */
@@ -107,12 +291,7 @@ where
             events
                 .split(|i| &[*i][..] == &b"\n"[..])
                 .map(String::from_utf8_lossy)
-                .filter(|event| {
-                    (!event.is_empty() && event != "\n")
-                        && (event.contains(&"EventID>1<"[..])
-                            || event.contains(&"EventID>3<"[..])
-                            || event.contains(&"EventID>11<"[..]))
-                })
+                .filter(|event| !event.is_empty() && event != "\n")
                 .collect()
         );
 
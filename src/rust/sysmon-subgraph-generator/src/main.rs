@@ -1,9 +1,13 @@
 #![type_length_limit = "1334469"]
 
+mod events;
+mod filters;
 mod generator;
 mod metrics;
 mod models;
 mod serialization;
+#[cfg(test)]
+mod tests;
 
 use sqs_lambda::cache::NopCache;
 
@@ -11,7 +15,8 @@ use graph_generator_lib::*;
 
 use log::*;
 
-use crate::generator::SysmonSubgraphGenerator;
+use crate::filters::{EventIdFilter, ImageLoadFilter};
+use crate::generator::{cache_concurrency_from_env, SysmonSubgraphGenerator};
 use crate::metrics::SysmonSubgraphGeneratorMetrics;
 use crate::serialization::ZstdDecoder;
 use grapl_config::*;
@@ -22,13 +27,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting sysmon-subgraph-generator");
 
     let metrics = SysmonSubgraphGeneratorMetrics::new(&env.service_name);
+    let event_id_filter = EventIdFilter::from_config();
+    let image_load_filter = ImageLoadFilter::from_env();
+    let cache_concurrency = cache_concurrency_from_env();
 
     if grapl_config::is_local() {
-        let generator = SysmonSubgraphGenerator::new(NopCache {}, metrics);
+        let generator = SysmonSubgraphGenerator::new(
+            NopCache {},
+            metrics,
+            event_id_filter,
+            image_load_filter,
+            cache_concurrency,
+        );
 
         run_graph_generator(generator, ZstdDecoder::default()).await;
     } else {
-        let generator = SysmonSubgraphGenerator::new(event_cache().await, metrics);
+        let generator = SysmonSubgraphGenerator::new(
+            event_cache().await,
+            metrics,
+            event_id_filter,
+            image_load_filter,
+            cache_concurrency,
+        );
 
         run_graph_generator(generator, ZstdDecoder::default()).await;
     }
@@ -31,4 +31,26 @@ impl SysmonSubgraphGeneratorMetrics {
             )
             .unwrap_or_else(|e| warn!("Metric failed: {}", e))
     }
+
+    pub fn report_events_skipped_by_policy(&mut self, count: usize) {
+        self.metric_reporter
+            .counter(
+                "sysmon-generator-events-skipped-by-policy",
+                count as f64,
+                None,
+            )
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+    }
+
+    pub fn report_parse_failures(&mut self, count: usize) {
+        self.metric_reporter
+            .counter("sysmon-generator-parse-failures", count as f64, None)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+    }
+
+    pub fn report_image_loads_filtered(&mut self, count: usize) {
+        self.metric_reporter
+            .counter("sysmon-generator-image-loads-filtered", count as f64, None)
+            .unwrap_or_else(|e| warn!("Metric failed: {}", e))
+    }
 }
@@ -0,0 +1,24 @@
+use derive_dynamic_node::{DynamicNode, GraplStaticId};
+use grapl_graph_descriptions::graph_description::*;
+
+mod query;
+
+pub use query::generate_dns_query_subgraph;
+
+/// A DNS name, as queried by a process.
+///
+/// Identity = Static(query_name)
+#[allow(dead_code)]
+#[derive(DynamicNode, GraplStaticId)]
+pub struct DnsQuery {
+    #[grapl(static_id)]
+    query_name: String,
+    query_status: u64,
+    last_seen_timestamp: u64,
+}
+
+impl IDnsQueryNode for DnsQueryNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
@@ -0,0 +1,76 @@
+use crate::events::DnsQueryEvent;
+use crate::models::dns::{DnsQueryNode, IDnsQueryNode};
+use crate::models::{get_image_name, utc_to_epoch};
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing a `DnsQueryEvent`
+///
+/// Subgraph generation for a `DnsQueryEvent` includes the following:
+/// * An `Asset` node - indicating the asset on which the query was made
+/// * A querying `Process` node - indicating the process that made the query
+/// * A `DnsQuery` node - the name that was queried
+/// * An `IpAddress` node for each address the name resolved to
+pub fn generate_dns_query_subgraph(dns_query: &DnsQueryEvent) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&dns_query.event_data.utc_time)?;
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(dns_query.system.computer.computer.clone())
+        .hostname(dns_query.system.computer.computer.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let querier = ProcessBuilder::default()
+        .asset_id(dns_query.system.computer.computer.clone())
+        .state(ProcessState::Existing)
+        .process_id(dns_query.event_data.process_id)
+        .process_guid(dns_query.event_data.process_guid.clone())
+        .process_name(get_image_name(&dns_query.event_data.image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let mut query = DnsQueryNode::new(DnsQueryNode::static_strategy(), timestamp);
+    query
+        .with_query_name(dns_query.event_data.query_name.to_lowercase())
+        .with_query_status(dns_query.event_data.query_status)
+        .with_last_seen_timestamp(timestamp);
+
+    // IpAddress nodes are identified by their address alone, so these merge with the
+    // destination addresses of any outbound connections made after the lookup
+    for resolved_ip in dns_query.resolved_ips() {
+        let ip_address = IpAddressBuilder::default()
+            .ip_address(resolved_ip)
+            .last_seen_timestamp(timestamp)
+            .build()
+            .map_err(|err| failure::err_msg(err))?;
+
+        graph.add_edge(
+            "dns_query_resolved_to",
+            query.clone_node_key(),
+            ip_address.clone_node_key(),
+        );
+
+        graph.add_node(ip_address);
+    }
+
+    graph.add_edge(
+        "process_asset",
+        querier.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "dns_queries",
+        querier.clone_node_key(),
+        query.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(querier);
+    graph.add_node(query);
+
+    Ok(graph)
+}
@@ -26,6 +26,7 @@ pub fn generate_file_create_subgraph(
         .asset_id(file_create.system.computer.computer.clone())
         .state(ProcessState::Existing)
         .process_id(file_create.event_data.process_id)
+        .process_guid(file_create.event_data.process_guid.process_guid.to_string())
         .process_name(get_image_name(&file_create.event_data.image.clone()).unwrap())
         .last_seen_timestamp(timestamp)
         //        .created_timestamp(file_create.event_data.process_guid.get_creation_timestamp())
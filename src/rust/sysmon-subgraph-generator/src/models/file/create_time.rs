@@ -0,0 +1,68 @@
+use crate::events::FileCreateTimeEvent;
+use crate::models::{get_image_name, strip_file_zone_identifier, utc_to_epoch};
+use grapl_graph_descriptions::file::FileState;
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing a `FileCreateTimeEvent`
+///
+/// The subgraph generation for a `FileCreateTimeEvent` includes the following:
+/// * A modifying `Process` node - denotes the process that changed the file's creation time
+/// * A subject `File` node - the file whose creation time was changed, carrying both the new
+///   and the previous creation time
+pub fn generate_file_create_time_subgraph(
+    file_create_time: &FileCreateTimeEvent,
+) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&file_create_time.event_data.utc_time)?;
+    let changed_creation_timestamp = utc_to_epoch(&file_create_time.event_data.creation_utc_time)?;
+    let previous_creation_timestamp =
+        utc_to_epoch(&file_create_time.event_data.previous_creation_utc_time)?;
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(file_create_time.system.computer.computer.clone())
+        .hostname(file_create_time.system.computer.computer.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let modifier = ProcessBuilder::default()
+        .asset_id(file_create_time.system.computer.computer.clone())
+        .state(ProcessState::Existing)
+        .process_id(file_create_time.event_data.process_id)
+        .process_guid(file_create_time.event_data.process_guid.clone())
+        .process_name(get_image_name(&file_create_time.event_data.image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let file = FileBuilder::default()
+        .asset_id(file_create_time.system.computer.computer.clone())
+        .state(FileState::Existing)
+        .file_path(strip_file_zone_identifier(
+            &file_create_time.event_data.target_filename,
+        ))
+        .last_seen_timestamp(timestamp)
+        .changed_creation_timestamp(changed_creation_timestamp)
+        .previous_creation_timestamp(previous_creation_timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    graph.add_edge(
+        "process_asset",
+        modifier.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "modified_file_time",
+        modifier.clone_node_key(),
+        file.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(modifier);
+    graph.add_node(file);
+
+    Ok(graph)
+}
@@ -0,0 +1,72 @@
+use crate::events::FileDeleteEvent;
+use crate::models::{get_image_name, parse_hashes, strip_file_zone_identifier, utc_to_epoch};
+use grapl_graph_descriptions::file::FileState;
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing a `FileDeleteEvent`
+///
+/// The subgraph generation for a `FileDeleteEvent` includes the following:
+/// * A deleter `Process` node - denotes the process that deleted the file
+/// * A subject `File` node - the file that is deleted as part of this event
+pub fn generate_file_delete_subgraph(
+    file_delete: &FileDeleteEvent,
+) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&file_delete.event_data.utc_time)?;
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(file_delete.system.computer.computer.clone())
+        .hostname(file_delete.system.computer.computer.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let deleter = ProcessBuilder::default()
+        .asset_id(file_delete.system.computer.computer.clone())
+        .state(ProcessState::Existing)
+        .process_id(file_delete.event_data.process_id)
+        .process_guid(file_delete.event_data.process_guid.clone())
+        .process_name(get_image_name(&file_delete.event_data.image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let hashes = file_delete
+        .event_data
+        .hashes
+        .as_ref()
+        .map(|hashes| parse_hashes(hashes))
+        .unwrap_or_default();
+
+    let file = FileBuilder::default()
+        .asset_id(file_delete.system.computer.computer.clone())
+        .state(FileState::Deleted)
+        .file_path(strip_file_zone_identifier(
+            &file_delete.event_data.target_filename,
+        ))
+        .md5_hash(hashes.md5)
+        .sha1_hash(hashes.sha1)
+        .sha256_hash(hashes.sha256)
+        .deleted_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    graph.add_edge(
+        "process_asset",
+        deleter.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "deleted_files",
+        deleter.clone_node_key(),
+        file.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(deleter);
+    graph.add_node(file);
+
+    Ok(graph)
+}
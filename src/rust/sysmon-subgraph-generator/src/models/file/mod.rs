@@ -1,3 +1,7 @@
 mod create;
+mod create_time;
+mod delete;
 
 pub use create::generate_file_create_subgraph;
+pub use create_time::generate_file_create_time_subgraph;
+pub use delete::generate_file_delete_subgraph;
@@ -0,0 +1,96 @@
+use crate::events::ImageLoadEvent;
+use crate::models::{get_image_name, parse_hashes, strip_file_zone_identifier, utc_to_epoch};
+use grapl_graph_descriptions::file::FileState;
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing an `ImageLoadEvent`
+///
+/// Subgraph generation for an `ImageLoadEvent` includes the following:
+/// * An `Asset` node - indicating the asset on which the module was loaded
+/// * A loading `Process` node - indicating the process the module was loaded into
+/// * A loaded `File` node - the module itself, with its hashes and signature information
+pub fn generate_image_load_subgraph(image_load: &ImageLoadEvent) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&image_load.event_data.utc_time)?;
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(image_load.system.computer.computer.clone())
+        .hostname(image_load.system.computer.computer.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let loader = ProcessBuilder::default()
+        .asset_id(image_load.system.computer.computer.clone())
+        .state(ProcessState::Existing)
+        .process_id(image_load.event_data.process_id)
+        .process_guid(image_load.event_data.process_guid.clone())
+        .process_name(get_image_name(&image_load.event_data.image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let hashes = image_load
+        .event_data
+        .hashes
+        .as_ref()
+        .map(|hashes| parse_hashes(hashes))
+        .unwrap_or_default();
+
+    let module = FileBuilder::default()
+        .asset_id(image_load.system.computer.computer.clone())
+        .state(FileState::Existing)
+        .last_seen_timestamp(timestamp)
+        .file_path(strip_file_zone_identifier(
+            &image_load.event_data.image_loaded,
+        ))
+        .file_version(
+            image_load
+                .event_data
+                .file_version
+                .clone()
+                .unwrap_or_default(),
+        )
+        .file_description(
+            image_load
+                .event_data
+                .description
+                .clone()
+                .unwrap_or_default(),
+        )
+        .file_product(image_load.event_data.product.clone().unwrap_or_default())
+        .file_company(image_load.event_data.company.clone().unwrap_or_default())
+        .md5_hash(hashes.md5)
+        .sha1_hash(hashes.sha1)
+        .sha256_hash(hashes.sha256)
+        .signed(image_load.event_data.signed)
+        .signature(image_load.event_data.signature.clone().unwrap_or_default())
+        .signed_status(
+            image_load
+                .event_data
+                .signature_status
+                .clone()
+                .unwrap_or_default(),
+        )
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    graph.add_edge(
+        "process_asset",
+        loader.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "loaded_modules",
+        loader.clone_node_key(),
+        module.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(loader);
+    graph.add_node(module);
+
+    Ok(graph)
+}
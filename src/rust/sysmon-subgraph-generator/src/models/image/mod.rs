@@ -0,0 +1,3 @@
+mod load;
+
+pub use load::generate_image_load_subgraph;
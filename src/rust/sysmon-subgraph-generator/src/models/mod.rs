@@ -5,9 +5,14 @@ use grapl_graph_descriptions::graph_description::*;
 use log::*;
 use sysmon::Event;
 
+use crate::events::SysmonEvent;
+
+mod dns;
 mod file;
+mod image;
 mod network;
 mod process;
+mod registry;
 
 /// Because this crate doesn't own sysmon::Event nor grapl_graph_descriptions::graph_description::Graph
 /// we need to create a new Trait to add a function to Graph for Event.
@@ -27,7 +32,7 @@ impl SysmonTryFrom<Event> for Graph {
             Event::ProcessCreate(event) => {
                 info!("Handling process create");
 
-                let result = process::generate_process_create_subgraph(&event);
+                let result = process::generate_process_create_subgraph(&event.into());
 
                 if let Err(e) = &result {
                     warn!("Failed to process process start event: {}", e);
@@ -79,6 +84,104 @@ impl SysmonTryFrom<Event> for Graph {
     }
 }
 
+impl SysmonTryFrom<SysmonEvent> for Graph {
+    type Error = failure::Error;
+
+    fn try_from(instance: SysmonEvent) -> Result<Self, Self::Error> {
+        match instance {
+            SysmonEvent::Sysmon(event) => Graph::try_from(event),
+            SysmonEvent::ProcessCreate(event) => {
+                info!("Handling process create");
+
+                let result = process::generate_process_create_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process process start event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::FileCreateTime(event) => {
+                info!("FileCreateTime");
+
+                let result = file::generate_file_create_time_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process file create time event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::ImageLoad(event) => {
+                info!("ImageLoad");
+
+                let result = image::generate_image_load_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process image load event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::CreateRemoteThread(event) => {
+                info!("CreateRemoteThread");
+
+                let result = process::generate_create_remote_thread_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process create remote thread event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::ProcessAccess(event) => {
+                info!("ProcessAccess");
+
+                let result = process::generate_process_access_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process process access event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::Registry(event) => {
+                info!("Registry");
+
+                let result = registry::generate_registry_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process registry event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::DnsQuery(event) => {
+                info!("DnsQuery");
+
+                let result = dns::generate_dns_query_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process dns query event: {}", e);
+                }
+
+                result
+            }
+            SysmonEvent::FileDelete(event) => {
+                info!("FileDelete");
+
+                let result = file::generate_file_delete_subgraph(&event);
+
+                if let Err(e) = &result {
+                    warn!("Failed to process file delete event: {}", e);
+                }
+
+                result
+            }
+        }
+    }
+}
+
 /// Returns the provided file path with the Windows Zone Identifier removed if present.
 ///
 /// When files are downloaded via a browser (e.g. Internet Explorer), an alternative data stream (ADS) may be created
@@ -125,3 +228,36 @@ pub fn utc_to_epoch(utc: &str) -> Result<u64, Error> {
         Ok(ts as u64)
     }
 }
+
+/// The file hashes reported in a Sysmon `Hashes` field.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileHashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// Parses a Sysmon `Hashes` field, such as `SHA1=...,MD5=...,SHA256=...,IMPHASH=...`.
+///
+/// Which algorithms are present depends on the Sysmon configuration. Algorithms that Grapl
+/// does not model (e.g. IMPHASH) and malformed entries are ignored.
+pub fn parse_hashes(hashes: &str) -> FileHashes {
+    let mut parsed = FileHashes::default();
+
+    for hash in hashes.split(',') {
+        let mut parts = hash.splitn(2, '=');
+        let (algorithm, value) = match (parts.next(), parts.next()) {
+            (Some(algorithm), Some(value)) => (algorithm.trim(), value.trim()),
+            _ => continue,
+        };
+
+        match algorithm.to_uppercase().as_str() {
+            "MD5" => parsed.md5 = value.to_lowercase(),
+            "SHA1" => parsed.sha1 = value.to_lowercase(),
+            "SHA256" => parsed.sha256 = value.to_lowercase(),
+            _ => (),
+        }
+    }
+
+    parsed
+}
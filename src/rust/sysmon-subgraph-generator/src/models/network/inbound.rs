@@ -32,6 +32,7 @@ pub fn generate_inbound_connection_subgraph(
         .hostname(conn_log.system.computer.computer.clone())
         .state(ProcessState::Existing)
         .process_id(conn_log.event_data.process_id)
+        .process_guid(conn_log.event_data.process_guid.process_guid.to_string())
         .last_seen_timestamp(timestamp)
         .build()
         .map_err(|err| failure::err_msg(err))?;
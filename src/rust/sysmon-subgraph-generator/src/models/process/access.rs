@@ -0,0 +1,99 @@
+use crate::events::ProcessAccessEvent;
+use crate::models::process::{IProcessAccessNode, ProcessAccessNode};
+use crate::models::{get_image_name, utc_to_epoch};
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing a `ProcessAccessEvent`
+///
+/// Subgraph generation for a `ProcessAccessEvent` includes the following:
+/// * An `Asset` node - indicating the asset on which both processes run
+/// * A source `Process` node - indicating the process that opened the handle
+/// * A target `Process` node - indicating the process the handle was opened to
+/// * A `ProcessAccess` node - carrying the granted access mask and call trace
+pub fn generate_process_access_subgraph(
+    process_access: &ProcessAccessEvent,
+) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&process_access.event_data.utc_time)?;
+    let asset_id = process_access.system.computer.computer.clone();
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(asset_id.clone())
+        .hostname(asset_id.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let source = ProcessBuilder::default()
+        .asset_id(asset_id.clone())
+        .state(ProcessState::Existing)
+        .process_id(process_access.event_data.source_process_id)
+        .process_guid(process_access.event_data.source_process_guid.clone())
+        .process_name(get_image_name(&process_access.event_data.source_image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let target = ProcessBuilder::default()
+        .asset_id(asset_id.clone())
+        .state(ProcessState::Existing)
+        .process_id(process_access.event_data.target_process_id)
+        .process_guid(process_access.event_data.target_process_guid.clone())
+        .process_name(get_image_name(&process_access.event_data.target_image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let mut access = ProcessAccessNode::new(ProcessAccessNode::static_strategy(), timestamp);
+    access
+        .with_source_process_guid(process_access.event_data.source_process_guid.clone())
+        .with_target_process_guid(process_access.event_data.target_process_guid.clone())
+        .with_granted_access(process_access.event_data.granted_access.clone())
+        .with_call_trace(
+            process_access
+                .event_data
+                .call_trace
+                .clone()
+                .unwrap_or_default(),
+        )
+        .with_last_seen_timestamp(timestamp);
+    access.with_asset_id(asset_id);
+
+    graph.add_edge(
+        "process_asset",
+        source.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "process_asset",
+        target.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "accessed_process",
+        source.clone_node_key(),
+        target.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "process_accesses",
+        source.clone_node_key(),
+        access.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "process_access_target",
+        access.clone_node_key(),
+        target.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(source);
+    graph.add_node(target);
+    graph.add_node(access);
+
+    Ok(graph)
+}
@@ -1,9 +1,9 @@
-use crate::models::{get_image_name, strip_file_zone_identifier, utc_to_epoch};
+use crate::events::ProcessCreateEvent;
+use crate::models::{get_image_name, parse_hashes, strip_file_zone_identifier, utc_to_epoch};
 use grapl_graph_descriptions::file::FileState;
 use grapl_graph_descriptions::graph_description::*;
 use grapl_graph_descriptions::node::NodeT;
 use grapl_graph_descriptions::process::ProcessState;
-use sysmon::ProcessCreateEvent;
 
 /// Creates a subgraph describing a `ProcessCreateEvent`.
 ///
@@ -13,8 +13,9 @@ use sysmon::ProcessCreateEvent;
 /// * A subject `Process` node - indicating the process created per the `ProcessCreateEvent`
 /// * A process `File` node - indicating the file executed in creating the new process
 pub fn generate_process_create_subgraph(
-    process_start: &ProcessCreateEvent,
+    process_create: &ProcessCreateEvent,
 ) -> Result<Graph, failure::Error> {
+    let process_start = &process_create.event;
     let timestamp = utc_to_epoch(&process_start.event_data.utc_time)?;
     let mut graph = Graph::new(timestamp);
 
@@ -28,8 +29,16 @@ pub fn generate_process_create_subgraph(
         .asset_id(process_start.system.computer.computer.clone())
         .state(ProcessState::Existing)
         .process_id(process_start.event_data.parent_process_id)
+        .process_guid(
+            process_start
+                .event_data
+                .parent_process_guid
+                .process_guid
+                .to_string(),
+        )
         .process_name(get_image_name(&process_start.event_data.parent_image.clone()).unwrap())
         .process_command_line(&process_start.event_data.parent_command_line.command_line)
+        .process_user(process_create.parent_user.clone().unwrap_or_default())
         .last_seen_timestamp(timestamp)
         //        .created_timestamp(process_start.event_data.parent_process_guid.get_creation_timestamp())
         .build()
@@ -39,17 +48,32 @@ pub fn generate_process_create_subgraph(
         .asset_id(process_start.system.computer.computer.clone())
         .process_name(get_image_name(&process_start.event_data.image.clone()).unwrap())
         .process_command_line(&process_start.event_data.command_line.command_line)
+        .process_user(&process_start.event_data.user.user)
+        .process_integrity_level(&process_start.event_data.integrity_level.integrity_level)
+        .process_current_directory(&process_start.event_data.current_directory.current_directory)
         .state(ProcessState::Created)
         .process_id(process_start.event_data.process_id)
+        .process_guid(
+            process_start
+                .event_data
+                .process_guid
+                .process_guid
+                .to_string(),
+        )
         .created_timestamp(timestamp)
         .build()
         .map_err(|err| failure::err_msg(err))?;
 
+    let hashes = parse_hashes(&process_start.event_data.hashes.hashes);
+
     let child_exe = FileBuilder::default()
         .asset_id(process_start.system.computer.computer.clone())
         .state(FileState::Existing)
         .last_seen_timestamp(timestamp)
         .file_path(strip_file_zone_identifier(&process_start.event_data.image))
+        .md5_hash(hashes.md5)
+        .sha1_hash(hashes.sha1)
+        .sha256_hash(hashes.sha256)
         .build()
         .map_err(|err| failure::err_msg(err))?;
 
@@ -1,3 +1,56 @@
+use derive_dynamic_node::{DynamicNode, GraplStaticId};
+use grapl_graph_descriptions::graph_description::*;
+
+mod access;
 mod create;
+mod remote_thread;
 
+pub use access::generate_process_access_subgraph;
 pub use create::generate_process_create_subgraph;
+pub use remote_thread::generate_create_remote_thread_subgraph;
+
+/// A thread created by one process inside of another.
+///
+/// Identity = Static(source_process_guid + target_process_guid + new_thread_id)
+#[allow(dead_code)]
+#[derive(DynamicNode, GraplStaticId)]
+pub struct RemoteThread {
+    #[grapl(static_id)]
+    source_process_guid: String,
+    #[grapl(static_id)]
+    target_process_guid: String,
+    #[grapl(static_id)]
+    new_thread_id: u64,
+    start_address: String,
+    start_module: String,
+    start_function: String,
+    created_timestamp: u64,
+}
+
+impl IRemoteThreadNode for RemoteThreadNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+/// A handle opened by one process to another, with the access rights it was granted.
+///
+/// Identity = Static(source_process_guid + target_process_guid + granted_access)
+#[allow(dead_code)]
+#[derive(DynamicNode, GraplStaticId)]
+pub struct ProcessAccess {
+    #[grapl(static_id)]
+    source_process_guid: String,
+    #[grapl(static_id)]
+    target_process_guid: String,
+    #[grapl(static_id)]
+    granted_access: String,
+    call_trace: String,
+    last_seen_timestamp: u64,
+}
+
+impl IProcessAccessNode for ProcessAccessNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
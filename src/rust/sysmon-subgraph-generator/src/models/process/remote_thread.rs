@@ -0,0 +1,107 @@
+use crate::events::CreateRemoteThreadEvent;
+use crate::models::process::{IRemoteThreadNode, RemoteThreadNode};
+use crate::models::{get_image_name, utc_to_epoch};
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing a `CreateRemoteThreadEvent`
+///
+/// Subgraph generation for a `CreateRemoteThreadEvent` includes the following:
+/// * An `Asset` node - indicating the asset on which both processes run
+/// * A source `Process` node - indicating the process that created the thread
+/// * A target `Process` node - indicating the process the thread was created in
+/// * A `RemoteThread` node - carrying the thread's start address, module and function
+pub fn generate_create_remote_thread_subgraph(
+    remote_thread: &CreateRemoteThreadEvent,
+) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&remote_thread.event_data.utc_time)?;
+    let asset_id = remote_thread.system.computer.computer.clone();
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(asset_id.clone())
+        .hostname(asset_id.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let source = ProcessBuilder::default()
+        .asset_id(asset_id.clone())
+        .state(ProcessState::Existing)
+        .process_id(remote_thread.event_data.source_process_id)
+        .process_guid(remote_thread.event_data.source_process_guid.clone())
+        .process_name(get_image_name(&remote_thread.event_data.source_image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let target = ProcessBuilder::default()
+        .asset_id(asset_id.clone())
+        .state(ProcessState::Existing)
+        .process_id(remote_thread.event_data.target_process_id)
+        .process_guid(remote_thread.event_data.target_process_guid.clone())
+        .process_name(get_image_name(&remote_thread.event_data.target_image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let mut thread = RemoteThreadNode::new(RemoteThreadNode::static_strategy(), timestamp);
+    thread
+        .with_source_process_guid(remote_thread.event_data.source_process_guid.clone())
+        .with_target_process_guid(remote_thread.event_data.target_process_guid.clone())
+        .with_new_thread_id(remote_thread.event_data.new_thread_id)
+        .with_start_address(remote_thread.event_data.start_address.clone())
+        .with_start_module(
+            remote_thread
+                .event_data
+                .start_module
+                .clone()
+                .unwrap_or_default(),
+        )
+        .with_start_function(
+            remote_thread
+                .event_data
+                .start_function
+                .clone()
+                .unwrap_or_default(),
+        )
+        .with_created_timestamp(timestamp);
+    thread.with_asset_id(asset_id);
+
+    graph.add_edge(
+        "process_asset",
+        source.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "process_asset",
+        target.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "injected_into",
+        source.clone_node_key(),
+        target.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "created_remote_thread",
+        source.clone_node_key(),
+        thread.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "remote_thread_target",
+        thread.clone_node_key(),
+        target.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(source);
+    graph.add_node(target);
+    graph.add_node(thread);
+
+    Ok(graph)
+}
@@ -0,0 +1,46 @@
+use derive_dynamic_node::DynamicNode;
+use grapl_graph_descriptions::graph_description::*;
+
+mod modify;
+
+pub use modify::generate_registry_subgraph;
+
+/// A registry key or value, as referenced by a Sysmon `TargetObject`.
+///
+/// Identity = Static(asset_id + normalized_key_path)
+#[allow(dead_code)]
+#[derive(DynamicNode)]
+pub struct RegistryKey {
+    key_path: String,
+    normalized_key_path: String,
+    value_data: String,
+    renamed_to: String,
+    last_event_type: String,
+    created_timestamp: u64,
+    deleted_timestamp: u64,
+    last_seen_timestamp: u64,
+}
+
+impl IRegistryKeyNode for RegistryKeyNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+impl RegistryKeyNode {
+    /// Registry paths are only unique per asset, so unlike `GraplStaticId` the asset id is
+    /// part of the identity.
+    pub fn static_strategy() -> IdStrategy {
+        Static {
+            primary_key_properties: vec!["normalized_key_path".to_string()],
+            primary_key_requires_asset_id: true,
+        }
+        .into()
+    }
+}
+
+/// Registry paths are case insensitive; the hive and key names are lowercased so that the same
+/// key always maps to the same node.
+pub fn normalize_key_path(key_path: &str) -> String {
+    key_path.trim().trim_end_matches('\\').to_lowercase()
+}
@@ -0,0 +1,102 @@
+use crate::events::RegistryEvent;
+use crate::models::get_image_name;
+use crate::models::registry::{normalize_key_path, IRegistryKeyNode, RegistryKeyNode};
+use crate::models::utc_to_epoch;
+use grapl_graph_descriptions::graph_description::*;
+use grapl_graph_descriptions::node::NodeT;
+use grapl_graph_descriptions::process::ProcessState;
+
+/// Creates a subgraph describing a `RegistryEvent`
+///
+/// Subgraph generation for a `RegistryEvent` includes the following:
+/// * An `Asset` node - indicating the asset on which the registry was modified
+/// * A modifying `Process` node - indicating the process that modified the registry
+/// * A `RegistryKey` node - the key or value that was created, deleted, set or renamed
+/// * For renames, a second `RegistryKey` node for the new name
+pub fn generate_registry_subgraph(registry_event: &RegistryEvent) -> Result<Graph, failure::Error> {
+    let timestamp = utc_to_epoch(&registry_event.event_data.utc_time)?;
+    let asset_id = registry_event.system.computer.computer.clone();
+    let mut graph = Graph::new(timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(asset_id.clone())
+        .hostname(asset_id.clone())
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let modifier = ProcessBuilder::default()
+        .asset_id(asset_id.clone())
+        .state(ProcessState::Existing)
+        .process_id(registry_event.event_data.process_id)
+        .process_guid(registry_event.event_data.process_guid.clone())
+        .process_name(get_image_name(&registry_event.event_data.image).unwrap_or_default())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .map_err(|err| failure::err_msg(err))?;
+
+    let mut registry_key = registry_key_node(
+        &asset_id,
+        &registry_event.event_data.target_object,
+        timestamp,
+    );
+    registry_key.with_last_event_type(registry_event.event_data.event_type.clone());
+
+    if registry_event.is_create() {
+        registry_key.with_created_timestamp(timestamp);
+    }
+
+    if registry_event.is_delete() {
+        registry_key.with_deleted_timestamp(timestamp);
+    }
+
+    if let Some(ref details) = registry_event.event_data.details {
+        registry_key.with_value_data(details.clone());
+    }
+
+    if let Some(ref new_name) = registry_event.event_data.new_name {
+        registry_key.with_renamed_to(new_name.clone());
+
+        let mut renamed_key = registry_key_node(&asset_id, new_name, timestamp);
+        renamed_key
+            .with_last_event_type(registry_event.event_data.event_type.clone())
+            .with_created_timestamp(timestamp);
+
+        graph.add_edge(
+            "modified_registry_key",
+            modifier.clone_node_key(),
+            renamed_key.clone_node_key(),
+        );
+
+        graph.add_node(renamed_key);
+    }
+
+    graph.add_edge(
+        "process_asset",
+        modifier.clone_node_key(),
+        asset.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "modified_registry_key",
+        modifier.clone_node_key(),
+        registry_key.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(modifier);
+    graph.add_node(registry_key);
+
+    Ok(graph)
+}
+
+fn registry_key_node(asset_id: &str, key_path: &str, timestamp: u64) -> RegistryKeyNode {
+    let mut registry_key = RegistryKeyNode::new(RegistryKeyNode::static_strategy(), timestamp);
+
+    registry_key
+        .with_asset_id(asset_id.to_string())
+        .with_key_path(key_path)
+        .with_normalized_key_path(normalize_key_path(key_path))
+        .with_last_seen_timestamp(timestamp);
+
+    registry_key
+}
@@ -0,0 +1,520 @@
+use crate::events::{RawEvent, SysmonEvent};
+use crate::filters::EventIdFilter;
+use crate::generator::{events_to_subgraph, parse_events, ParsedEvent};
+use crate::models::{parse_hashes, SysmonTryFrom};
+use grapl_graph_descriptions::file::FileState;
+use grapl_graph_descriptions::graph_description::*;
+
+#[test]
+fn parse_hashes_extracts_known_algorithms() {
+    let hashes = parse_hashes("SHA1=AB12,MD5=CD34,SHA256=EF56,IMPHASH=0000");
+
+    assert_eq!(hashes.md5, "cd34");
+    assert_eq!(hashes.sha1, "ab12");
+    assert_eq!(hashes.sha256, "ef56");
+}
+
+#[test]
+fn event_id_filter_only_enables_supported_ids() {
+    let default_filter = EventIdFilter::default();
+    assert!(default_filter.is_enabled(1));
+    assert!(default_filter.is_enabled(22));
+    assert!(!default_filter.is_enabled(5));
+
+    let configured = EventIdFilter::new(vec![1, 5, 7]);
+    assert!(configured.is_enabled(1));
+    assert!(configured.is_enabled(7));
+    assert!(!configured.is_enabled(5));
+    assert!(!configured.is_enabled(22));
+}
+
+#[test]
+fn raw_event_id_is_parsed_from_header() {
+    let raw = RawEvent::from_str(&read_test_data("image_load.xml")).expect("Failed to parse");
+
+    assert_eq!(raw.event_id(), 7);
+}
+
+#[test]
+fn image_load_event_to_subgraph() {
+    let raw = read_test_data("image_load.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse image load event") {
+        SysmonEvent::ImageLoad(event) => event,
+        other => panic!("Expected ImageLoad, got {:?}", other),
+    };
+
+    assert_eq!(event.event_data.process_id, 6580);
+    assert!(!event.is_validly_signed());
+
+    let graph = Graph::try_from(SysmonEvent::ImageLoad(event)).expect("Failed to build subgraph");
+
+    let module = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_file())
+        .next()
+        .expect("Missing loaded module");
+
+    assert_eq!(
+        module.file_path,
+        r"C:\Users\grapltest\AppData\Local\Temp\version.dll"
+    );
+    assert_eq!(
+        module.sha256_hash,
+        "6055a20cf7ec81843310ad37700ff67b2cf8cde3dce68d54ba42934177c10b57"
+    );
+
+    let loader = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_process())
+        .next()
+        .expect("Missing loading process");
+
+    assert_eq!(loader.process_guid, "87e8d3bd-9dda-5d38-0000-00109d2f1200");
+
+    let loaded_modules = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .filter(|edge| edge.edge_name == "loaded_modules")
+        .count();
+
+    assert_eq!(loaded_modules, 1);
+}
+
+#[test]
+fn registry_value_set_event_to_subgraph() {
+    let raw = read_test_data("registry_value_set.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse registry event") {
+        SysmonEvent::Registry(event) => event,
+        other => panic!("Expected Registry, got {:?}", other),
+    };
+
+    assert_eq!(event.event_data.event_type, "SetValue");
+    assert!(!event.is_delete());
+
+    let graph = Graph::try_from(SysmonEvent::Registry(event)).expect("Failed to build subgraph");
+
+    let registry_key = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_dynamic_node())
+        .next()
+        .expect("Missing registry key");
+
+    assert_eq!(registry_key.node_type, "RegistryKey");
+    assert_eq!(
+        registry_key.properties["normalized_key_path"]
+            .as_str_prop()
+            .unwrap(),
+        r"hku\s-1-5-21-2337462296-1234567890-987654321-1001\software\microsoft\windows\currentversion\run\updater"
+    );
+    assert_eq!(
+        registry_key.properties["value_data"].as_str_prop().unwrap(),
+        r"C:\Users\grapltest\AppData\Local\Temp\updater.exe"
+    );
+
+    let modified_registry_keys = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .filter(|edge| edge.edge_name == "modified_registry_key")
+        .count();
+
+    assert_eq!(modified_registry_keys, 1);
+}
+
+#[test]
+fn dns_query_event_to_subgraph() {
+    let raw = read_test_data("dns_query.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse dns query event") {
+        SysmonEvent::DnsQuery(event) => event,
+        other => panic!("Expected DnsQuery, got {:?}", other),
+    };
+
+    assert_eq!(
+        event.resolved_ips(),
+        vec!["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"]
+    );
+
+    let graph = Graph::try_from(SysmonEvent::DnsQuery(event)).expect("Failed to build subgraph");
+
+    let query = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_dynamic_node())
+        .next()
+        .expect("Missing dns query");
+
+    assert_eq!(query.node_type, "DnsQuery");
+    assert_eq!(
+        query.properties["query_name"].as_str_prop().unwrap(),
+        "www.example.com"
+    );
+
+    let ip_addresses = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_ip_address())
+        .count();
+
+    assert_eq!(ip_addresses, 2);
+
+    let resolved_to = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .filter(|edge| edge.edge_name == "dns_query_resolved_to")
+        .count();
+
+    assert_eq!(resolved_to, 2);
+}
+
+#[test]
+fn process_access_event_to_subgraph() {
+    let raw = read_test_data("process_access.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse process access event") {
+        SysmonEvent::ProcessAccess(event) => event,
+        other => panic!("Expected ProcessAccess, got {:?}", other),
+    };
+
+    assert_eq!(event.event_data.target_process_id, 620);
+
+    let graph =
+        Graph::try_from(SysmonEvent::ProcessAccess(event)).expect("Failed to build subgraph");
+
+    let access = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_dynamic_node())
+        .next()
+        .expect("Missing process access");
+
+    assert_eq!(access.node_type, "ProcessAccess");
+    assert_eq!(
+        access.properties["granted_access"].as_str_prop().unwrap(),
+        "0x1010"
+    );
+
+    let accessed_process = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .find(|edge| edge.edge_name == "accessed_process")
+        .expect("Missing accessed_process edge");
+
+    let target = graph.nodes[&accessed_process.to]
+        .as_process()
+        .expect("accessed_process should point to a Process");
+
+    assert_eq!(target.process_id, 620);
+}
+
+#[test]
+fn create_remote_thread_event_to_subgraph() {
+    let raw = read_test_data("create_remote_thread.xml");
+
+    let event =
+        match SysmonEvent::from_str(raw).expect("Failed to parse create remote thread event") {
+            SysmonEvent::CreateRemoteThread(event) => event,
+            other => panic!("Expected CreateRemoteThread, got {:?}", other),
+        };
+
+    assert_eq!(event.event_data.new_thread_id, 7312);
+
+    let graph =
+        Graph::try_from(SysmonEvent::CreateRemoteThread(event)).expect("Failed to build subgraph");
+
+    let thread = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_dynamic_node())
+        .next()
+        .expect("Missing remote thread");
+
+    assert_eq!(thread.node_type, "RemoteThread");
+    assert_eq!(
+        thread.properties["start_function"].as_str_prop().unwrap(),
+        "LoadLibraryW"
+    );
+
+    let injected_into = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .find(|edge| edge.edge_name == "injected_into")
+        .expect("Missing injected_into edge");
+
+    let target = graph.nodes[&injected_into.to]
+        .as_process()
+        .expect("injected_into should point to a Process");
+
+    assert_eq!(target.process_id, 4256);
+}
+
+#[test]
+fn file_create_time_event_to_subgraph() {
+    let raw = read_test_data("file_create_time.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse file create time event") {
+        SysmonEvent::FileCreateTime(event) => event,
+        other => panic!("Expected FileCreateTime, got {:?}", other),
+    };
+
+    let graph =
+        Graph::try_from(SysmonEvent::FileCreateTime(event)).expect("Failed to build subgraph");
+
+    let file = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_file())
+        .next()
+        .expect("Missing file");
+
+    assert_eq!(file.changed_creation_timestamp, 1_468_685_941_000);
+    assert_eq!(file.previous_creation_timestamp, 1_563_991_510_512);
+
+    let modified_file_time = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .filter(|edge| edge.edge_name == "modified_file_time")
+        .count();
+
+    assert_eq!(modified_file_time, 1);
+}
+
+#[test]
+fn file_delete_event_to_subgraph() {
+    let raw = read_test_data("file_delete.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse file delete event") {
+        SysmonEvent::FileDelete(event) => event,
+        other => panic!("Expected FileDelete, got {:?}", other),
+    };
+
+    assert!(event.event_data.archived);
+
+    let graph = Graph::try_from(SysmonEvent::FileDelete(event)).expect("Failed to build subgraph");
+
+    let file = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_file())
+        .next()
+        .expect("Missing deleted file");
+
+    assert_eq!(file.state, u32::from(FileState::Deleted));
+    assert_eq!(file.deleted_timestamp, 1_563_991_760_417);
+
+    let deleted_files = graph
+        .edges
+        .values()
+        .flat_map(|edge_list| edge_list.edges.iter())
+        .filter(|edge| edge.edge_name == "deleted_files")
+        .count();
+
+    assert_eq!(deleted_files, 1);
+
+    // FileDeleteDetected logs the same deletion, without archiving the file
+    let detected = read_test_data("file_delete.xml")
+        .replace("<EventID>23</EventID>", "<EventID>26</EventID>")
+        .replace(r#"<Data Name="Archived">true</Data>"#, "");
+
+    match SysmonEvent::from_str(detected).expect("Failed to parse file delete detected event") {
+        SysmonEvent::FileDelete(event) => assert!(!event.event_data.archived),
+        other => panic!("Expected FileDelete, got {:?}", other),
+    }
+}
+
+#[test]
+fn process_create_event_to_subgraph() {
+    let raw = read_test_data("process_create.xml");
+
+    let event = match SysmonEvent::from_str(raw).expect("Failed to parse process create event") {
+        SysmonEvent::ProcessCreate(event) => event,
+        other => panic!("Expected ProcessCreate, got {:?}", other),
+    };
+
+    assert_eq!(
+        event.parent_user.as_deref(),
+        Some(r"DESKTOP-FVSHABR\grapltest")
+    );
+
+    let graph =
+        Graph::try_from(SysmonEvent::ProcessCreate(event)).expect("Failed to build subgraph");
+
+    let child = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_process())
+        .find(|process| process.process_id == 6580)
+        .expect("Missing child process");
+
+    assert_eq!(child.process_user, r"DESKTOP-FVSHABR\grapltest");
+    assert_eq!(child.process_integrity_level, "Medium");
+    assert_eq!(
+        child.process_current_directory,
+        r"C:\Users\grapltest\AppData\Local\Temp\"
+    );
+
+    let child_exe = graph
+        .nodes
+        .values()
+        .filter_map(|node| node.as_file())
+        .next()
+        .expect("Missing child binary");
+
+    assert_eq!(child_exe.md5_hash, "80f8e0c26028e83f1ef371d7b44de3df");
+    assert_eq!(
+        child_exe.sha1_hash,
+        "6778dad71c8b06264cf2929a5242d2612d3eb026"
+    );
+}
+
+#[test]
+fn json_process_create_events_match_xml() {
+    let summarize = |filename: &str| {
+        let event = SysmonEvent::from_str(read_test_data(filename))
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", filename, e));
+        let graph = Graph::try_from(event).expect("Failed to build subgraph");
+
+        let mut processes: Vec<_> = graph
+            .nodes
+            .values()
+            .filter_map(|node| node.as_process())
+            .map(|process| {
+                (
+                    process.asset_id.clone(),
+                    process.process_guid.clone(),
+                    process.process_id,
+                    process.process_name.clone(),
+                    process.process_user.clone(),
+                    process.created_timestamp,
+                )
+            })
+            .collect();
+        processes.sort();
+
+        let mut files: Vec<_> = graph
+            .nodes
+            .values()
+            .filter_map(|node| node.as_file())
+            .map(|file| (file.file_path.clone(), file.sha256_hash.clone()))
+            .collect();
+        files.sort();
+
+        (processes, files, graph.edges.len())
+    };
+
+    let from_xml = summarize("process_create.xml");
+
+    assert_eq!(summarize("winlogbeat_process_create.json"), from_xml);
+    assert_eq!(summarize("nxlog_process_create.json"), from_xml);
+}
+
+#[test]
+fn parallel_conversion_matches_sequential_merge() {
+    let batch = large_batch(50);
+    let events: Vec<SysmonEvent> = parse_events(&EventIdFilter::default(), &batch)
+        .into_iter()
+        .map(|parsed| match parsed {
+            ParsedEvent::Event(event) => *event,
+            ParsedEvent::SkippedByPolicy(id) => panic!("Skipped event id {}", id),
+            ParsedEvent::Failed(e) => panic!("Failed to parse: {}", e),
+        })
+        .collect();
+
+    let mut sequential = Graph::new(0);
+    for event in events.iter() {
+        sequential.merge(&Graph::try_from(event.clone()).expect("Failed to build subgraph"));
+    }
+
+    let converted = events_to_subgraph(events.clone());
+
+    assert!(converted.last_failure.is_none());
+    assert_eq!(converted.identities.len(), events.len());
+    assert_eq!(converted.graph.nodes.len(), sequential.nodes.len());
+    assert_eq!(converted.graph.edges.len(), sequential.edges.len());
+}
+
+/// Compares parsing and converting a large batch on a single thread against rayon's default
+/// pool. Run with `cargo test --release -- --ignored --nocapture bench_`.
+#[test]
+#[ignore]
+fn bench_parallel_process_events() {
+    let batch = large_batch(2_000);
+    let filter = EventIdFilter::default();
+
+    let run = |pool: &rayon::ThreadPool| {
+        pool.install(|| {
+            let start = std::time::Instant::now();
+
+            let events: Vec<_> = parse_events(&filter, &batch)
+                .into_iter()
+                .filter_map(|parsed| match parsed {
+                    ParsedEvent::Event(event) => Some(*event),
+                    _ => None,
+                })
+                .collect();
+            let converted = events_to_subgraph(events);
+
+            (start.elapsed(), converted.identities.len())
+        })
+    };
+
+    let single = rayon::ThreadPoolBuilder::new()
+        .num_threads(1)
+        .build()
+        .expect("Failed to build thread pool");
+    let parallel = rayon::ThreadPoolBuilder::new()
+        .build()
+        .expect("Failed to build thread pool");
+
+    let (single_elapsed, single_count) = run(&single);
+    let (parallel_elapsed, parallel_count) = run(&parallel);
+
+    assert_eq!(single_count, parallel_count);
+
+    println!(
+        "{} events: 1 thread {:?}, {} threads {:?} ({:.2}x)",
+        batch.len(),
+        single_elapsed,
+        parallel.current_num_threads(),
+        parallel_elapsed,
+        single_elapsed.as_secs_f64() / parallel_elapsed.as_secs_f64()
+    );
+}
+
+/// Builds a batch of `copies` of every event fixture, one event per line
+fn large_batch(copies: usize) -> Vec<std::borrow::Cow<'static, str>> {
+    let fixtures: Vec<String> = [
+        "process_create.xml",
+        "winlogbeat_process_create.json",
+        "nxlog_process_create.json",
+        "image_load.xml",
+        "registry_value_set.xml",
+        "dns_query.xml",
+        "process_access.xml",
+        "create_remote_thread.xml",
+        "file_create_time.xml",
+        "file_delete.xml",
+    ]
+    .iter()
+    .map(|filename| read_test_data(filename).trim().to_owned())
+    .collect();
+
+    (0..copies)
+        .flat_map(|_| fixtures.iter().cloned().map(std::borrow::Cow::Owned))
+        .collect()
+}
+
+pub(crate) fn read_test_data(filename: &str) -> String {
+    std::fs::read_to_string(format!("test_data/{filename}", filename = filename))
+        .expect("Failed to read test data")
+}